@@ -94,11 +94,15 @@ where
     C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
     C::Api: BlockBuilder<Block>,
     C::Api: BabeApi<Block>,
+    C::Api: pallet_provider_rpc::ProviderRuntimeApi<Block, AccountId, BlockNumber>,
+    C::Api: pallet_resource_order_rpc::ResourceOrderRuntimeApi<Block, AccountId>,
     P: TransactionPool + 'static,
     SC: SelectChain<Block> + 'static,
     B: sc_client_api::Backend<Block> + Send + Sync + 'static,
     B::State: sc_client_api::backend::StateBackend<sp_runtime::traits::HashFor<Block>>,
 {
+    use pallet_provider_rpc::{Provider, ProviderApi};
+    use pallet_resource_order_rpc::{ResourceOrder, ResourceOrderApi};
     use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
     use substrate_frame_rpc_system::{FullSystem, SystemApi};
 
@@ -143,6 +147,12 @@ where
         client.clone(),
     )));
 
+    io.extend_with(ProviderApi::to_delegate(Provider::new(client.clone())));
+
+    io.extend_with(ResourceOrderApi::to_delegate(ResourceOrder::new(
+        client.clone(),
+    )));
+
     io.extend_with(sc_consensus_babe_rpc::BabeApi::to_delegate(
         BabeRpcHandler::new(
             client.clone(),
@@ -6,9 +6,10 @@ use frame_support::traits::Currency;
 use frame_system::pallet_prelude::*;
 pub use pallet::*;
 use primitives::p_market::MarketInterface;
+use primitives::p_peer_id::PeerId;
 use primitives::p_resource_order::RentalAgreement;
 use primitives::{p_chunkcycle::*, p_gateway::*, p_provider::*};
-use sp_runtime::traits::Saturating;
+use sp_runtime::traits::{Saturating, Zero};
 use sp_runtime::Perbill;
 pub use sp_std::vec::Vec;
 
@@ -43,6 +44,9 @@ pub mod pallet {
         type MarketInterface: MarketInterface<Self::AccountId>;
 
         type GatewayInterface: GatewayInterface<Self::AccountId, Self::BlockNumber>;
+
+        /// provider reputation used to weight the provider rewards
+        type ProviderInterface: ProviderInterface<Self::AccountId>;
     }
 
     #[pallet::pallet]
@@ -139,11 +143,7 @@ impl<T: Config> Pallet<T> {
 
     /// gateway_list: [(accountId, peer ids), (peer ids, online time), total gateway online time ]
     pub fn compute_gateway(
-        _gateway_list: (
-            Vec<(T::AccountId, Vec<Vec<u8>>)>,
-            Vec<(Vec<u8>, u128)>,
-            u128,
-        ),
+        _gateway_list: (Vec<(T::AccountId, Vec<PeerId>)>, Vec<(PeerId, u128)>, u128),
         payout: u128,
         for_index: u128,
     ) -> u128 {
@@ -199,16 +199,13 @@ impl<T: Config> Pallet<T> {
     ) -> u128 {
         let list = provider_list.0;
         let total_resource = provider_list.1;
-        let provider_count = provider_list.2;
+        let total_duration = provider_list.2;
 
         // 0. compute resource reward and time reward
         let resource_reward = Perbill::from_percent(60) * T::NumberToBalance::convert(payout);
         let time_reward = Perbill::from_percent(40) * T::NumberToBalance::convert(payout);
 
-        // 1. compute the time reward
-        let t_reward = Perbill::from_rational(1, provider_count) * time_reward;
-
-        // 2. compute the resource reward, start from the for index and end at the for index + FORBLOCK(if enough)
+        // 1. compute the rewards, start from the for index and end at the for index + FORBLOCK(if enough)
         let mut cycle_time = 0;
         for (who, points) in list.iter().skip(for_index as usize) {
             // code the cycle time
@@ -218,8 +215,18 @@ impl<T: Config> Pallet<T> {
                 Perbill::from_rational(points.resource_points, total_resource as u64);
             // compute the resource reward
             let r_reward = resource_part * resource_reward;
-            // get the total reward
-            let total_reward = r_reward.saturating_add(t_reward);
+            // 3. compute the time reward in proportion to the duration points,
+            // nothing is paid when no provider has been online for an hour
+            let t_reward = if total_duration == 0 {
+                Zero::zero()
+            } else {
+                Perbill::from_rational(points.duration_points as u128, total_duration) * time_reward
+            };
+            // get the total reward, a provider below the neutral reputation gets a part of it
+            let reputation = T::ProviderInterface::get_provider_reputation(who);
+            let total_reward =
+                Perbill::from_rational(reputation.min(NEUTRAL_REPUTATION), NEUTRAL_REPUTATION)
+                    * r_reward.saturating_add(t_reward);
             // save the provider reward
             T::MarketInterface::update_provider_income(
                 who.clone(),
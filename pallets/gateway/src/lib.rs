@@ -19,6 +19,7 @@ pub use pallet::*;
 pub use primitives::p_chunkcycle::*;
 pub use primitives::p_gateway::*;
 pub use primitives::p_market::*;
+pub use primitives::p_peer_id::{LivenessPayload, LivenessProof, PeerId, MAX_PEER_ID_LEN};
 use sp_runtime::traits::Zero;
 use sp_std::vec::Vec;
 
@@ -56,6 +57,9 @@ pub mod pallet {
         type GatewayNodeHeartbeatInterval: Get<Self::BlockNumber>;
 
         type MarketInterface: MarketInterface<Self::AccountId>;
+
+        /// operator interface, resolves the accounts allowed to act for a gateway owner
+        type OperatorInterface: OperatorInterface<Self::AccountId>;
     }
 
     #[pallet::pallet]
@@ -65,32 +69,27 @@ pub mod pallet {
     /// gateway node information
     #[pallet::storage]
     #[pallet::getter(fn gateway)]
-    pub(super) type GatewayNodes<T: Config> = StorageMap<
-        _,
-        Twox64Concat,
-        Vec<u8>,
-        GatewayNode<T::BlockNumber, T::AccountId>,
-        OptionQuery,
-    >;
+    pub(super) type GatewayNodes<T: Config> =
+        StorageMap<_, Twox64Concat, PeerId, GatewayNode<T::BlockNumber, T::AccountId>, OptionQuery>;
 
     ///list of gateway nodes
     #[pallet::storage]
     #[pallet::getter(fn gateways)]
-    pub(super) type Gateways<T: Config> = StorageValue<_, Vec<Vec<u8>>, ValueQuery>;
+    pub(super) type Gateways<T: Config> = StorageValue<_, Vec<PeerId>, ValueQuery>;
 
     /// Account and peer id map
     /// Use to map the account to the peer id
     #[pallet::storage]
     #[pallet::getter(fn account_peerid_map)]
     pub(super) type AccountPeerMap<T: Config> =
-        StorageMap<_, Twox64Concat, T::AccountId, Vec<Vec<u8>>, OptionQuery>;
+        StorageMap<_, Twox64Concat, T::AccountId, Vec<PeerId>, OptionQuery>;
 
     /// number of gateway nodes
     #[pallet::storage]
     #[pallet::getter(fn gateway_node_count)]
     pub(super) type GatewayNodeCount<T: Config> = StorageValue<_, u64, ValueQuery>;
 
-    /// This is set to v1.0.0 for new networks.
+    /// This is set to v2.0.0 for new networks.
     #[pallet::storage]
     #[pallet::getter(fn storage_version)]
     pub(crate) type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
@@ -99,20 +98,32 @@ pub mod pallet {
     #[pallet::storage]
     #[pallet::getter(fn gateway_node_register_time)]
     pub(super) type GatewayNodeRegisterTime<T: Config> =
-        StorageMap<_, Twox64Concat, Vec<u8>, T::BlockNumber, OptionQuery>;
+        StorageMap<_, Twox64Concat, PeerId, T::BlockNumber, OptionQuery>;
 
     /// The total online time on the current era of all the gateway nodes
     #[pallet::storage]
     #[pallet::getter(fn total_online_time)]
     pub(super) type TotalOnlineTime<T: Config> = StorageValue<_, u128, ValueQuery>;
 
+    /// gateway nodes registered before the peer ids were validated whose peer id is not valid
+    #[pallet::storage]
+    #[pallet::getter(fn invalid_peer_ids)]
+    pub(super) type InvalidPeerIds<T: Config> =
+        StorageMap<_, Twox64Concat, PeerId, bool, ValueQuery>;
+
+    /// the block of the last liveness proof of the gateway node
+    #[pallet::storage]
+    #[pallet::getter(fn liveness_proofs)]
+    pub(super) type LivenessProofs<T: Config> =
+        StorageMap<_, Twox64Concat, PeerId, T::BlockNumber, OptionQuery>;
+
     // The genesis config type.
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
-        pub gateway: Vec<(Vec<u8>, GatewayNode<T::BlockNumber, T::AccountId>)>,
+        pub gateway: Vec<(PeerId, GatewayNode<T::BlockNumber, T::AccountId>)>,
         pub gateway_node_count: u64,
-        pub account_peer_map: Vec<(T::AccountId, Vec<Vec<u8>>)>,
-        pub gateways: Vec<Vec<u8>>,
+        pub account_peer_map: Vec<(T::AccountId, Vec<PeerId>)>,
+        pub gateways: Vec<PeerId>,
     }
 
     // The default value for the genesis config type.
@@ -141,6 +152,7 @@ pub mod pallet {
             }
 
             <Gateways<T>>::set(self.gateways.clone());
+            <StorageVersion<T>>::put(Releases::V2_0_0);
         }
     }
 
@@ -152,9 +164,11 @@ pub mod pallet {
     pub enum Event<T: Config> {
         /// successfully registered resources
         /// [accountId, registration_time, peerId, ]
-        RegisterGatewayNodeSuccess(T::AccountId, T::BlockNumber, Vec<u8>),
+        RegisterGatewayNodeSuccess(T::AccountId, T::BlockNumber, PeerId),
         /// health check successfully [accountId, registration_time]
         HealthCheckSuccess(T::AccountId, T::BlockNumber),
+        /// the gateway node signed the heartbeat with its peer key [peerId, block of the proof]
+        LivenessProven(PeerId, T::BlockNumber),
     }
 
     // Errors inform users that something went wrong.
@@ -172,6 +186,14 @@ pub mod pallet {
         StakingNotExit,
 
         LockAmountFailed,
+        /// the peer id is not a libp2p peer id
+        InvalidPeerId,
+        /// the peer id does not inline an ed25519 key to verify the proof with
+        PeerKeyUnavailable,
+        /// the proof is not signed by the peer key
+        InvalidLivenessProof,
+        /// the block of the proof is not recent or not after the last proof
+        StaleLivenessProof,
     }
 
     #[pallet::hooks]
@@ -198,6 +220,14 @@ pub mod pallet {
             }
             0
         }
+
+        fn on_runtime_upgrade() -> Weight {
+            let mut weight = T::DbWeight::get().reads(1);
+            if StorageVersion::<T>::get() == Releases::V1_0_0 {
+                weight = weight.saturating_add(migrations::v2::migrate::<T>());
+            }
+            weight
+        }
     }
 
     // Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -205,12 +235,15 @@ pub mod pallet {
     // Dispatchable functions must be annotated with a weight and must return a DispatchResult.
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        /// register gateway node
+        /// register gateway node, the peer id is base58 encoded or a raw multihash
         #[transactional]
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
         pub fn register_gateway_node(account_id: OriginFor<T>, peer_id: Vec<u8>) -> DispatchResult {
             let who = ensure_signed(account_id)?;
 
+            // 0. check the peer id
+            let peer_id = PeerId::parse(&peer_id).map_err(|_| Error::<T>::InvalidPeerId)?;
+
             // 1. check the gateway node nus now < 1000
             let gateway_node_count = GatewayNodeCount::<T>::get();
             if gateway_node_count >= GATEWAY_LIMIT {
@@ -278,21 +311,39 @@ pub mod pallet {
         }
 
         /// gateway node heartbeat
+        /// * proof: optional payload signed by the peer key of the gateway node
         #[transactional]
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
-        pub fn heartbeat(origin: OriginFor<T>, peer_id: Vec<u8>) -> DispatchResult {
+        pub fn heartbeat(
+            origin: OriginFor<T>,
+            peer_id: PeerId,
+            proof: Option<LivenessProof<T::BlockNumber>>,
+        ) -> DispatchResult {
             let who = ensure_signed(origin)?;
+            // the peer ids stored before the validation are unbounded, they only go offline
+            ensure!(
+                peer_id.as_bytes().len() <= MAX_PEER_ID_LEN,
+                Error::<T>::InvalidPeerId
+            );
             // get gateway node
             ensure!(
                 GatewayNodes::<T>::contains_key(peer_id.clone()),
                 Error::<T>::GatewayNodeNotFound
             );
             let mut gateway_node = GatewayNodes::<T>::get(peer_id.clone()).unwrap();
-            // determine whether it is me
+            // determine whether it is me or my operator
             ensure!(
-                who.clone() == gateway_node.account_id,
+                T::OperatorInterface::is_authorized(
+                    &gateway_node.account_id,
+                    &who,
+                    OperatorScope::Gateway(peer_id.clone()),
+                ),
                 Error::<T>::GatewayNodeNotOwnedByYou
             );
+            // check the machine behind the peer id signed the heartbeat
+            if let Some(proof) = proof.as_ref() {
+                Self::check_liveness_proof(&peer_id, proof)?;
+            }
             // get the current block height
             let block_number = <frame_system::Pallet<T>>::block_number();
 
@@ -300,16 +351,23 @@ pub mod pallet {
             gateway_node.registration_time = block_number;
 
             // save the gateway node
-            GatewayNodes::<T>::insert(peer_id, gateway_node.clone());
+            GatewayNodes::<T>::insert(peer_id.clone(), gateway_node.clone());
 
-            Self::deposit_event(Event::HealthCheckSuccess(who.clone(), block_number));
+            Self::deposit_event(Event::HealthCheckSuccess(
+                gateway_node.account_id,
+                block_number,
+            ));
+            if let Some(proof) = proof {
+                LivenessProofs::<T>::insert(peer_id.clone(), proof.block);
+                Self::deposit_event(Event::LivenessProven(peer_id, proof.block));
+            }
             Ok(())
         }
 
         /// Take the specified peer offline
         #[transactional]
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
-        pub fn offline(account_id: OriginFor<T>, peer_id: Vec<u8>) -> DispatchResult {
+        pub fn offline(account_id: OriginFor<T>, peer_id: PeerId) -> DispatchResult {
             let who = ensure_signed(account_id)?;
 
             // 1. check the gateway node exit
@@ -333,7 +391,7 @@ pub mod pallet {
 }
 
 impl<T: Config> Pallet<T> {
-    pub fn offline_gateway_node(who: T::AccountId, peer_id: Vec<u8>) {
+    pub fn offline_gateway_node(who: T::AccountId, peer_id: PeerId) {
         // 1. update the gateway node count
         let gateway_node_count = GatewayNodeCount::<T>::get();
         GatewayNodeCount::<T>::set(gateway_node_count.saturating_sub(1));
@@ -363,6 +421,8 @@ impl<T: Config> Pallet<T> {
         if let Some(_) = GatewayNodeRegisterTime::<T>::get(peer_id.clone()) {
             GatewayNodeRegisterTime::<T>::remove(peer_id.clone());
         }
+        InvalidPeerIds::<T>::remove(peer_id.clone());
+        LivenessProofs::<T>::remove(peer_id.clone());
 
         // 6. sub the total online time
         Self::sub_total_online_time();
@@ -376,8 +436,42 @@ impl<T: Config> Pallet<T> {
         );
     }
 
-    pub fn update_account_peer_map(who: T::AccountId, peer_id: Vec<u8>) {
-        let mut account_peer_map: Vec<Vec<u8>>;
+    /// check the proof is signed by the peer key over a recent block after the last proof.
+    /// the nonce is the hash of that block, gateway nodes sign with the index 0
+    fn check_liveness_proof(
+        peer_id: &PeerId,
+        proof: &LivenessProof<T::BlockNumber>,
+    ) -> DispatchResult {
+        ensure!(
+            proof.block < <frame_system::Pallet<T>>::block_number(),
+            Error::<T>::StaleLivenessProof
+        );
+        if let Some(last) = LivenessProofs::<T>::get(peer_id) {
+            ensure!(proof.block > last, Error::<T>::StaleLivenessProof);
+        }
+        // the hashes of the blocks older than BlockHashCount are pruned
+        let nonce = <frame_system::Pallet<T>>::block_hash(proof.block);
+        ensure!(nonce != Default::default(), Error::<T>::StaleLivenessProof);
+
+        ensure!(
+            peer_id.ed25519_public().is_some(),
+            Error::<T>::PeerKeyUnavailable
+        );
+        let payload = LivenessPayload {
+            index: 0,
+            block: proof.block,
+            nonce,
+        };
+        ensure!(
+            peer_id.verify(&payload, &proof.signature),
+            Error::<T>::InvalidLivenessProof
+        );
+
+        Ok(())
+    }
+
+    pub fn update_account_peer_map(who: T::AccountId, peer_id: PeerId) {
+        let mut account_peer_map: Vec<PeerId>;
 
         if AccountPeerMap::<T>::contains_key(who.clone()) {
             account_peer_map = AccountPeerMap::<T>::get(who.clone()).unwrap();
@@ -415,7 +509,7 @@ impl<T: Config>
         <T as frame_system::Config>::BlockNumber,
     > for Pallet<T>
 {
-    fn account_own_peerid(who: <T as frame_system::Config>::AccountId, peerid: Vec<u8>) -> bool {
+    fn account_own_peerid(who: <T as frame_system::Config>::AccountId, peerid: PeerId) -> bool {
         if !AccountPeerMap::<T>::contains_key(who.clone()) {
             return false;
         }
@@ -435,8 +529,8 @@ impl<T: Config>
     /// * the gateway node register time
     /// * the total online time
     fn gateway_online_list() -> (
-        Vec<(<T as frame_system::Config>::AccountId, Vec<Vec<u8>>)>,
-        Vec<(Vec<u8>, u128)>,
+        Vec<(<T as frame_system::Config>::AccountId, Vec<PeerId>)>,
+        Vec<(PeerId, u128)>,
         u128,
     ) {
         let total_online_time = TotalOnlineTime::<T>::get();
@@ -458,7 +552,7 @@ impl<T: Config>
     }
 
     // Update the gateway node register time on the current era
-    fn update_gateway_node_register_time(peer_id: Vec<u8>) {
+    fn update_gateway_node_register_time(peer_id: PeerId) {
         // if the gateway node still online, update the register time
         if let Some(_) = GatewayNodeRegisterTime::<T>::get(peer_id.clone()) {
             let block_number = <frame_system::Pallet<T>>::block_number();
@@ -482,5 +576,45 @@ impl Default for Releases {
 
 pub mod migrations {
 
-    pub mod v2 {}
+    pub mod v2 {
+        use super::super::*;
+
+        use frame_support::storage::{migration::storage_key_iter, StoragePrefixedMap};
+
+        /// gateway node with the peer id stored as raw bytes, of any length
+        #[derive(Decode)]
+        struct OldGatewayNode<BlockNumber, AccountId> {
+            _account_id: AccountId,
+            _peer_id: Vec<u8>,
+            _registration_time: BlockNumber,
+        }
+
+        /// flag the gateway nodes whose peer id is not a valid libp2p peer id.
+        /// the keys are read as raw bytes so that no node is skipped.
+        /// they are kept, the owner takes them offline and registers them again
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+            let mut flagged: u64 = 0;
+
+            for (peer_id, _) in storage_key_iter::<
+                Vec<u8>,
+                OldGatewayNode<T::BlockNumber, T::AccountId>,
+                Twox64Concat,
+            >(
+                GatewayNodes::<T>::module_prefix(),
+                GatewayNodes::<T>::storage_prefix(),
+            ) {
+                count += 1;
+                let peer_id = PeerId::new_unchecked(peer_id);
+                if !peer_id.is_valid() {
+                    flagged += 1;
+                    InvalidPeerIds::<T>::insert(peer_id, true);
+                }
+            }
+
+            StorageVersion::<T>::put(Releases::V2_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, flagged + 1)
+        }
+    }
 }
@@ -25,11 +25,48 @@ pub const MILLISECS_PER_BLOCK: u64 = 6000;
 pub const MINUTES: BlockNumber = 60_000 / (MILLISECS_PER_BLOCK as BlockNumber);
 pub const HOURS: BlockNumber = MINUTES * 60;
 
+/// base58 encoded libp2p peer ids, in ascending order
+pub const PEER_ID_1: &str = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e";
+pub const PEER_ID_2: &str = "12D3KooW9xCm2jWjNVrwh51SWCQBMYdMyeU3NpT85QhLVkF6PcNM";
+
+pub fn peer_id(peer_id: &str) -> PeerId {
+    PeerId::parse(peer_id.as_bytes()).unwrap()
+}
+
 parameter_types! {
     // polling interval
     pub const ResourceInterval: BlockNumber = 3 * HOURS;
+    // maximum number of resources expired in a block
+    pub const MaxExpirationsPerBlock: u32 = 100;
+    // the reputation decays a tenth of the way to neutral every period
+    pub const ReputationDecayPeriod: BlockNumber = HOURS;
+    pub const MaxBatchResources: u32 = 10;
+    pub const ProbationPeriod: BlockNumber = HOURS;
+    pub const ProbationHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // the auto extension is attempted this long before the end of rent
+    pub const AutoExtendWindow: BlockNumber = 10 * MINUTES;
+    pub const MaxStatusHistory: u32 = 10;
     // health check interval
     pub const HealthCheckInterval: BlockNumber = 10 * MINUTES;
+    // maximum duration of a free resource trial (hours)
+    pub const MaxFreeResourceDuration: u32 = 24;
+    // maximum number of agreements checked for missed heartbeats in one block
+    pub const MaxHealthChecksPerBlock: u32 = 100;
+    // share of the remaining rent paid to the provider on early termination
+    pub const EarlyTerminationFee: sp_runtime::Perbill = sp_runtime::Perbill::from_percent(10);
+    // waiting time of a pending order
+    pub const OrderWaitingTime: BlockNumber = 30 * MINUTES;
+    // how long an order waits for a resource meeting its requirement
+    pub const OrderMatchWaitingTime: BlockNumber = HOURS;
+    pub const MaxWaitingOrders: u32 = 10;
+    // waiting orders matched in a block
+    pub const MaxWaitingOrderMatches: u32 = 10;
+    // longest bidding on a compute request
+    pub const MaxBiddingPeriod: BlockNumber = HOURS;
+    pub const MaxBidsPerRequest: u32 = 10;
+    pub const MaxComputeRequestsPerBlock: u32 = 10;
+    // maximum number of resources in a cluster order
+    pub const MaxClusterSize: u32 = 3;
 }
 
 // Configure a mock runtime to test the pallet.
@@ -111,6 +148,7 @@ impl pallet_gateway::Config for Test {
 
     type MarketInterface = Market;
     type BlockNumberToNumber = ConvertInto;
+    type OperatorInterface = Market;
 }
 
 impl pallet_market::Config for Test {
@@ -144,6 +182,15 @@ impl pallet_provider::Config for Test {
     type NumberToBalance = ConvertInto;
     type ResourceInterval = ResourceInterval;
     type MarketInterface = Market;
+    type MaxExpirationsPerBlock = MaxExpirationsPerBlock;
+    type ReputationDecayPeriod = ReputationDecayPeriod;
+    type MaxBatchResources = MaxBatchResources;
+    type ProbationPeriod = ProbationPeriod;
+    type ProbationHeartbeatInterval = ProbationHeartbeatInterval;
+    type MaxStatusHistory = MaxStatusHistory;
+    type AutoExtendWindow = AutoExtendWindow;
+    type ResourceOrderInterface = ResourceOrder;
+    type OperatorInterface = Market;
 }
 
 impl pallet_chunkcycle::Config for Test {
@@ -155,6 +202,7 @@ impl pallet_chunkcycle::Config for Test {
     type BlockNumberToNumber = ConvertInto;
     type MarketInterface = Market;
     type GatewayInterface = Gateway;
+    type ProviderInterface = Provider;
 }
 
 impl pallet_resource_order::Config for Test {
@@ -166,7 +214,20 @@ impl pallet_resource_order::Config for Test {
     type NumberToBalance = ConvertInto;
     type BalanceToNumber = ConvertInto;
     type HealthCheckInterval = HealthCheckInterval;
+    type OrderWaitingTime = OrderWaitingTime;
+    type MaxHealthChecksPerBlock = MaxHealthChecksPerBlock;
+    type EarlyTerminationFee = EarlyTerminationFee;
+    type OperatorInterface = Market;
     type UnixTime = Timestamp;
+    type FreeResourceOrigin = frame_system::EnsureRoot<u64>;
+    type MaxFreeResourceDuration = MaxFreeResourceDuration;
+    type OrderMatchWaitingTime = OrderMatchWaitingTime;
+    type MaxWaitingOrders = MaxWaitingOrders;
+    type MaxWaitingOrderMatches = MaxWaitingOrderMatches;
+    type MaxBiddingPeriod = MaxBiddingPeriod;
+    type MaxBidsPerRequest = MaxBidsPerRequest;
+    type MaxComputeRequestsPerBlock = MaxComputeRequestsPerBlock;
+    type MaxClusterSize = MaxClusterSize;
 }
 
 // Build genesis storage according to the mock runtime.
@@ -198,13 +259,13 @@ pub fn test_offline_ext() -> sp_io::TestExternalities {
         .unwrap()
         .into();
 
-    let gateway_node = node::new(1, "peer_id".as_bytes().to_vec(), 1 as BlockNumber);
+    let gateway_node = node::new(1, peer_id(PEER_ID_1), 1 as BlockNumber);
 
     pallet_gateway::GenesisConfig::<Test> {
-        gateway: vec![("peer_id".as_bytes().to_vec(), gateway_node)],
+        gateway: vec![(peer_id(PEER_ID_1), gateway_node)],
         gateway_node_count: 1,
-        account_peer_map: vec![(1, vec!["peer_id".as_bytes().to_vec()])],
-        gateways: vec!["peer_id".as_bytes().to_vec()],
+        account_peer_map: vec![(1, vec![peer_id(PEER_ID_1)])],
+        gateways: vec![peer_id(PEER_ID_1)],
     }
     .assimilate_storage(&mut t)
     .unwrap();
@@ -219,14 +280,14 @@ pub fn test_hearbreat_ext() -> sp_io::TestExternalities {
         .unwrap()
         .into();
 
-    let peer_id = "peer_id1".as_bytes().to_vec();
+    let peer_id = peer_id(PEER_ID_1);
     let gateway_node: GatewayNode<u64, u64> = node::new(1, peer_id.clone(), 1);
 
     pallet_gateway::GenesisConfig::<Test> {
-        gateway: vec![("peer_id1".as_bytes().to_vec(), gateway_node)],
+        gateway: vec![(peer_id.clone(), gateway_node)],
         gateway_node_count: 1,
-        account_peer_map: vec![(1, vec!["peer_id1".as_bytes().to_vec()])],
-        gateways: vec!["peer_id1".as_bytes().to_vec()],
+        account_peer_map: vec![(1, vec![peer_id.clone()])],
+        gateways: vec![peer_id],
     }
     .assimilate_storage(&mut t)
     .unwrap();
@@ -235,9 +296,6 @@ pub fn test_hearbreat_ext() -> sp_io::TestExternalities {
     ext
 }
 
-
-
-
 // pub fn test_heartbeart_ext() -> sp_io::TestExternalities {
 //     let mut t = system::GenesisConfig::default()
 //         .build_storage::<Test>()
@@ -1,7 +1,12 @@
-use crate::{mock::*, Error};
+use crate::{
+    mock::*, AccountPeerMap, Error, GatewayNodes, LivenessPayload, LivenessProof, PeerId, Releases,
+    StorageVersion,
+};
 use alloc::vec;
-use frame_support::assert_ok;
-use primitives::p_gateway::GatewayNode;
+use codec::Encode;
+use frame_support::{assert_noop, assert_ok};
+use primitives::{p_gateway::GatewayNode, p_peer_id::MAX_PEER_ID_LEN};
+use sp_core::{ed25519, Pair, H256};
 
 #[test]
 fn test_register_for_one_account_one_peerid() {
@@ -12,7 +17,7 @@ fn test_register_for_one_account_one_peerid() {
         println!("{:?}", total_staking);
 
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(1), "1234".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
@@ -26,16 +31,16 @@ fn test_register_for_one_account_one_peerid() {
         // check the Gateways: peerId list
         let peer_ids = Gateway::gateways();
         assert_eq!(peer_ids.len(), 1);
-        assert_eq!(peer_ids[0], "1234".as_bytes().to_vec());
+        assert_eq!(peer_ids[0], peer_id(PEER_ID_1));
 
         // check the AccountPeerMap
         let account_peer_map = Gateway::account_peerid_map(1).unwrap();
         assert_eq!(account_peer_map.len(), 1);
-        assert_eq!(account_peer_map[0], "1234".as_bytes().to_vec());
+        assert_eq!(account_peer_map[0], peer_id(PEER_ID_1));
 
         // check the GatewayNode
-        let gateway_node = Gateway::gateway("1234".as_bytes().to_vec()).unwrap();
-        assert_eq!(gateway_node.peer_id, "1234".as_bytes().to_vec());
+        let gateway_node = Gateway::gateway(peer_id(PEER_ID_1)).unwrap();
+        assert_eq!(gateway_node.peer_id, peer_id(PEER_ID_1));
         assert_eq!(gateway_node.account_id, 1);
         assert_eq!(gateway_node.registration_time, System::block_number());
 
@@ -61,13 +66,13 @@ fn test_register_one_account_n_peerid() {
         System::set_block_number(1);
 
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(1), "peer_id_1".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
 
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(1), "peer_id_2".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_2.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
@@ -78,24 +83,22 @@ fn test_register_one_account_n_peerid() {
         // check the Gateways: peerId list
         let peer_ids = Gateway::gateways();
         assert_eq!(peer_ids.len(), 2);
-        assert_eq!(peer_ids[0], "peer_id_1".as_bytes().to_vec());
-        assert_eq!(peer_ids[1], "peer_id_2".as_bytes().to_vec());
+        assert_eq!(peer_ids[0], peer_id(PEER_ID_1));
+        assert_eq!(peer_ids[1], peer_id(PEER_ID_2));
 
         // check the AccountPeerMap
         let account_peer_map = Gateway::account_peerid_map(1).unwrap();
         assert_eq!(account_peer_map.len(), 2);
-        assert_eq!(account_peer_map[0], "peer_id_1".as_bytes().to_vec());
-        assert_eq!(account_peer_map[1], "peer_id_2".as_bytes().to_vec());
+        assert_eq!(account_peer_map[0], peer_id(PEER_ID_1));
+        assert_eq!(account_peer_map[1], peer_id(PEER_ID_2));
 
         // check the GatewayNode
-        let gateway_node1 = Gateway::gateway("peer_id_1".as_bytes().to_vec()).unwrap();
-        let t_gateway_node1 =
-            GatewayNode::new(1, "peer_id_1".as_bytes().to_vec(), System::block_number());
+        let gateway_node1 = Gateway::gateway(peer_id(PEER_ID_1)).unwrap();
+        let t_gateway_node1 = GatewayNode::new(1, peer_id(PEER_ID_1), System::block_number());
         assert_eq!(gateway_node1, t_gateway_node1);
 
-        let gateway_node2 = Gateway::gateway("peer_id_2".as_bytes().to_vec()).unwrap();
-        let t_gateway_node2 =
-            GatewayNode::new(1, "peer_id_2".as_bytes().to_vec(), System::block_number());
+        let gateway_node2 = Gateway::gateway(peer_id(PEER_ID_2)).unwrap();
+        let t_gateway_node2 = GatewayNode::new(1, peer_id(PEER_ID_2), System::block_number());
         assert_eq!(gateway_node2, t_gateway_node2);
     });
 }
@@ -106,12 +109,12 @@ fn test_register_n_account_n_peerid() {
         System::set_block_number(1);
 
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(1), "peer_id_1".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(2), "peer_id_2".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(2), PEER_ID_2.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
@@ -122,30 +125,25 @@ fn test_register_n_account_n_peerid() {
         // check the Gateways: peerId list
         let peer_ids = Gateway::gateways();
         assert_eq!(peer_ids.len(), 2);
-        let t_peer_ids = vec![
-            "peer_id_1".as_bytes().to_vec(),
-            "peer_id_2".as_bytes().to_vec(),
-        ];
+        let t_peer_ids = vec![peer_id(PEER_ID_1), peer_id(PEER_ID_2)];
         assert_eq!(peer_ids, t_peer_ids);
 
         // check the AccountPeerMap
         let account_peer_map1 = Gateway::account_peerid_map(1).unwrap();
         assert_eq!(account_peer_map1.len(), 1);
-        assert_eq!(account_peer_map1[0], "peer_id_1".as_bytes().to_vec());
+        assert_eq!(account_peer_map1[0], peer_id(PEER_ID_1));
 
         let account_peer_map2 = Gateway::account_peerid_map(2).unwrap();
         assert_eq!(account_peer_map2.len(), 1);
-        assert_eq!(account_peer_map2[0], "peer_id_2".as_bytes().to_vec());
+        assert_eq!(account_peer_map2[0], peer_id(PEER_ID_2));
 
         // check the GatewayNode
-        let gateway_node1 = Gateway::gateway("peer_id_1".as_bytes().to_vec()).unwrap();
-        let t_gateway_node1 =
-            GatewayNode::new(1, "peer_id_1".as_bytes().to_vec(), System::block_number());
+        let gateway_node1 = Gateway::gateway(peer_id(PEER_ID_1)).unwrap();
+        let t_gateway_node1 = GatewayNode::new(1, peer_id(PEER_ID_1), System::block_number());
         assert_eq!(gateway_node1, t_gateway_node1);
 
-        let gateway_node2 = Gateway::gateway("peer_id_2".as_bytes().to_vec()).unwrap();
-        let t_gateway_node2 =
-            GatewayNode::new(2, "peer_id_2".as_bytes().to_vec(), System::block_number());
+        let gateway_node2 = Gateway::gateway(peer_id(PEER_ID_2)).unwrap();
+        let t_gateway_node2 = GatewayNode::new(2, peer_id(PEER_ID_2), System::block_number());
         assert_eq!(gateway_node2, t_gateway_node2);
     });
 }
@@ -155,7 +153,7 @@ fn test_offline() {
     test_offline_ext().execute_with(|| {
         System::set_block_number(1);
 
-        if let Err(e) = Gateway::offline(Origin::signed(1), "peer_id".as_bytes().to_vec()) {
+        if let Err(e) = Gateway::offline(Origin::signed(1), peer_id(PEER_ID_1)) {
             println!("{:?}", e);
         }
 
@@ -170,7 +168,7 @@ fn test_offline() {
         assert_eq!(Gateway::account_peerid_map(1), None);
 
         // check the GatewayNode
-        assert_eq!(Gateway::gateway("peer_id".as_bytes().to_vec()), None);
+        assert_eq!(Gateway::gateway(peer_id(PEER_ID_1)), None);
     });
 }
 
@@ -186,7 +184,7 @@ fn check_offline_info() {
     assert_eq!(Gateway::account_peerid_map(1), None);
 
     // check the GatewayNode
-    assert_eq!(Gateway::gateway("peer_id".as_bytes().to_vec()), None);
+    assert_eq!(Gateway::gateway(peer_id(PEER_ID_1)), None);
 }
 
 fn check_register_info() {
@@ -196,16 +194,16 @@ fn check_register_info() {
     // check the Gateways: peerId list
     let peer_ids = Gateway::gateways();
     assert_eq!(peer_ids.len(), 1);
-    assert_eq!(peer_ids[0], "peer_id".as_bytes().to_vec());
+    assert_eq!(peer_ids[0], peer_id(PEER_ID_1));
 
     // check the AccountPeerMap
     let account_peer_map = Gateway::account_peerid_map(1).unwrap();
     assert_eq!(account_peer_map.len(), 1);
-    assert_eq!(account_peer_map[0], "peer_id".as_bytes().to_vec());
+    assert_eq!(account_peer_map[0], peer_id(PEER_ID_1));
 
     // check the GatewayNode
-    let gateway_node = Gateway::gateway("peer_id".as_bytes().to_vec()).unwrap();
-    assert_eq!(gateway_node.peer_id, "peer_id".as_bytes().to_vec());
+    let gateway_node = Gateway::gateway(peer_id(PEER_ID_1)).unwrap();
+    assert_eq!(gateway_node.peer_id, peer_id(PEER_ID_1));
     assert_eq!(gateway_node.account_id, 1);
     assert_eq!(gateway_node.registration_time, System::block_number());
 }
@@ -215,13 +213,13 @@ fn test_n_register_n_offline() {
     new_test_ext().execute_with(|| {
         for _ in 0..10 {
             if let Err(e) =
-                Gateway::register_gateway_node(Origin::signed(1), "peer_id".as_bytes().to_vec())
+                Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1.as_bytes().to_vec())
             {
                 println!("{:?}", e);
             }
             check_register_info();
 
-            if let Err(e) = Gateway::offline(Origin::signed(1), "peer_id".as_bytes().to_vec()) {
+            if let Err(e) = Gateway::offline(Origin::signed(1), peer_id(PEER_ID_1)) {
                 println!("{:?}", e);
             }
             check_offline_info();
@@ -235,7 +233,7 @@ fn test_staking_info() {
         System::set_block_number(1);
 
         if let Err(e) =
-            Gateway::register_gateway_node(Origin::signed(1), "peer_id".as_bytes().to_vec())
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1.as_bytes().to_vec())
         {
             println!("{:?}", e);
         }
@@ -253,7 +251,7 @@ fn test_staking_info() {
         assert_eq!(total_staking.total_gateway_staking, 100_000_000_000_000);
         assert_eq!(total_staking.total_client_staking, 0);
 
-        if let Err(e) = Gateway::offline(Origin::signed(1), "peer_id".as_bytes().to_vec()) {
+        if let Err(e) = Gateway::offline(Origin::signed(1), peer_id(PEER_ID_1)) {
             println!("{:?}", e);
         }
 
@@ -285,11 +283,14 @@ fn test_online_time() {
         System::set_block_number(1);
         assert_ok!(Gateway::register_gateway_node(
             Origin::signed(1),
-            "peer_id1".as_bytes().to_vec()
+            PEER_ID_1.as_bytes().to_vec()
         ));
 
-       // check the register time of gateway node
-        assert_eq!(Gateway::gateway_node_register_time("peer_id1".as_bytes().to_vec()).unwrap(), 1);
+        // check the register time of gateway node
+        assert_eq!(
+            Gateway::gateway_node_register_time(peer_id(PEER_ID_1)).unwrap(),
+            1
+        );
 
         // check the total online time
         System::set_block_number(2);
@@ -298,13 +299,141 @@ fn test_online_time() {
 
         System::set_block_number(3);
         <Gateway as frame_support::traits::Hooks<BlockNumber>>::on_initialize(3);
-        assert_ok!(Gateway::offline(Origin::signed(1), "peer_id1".as_bytes().to_vec()));
+        assert_ok!(Gateway::offline(Origin::signed(1), peer_id(PEER_ID_1)));
         assert_eq!(Gateway::total_online_time(), 1);
-        assert_eq!(Gateway::gateway_node_register_time("peer_id1".as_bytes().to_vec()), None);
+        assert_eq!(
+            Gateway::gateway_node_register_time(peer_id(PEER_ID_1)),
+            None
+        );
     })
 }
 
+#[test]
+fn test_register_validates_peer_id() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        // garbage and truncated peer ids are rejected
+        assert_noop!(
+            Gateway::register_gateway_node(Origin::signed(1), "peer_id".as_bytes().to_vec()),
+            Error::<Test>::InvalidPeerId
+        );
+        assert_noop!(
+            Gateway::register_gateway_node(Origin::signed(1), PEER_ID_1[..40].as_bytes().to_vec()),
+            Error::<Test>::InvalidPeerId
+        );
+        assert_noop!(
+            Gateway::register_gateway_node(
+                Origin::signed(1),
+                [PEER_ID_1, PEER_ID_2].concat().into_bytes()
+            ),
+            Error::<Test>::InvalidPeerId
+        );
+
+        // the raw multihash is stored base58 encoded
+        let multihash = peer_id(PEER_ID_1).to_multihash().unwrap();
+        assert_ok!(Gateway::register_gateway_node(Origin::signed(1), multihash));
+        assert_eq!(Gateway::gateways(), vec![peer_id(PEER_ID_1)]);
+        assert_eq!(Gateway::gateway(peer_id(PEER_ID_1)).unwrap().account_id, 1);
+    })
+}
+
+#[test]
+fn test_migration_flags_invalid_peer_ids() {
+    test_offline_ext().execute_with(|| {
+        let invalid = PeerId::new_unchecked("peer_id".as_bytes().to_vec());
+        GatewayNodes::<Test>::insert(invalid.clone(), GatewayNode::new(2, invalid.clone(), 1));
+        AccountPeerMap::<Test>::insert(2, vec![invalid.clone()]);
+        // the peer ids stored before the validation are of any length
+        let long = PeerId::new_unchecked(vec![b'Q'; MAX_PEER_ID_LEN + 16]);
+        GatewayNodes::<Test>::insert(long.clone(), GatewayNode::new(3, long.clone(), 1));
+        AccountPeerMap::<Test>::insert(3, vec![long.clone()]);
+        StorageVersion::<Test>::put(Releases::V1_0_0);
+
+        <Gateway as frame_support::traits::Hooks<BlockNumber>>::on_runtime_upgrade();
+
+        assert_eq!(Gateway::storage_version(), Releases::V2_0_0);
+        assert!(Gateway::invalid_peer_ids(invalid.clone()));
+        assert!(Gateway::invalid_peer_ids(long.clone()));
+        assert!(!Gateway::invalid_peer_ids(peer_id(PEER_ID_1)));
+
+        // the flagged gateway nodes can still be taken offline, not kept alive
+        assert_ok!(Gateway::offline(Origin::signed(2), invalid.clone()));
+        assert!(!Gateway::invalid_peer_ids(invalid));
+        assert_noop!(
+            Gateway::heartbeat(Origin::signed(3), long.clone(), None),
+            Error::<Test>::InvalidPeerId
+        );
+        assert_ok!(Gateway::offline(Origin::signed(3), long.clone()));
+        assert!(!Gateway::invalid_peer_ids(long.clone()));
+        assert!(Gateway::gateway(long).is_none());
+    })
+}
 
+#[test]
+fn test_heartbeat_liveness_proof() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(10);
+        let machine = ed25519::Pair::from_seed(&[1; 32]);
+        let peer = PeerId::from_ed25519(&machine.public());
+        assert_ok!(Gateway::register_gateway_node(
+            Origin::signed(1),
+            peer.as_bytes().to_vec()
+        ));
+        frame_system::BlockHash::<Test>::insert(5, H256::repeat_byte(5));
+        let prove = |pair: &ed25519::Pair, block: BlockNumber| {
+            let payload = LivenessPayload {
+                index: 0,
+                block,
+                nonce: System::block_hash(block),
+            };
+            Some(LivenessProof {
+                block,
+                signature: pair.sign(&payload.encode()),
+            })
+        };
+
+        // signed by another machine or over the current block
+        let other = ed25519::Pair::from_seed(&[2; 32]);
+        assert_noop!(
+            Gateway::heartbeat(Origin::signed(1), peer.clone(), prove(&other, 5)),
+            Error::<Test>::InvalidLivenessProof
+        );
+        assert_noop!(
+            Gateway::heartbeat(Origin::signed(1), peer.clone(), prove(&machine, 10)),
+            Error::<Test>::StaleLivenessProof
+        );
+
+        assert_ok!(Gateway::heartbeat(
+            Origin::signed(1),
+            peer.clone(),
+            prove(&machine, 5)
+        ));
+        System::assert_last_event(Event::Gateway(crate::Event::LivenessProven(
+            peer.clone(),
+            5,
+        )));
+        assert_eq!(Gateway::liveness_proofs(peer.clone()), Some(5));
+
+        // the proof cannot be replayed
+        assert_noop!(
+            Gateway::heartbeat(Origin::signed(1), peer.clone(), prove(&machine, 5)),
+            Error::<Test>::StaleLivenessProof
+        );
+
+        // a peer id hashing its key cannot prove its liveness
+        let hashed = peer_id("QmNQa1FSTXNHmrjjfgUW3Px3Vkke4oKiFWdigWkYSux2Pi");
+        assert_ok!(Gateway::register_gateway_node(
+            Origin::signed(2),
+            hashed.as_bytes().to_vec()
+        ));
+        assert_noop!(
+            Gateway::heartbeat(Origin::signed(2), hashed.clone(), prove(&machine, 5)),
+            Error::<Test>::PeerKeyUnavailable
+        );
+        assert_ok!(Gateway::heartbeat(Origin::signed(2), hashed, None));
+    })
+}
 
 // #[test]
 // fn it_works_heartbeat_logic() {
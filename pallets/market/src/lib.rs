@@ -7,8 +7,7 @@ use frame_support::{
     dispatch::DispatchResult,
     pallet_prelude::*,
     traits::{Currency, ExistenceRequirement, UnixTime},
-    PalletId,
-    transactional
+    transactional, PalletId,
 };
 
 use frame_system::pallet_prelude::*;
@@ -34,6 +33,8 @@ type BalanceOf<T> =
 
 const PALLET_ID: PalletId = PalletId(*b"ttchain!");
 pub const BALANCE_UNIT: u128 = 1_000_000_000_000; //10^12
+/// maximum number of scopes an operator can be authorized for by one owner
+const OPERATOR_SCOPE_LIMIT: usize = 100;
 
 #[cfg(test)]
 mod mock;
@@ -166,6 +167,20 @@ pub mod pallet {
     pub(super) type EraClientRewards<T: Config> =
         StorageMap<_, Twox64Concat, EraIndex, BalanceOf<T>, OptionQuery>;
 
+    /// Operators
+    /// The scopes in which the operator can act for the owner [owner, operator, scopes]
+    #[pallet::storage]
+    #[pallet::getter(fn operators)]
+    pub(super) type Operators<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        T::AccountId,
+        Twox64Concat,
+        T::AccountId,
+        Vec<OperatorScope>,
+        ValueQuery,
+    >;
+
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
         // T::AccountId, p_market::StakingAmount
@@ -228,6 +243,12 @@ pub mod pallet {
         UpdateClientStakingFee(BalanceOf<T>),
 
         UpdateMarketBaseMultiplier(u128, u128, u128),
+
+        // Operator authorized, (owner, operator, scope)
+        OperatorAuthorized(T::AccountId, T::AccountId, OperatorScope),
+
+        // Operator revoked, (owner, operator, scope)
+        OperatorRevoked(T::AccountId, T::AccountId, OperatorScope),
     }
 
     #[pallet::hooks]
@@ -242,6 +263,15 @@ pub mod pallet {
         NotEnoughBalanceTobond,
 
         NotBond,
+
+        // the operator has already been authorized for the scope
+        OperatorAlreadyAuthorized,
+
+        // the operator is not authorized for the scope
+        OperatorNotAuthorized,
+
+        // the operator has been authorized for too many scopes
+        TooManyOperatorScopes,
     }
 
     #[pallet::call]
@@ -477,6 +507,72 @@ pub mod pallet {
             Self::deposit_event(Event::UpdateMarketBaseMultiplier(provider, gateway, client));
             Ok(())
         }
+
+        /// Authorize an operator account to act for the owner in the scope
+        /// * operators can report heartbeats and execute orders
+        /// * staking, price and withdrawal actions stay with the owner
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn authorize_operator(
+            origin: OriginFor<T>,
+            operator: T::AccountId,
+            scope: OperatorScope,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // 1. get the scopes of the operator
+            let mut scopes = Operators::<T>::get(who.clone(), operator.clone());
+
+            // 2. check the scope has not been authorized
+            ensure!(
+                !scopes.contains(&scope),
+                Error::<T>::OperatorAlreadyAuthorized
+            );
+            ensure!(
+                scopes.len() < OPERATOR_SCOPE_LIMIT,
+                Error::<T>::TooManyOperatorScopes
+            );
+
+            // 3. update the Operators
+            scopes.push(scope.clone());
+            Operators::<T>::insert(who.clone(), operator.clone(), scopes);
+
+            // 4. emit event
+            Self::deposit_event(Event::OperatorAuthorized(who, operator, scope));
+            Ok(())
+        }
+
+        /// Revoke the authorization of an operator account in the scope
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn revoke_operator(
+            origin: OriginFor<T>,
+            operator: T::AccountId,
+            scope: OperatorScope,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // 1. get the scopes of the operator
+            let mut scopes = Operators::<T>::get(who.clone(), operator.clone());
+
+            // 2. check the scope has been authorized
+            let index = match scopes.iter().position(|x| x == &scope) {
+                Some(x) => x,
+                None => Err(Error::<T>::OperatorNotAuthorized)?,
+            };
+
+            // 3. update the Operators
+            scopes.remove(index);
+            if scopes.is_empty() {
+                Operators::<T>::remove(who.clone(), operator.clone());
+            } else {
+                Operators::<T>::insert(who.clone(), operator.clone(), scopes);
+            }
+
+            // 4. emit event
+            Self::deposit_event(Event::OperatorRevoked(who, operator, scope));
+            Ok(())
+        }
     }
 }
 
@@ -683,11 +779,14 @@ impl<T: Config> MarketInterface<<T as frame_system::Config>::AccountId> for Pall
         EraGatewayRewards::<T>::insert(index, gateway_payout);
 
         // 4. Push the provider points list to cycle and compute provider nodes reward
+        T::ProviderInterface::accrue_duration_points();
         let ds_provider = T::ProviderInterface::get_providers_points();
         T::ChunkCycleInterface::push(
             ForDs::Provider(ds_provider),
             T::BalanceToNumber::convert(provider_payout),
         );
+        // the duration points are counted per era
+        T::ProviderInterface::reset_duration_points();
         // Save the history ear provider reward
         EraProviderRewards::<T>::insert(index, provider_payout);
 
@@ -800,6 +899,12 @@ impl<T: Config> MarketInterface<<T as frame_system::Config>::AccountId> for Pall
     }
 }
 
+impl<T: Config> OperatorInterface<<T as frame_system::Config>::AccountId> for Pallet<T> {
+    fn is_authorized(owner: &T::AccountId, who: &T::AccountId, scope: OperatorScope) -> bool {
+        owner == who || Operators::<T>::get(owner, who).contains(&scope)
+    }
+}
+
 impl<T: Config> ForChunkCycle for Pallet<T> {
     fn gateway_chunk_cycle() {
         todo!()
@@ -42,6 +42,16 @@ parameter_types! {
     pub const SS58Prefix: u8 = 42;
     // polling interval
     pub const ResourceInterval: BlockNumber = 3 * HOURS;
+    // maximum number of resources expired in a block
+    pub const MaxExpirationsPerBlock: u32 = 100;
+    // the reputation decays a tenth of the way to neutral every period
+    pub const ReputationDecayPeriod: BlockNumber = HOURS;
+    pub const MaxBatchResources: u32 = 10;
+    pub const ProbationPeriod: BlockNumber = HOURS;
+    pub const ProbationHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // the auto extension is attempted this long before the end of rent
+    pub const AutoExtendWindow: BlockNumber = 10 * MINUTES;
+    pub const MaxStatusHistory: u32 = 10;
 }
 
 impl system::Config for Test {
@@ -112,6 +122,7 @@ impl pallet_gateway::Config for Test {
     type GatewayNodeTimedRemovalInterval = GatewayNodeTimedRemovalInterval;
     type GatewayNodeHeartbeatInterval = GatewayNodeHeartbeatInterval;
     type MarketInterface = Market;
+    type OperatorInterface = Market;
 }
 
 impl pallet_provider::Config for Test {
@@ -121,6 +132,15 @@ impl pallet_provider::Config for Test {
     type NumberToBalance = ();
     type ResourceInterval = ResourceInterval;
     type MarketInterface = Market;
+    type MaxExpirationsPerBlock = MaxExpirationsPerBlock;
+    type ReputationDecayPeriod = ReputationDecayPeriod;
+    type MaxBatchResources = MaxBatchResources;
+    type ProbationPeriod = ProbationPeriod;
+    type ProbationHeartbeatInterval = ProbationHeartbeatInterval;
+    type MaxStatusHistory = MaxStatusHistory;
+    type AutoExtendWindow = AutoExtendWindow;
+    type ResourceOrderInterface = ();
+    type OperatorInterface = Market;
 }
 
 parameter_types! {
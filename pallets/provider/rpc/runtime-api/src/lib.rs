@@ -0,0 +1,20 @@
+//! Runtime API definition for the provider pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use frame_support::Parameter;
+use sp_runtime::traits::AtLeast32BitUnsigned;
+use sp_std::vec::Vec;
+
+pub use primitives::p_provider::{ComputingResource, ResourceQuery, ResourceSort};
+
+sp_api::decl_runtime_apis! {
+    pub trait ProviderApi<AccountId, BlockNumber> where
+        AccountId: Codec,
+        BlockNumber: Parameter + AtLeast32BitUnsigned,
+    {
+        /// the resources matching the query, sorted and paged
+        fn resources(query: ResourceQuery) -> Vec<ComputingResource<BlockNumber, AccountId>>;
+    }
+}
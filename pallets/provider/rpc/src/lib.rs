@@ -0,0 +1,88 @@
+//! RPC interface for the provider pallet.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use frame_support::Parameter;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{
+    generic::BlockId,
+    traits::{AtLeast32BitUnsigned, Block as BlockT},
+};
+
+pub use pallet_provider_rpc_runtime_api::ProviderApi as ProviderRuntimeApi;
+use pallet_provider_rpc_runtime_api::{ComputingResource, ResourceQuery};
+
+#[rpc]
+pub trait ProviderApi<BlockHash, AccountId, BlockNumber>
+where
+    BlockNumber: Parameter + AtLeast32BitUnsigned,
+{
+    /// search the resource catalogue
+    #[rpc(name = "hamster_resources")]
+    fn resources(
+        &self,
+        query: ResourceQuery,
+        at: Option<BlockHash>,
+    ) -> Result<Vec<ComputingResource<BlockNumber, AccountId>>>;
+}
+
+/// A struct that implements the `ProviderApi`.
+pub struct Provider<C, B> {
+    client: Arc<C>,
+    _marker: std::marker::PhantomData<B>,
+}
+
+impl<C, B> Provider<C, B> {
+    /// Create new `Provider` with the given reference to the client.
+    pub fn new(client: Arc<C>) -> Self {
+        Provider {
+            client,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+    /// The call to runtime failed.
+    RuntimeError,
+}
+
+impl From<Error> for i64 {
+    fn from(e: Error) -> i64 {
+        match e {
+            Error::RuntimeError => 1,
+        }
+    }
+}
+
+impl<C, Block, AccountId, BlockNumber> ProviderApi<<Block as BlockT>::Hash, AccountId, BlockNumber>
+    for Provider<C, Block>
+where
+    Block: BlockT,
+    C: 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: ProviderRuntimeApi<Block, AccountId, BlockNumber>,
+    AccountId: Codec,
+    BlockNumber: Parameter + AtLeast32BitUnsigned,
+{
+    fn resources(
+        &self,
+        query: ResourceQuery,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Vec<ComputingResource<BlockNumber, AccountId>>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(||
+            // If the block hash is not supplied assume the best block.
+            self.client.info().best_hash));
+
+        api.resources(&at, query).map_err(|e| RpcError {
+            code: ErrorCode::ServerError(Error::RuntimeError.into()),
+            message: "Unable to query resources.".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+}
@@ -1,9 +1,10 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 extern crate alloc;
 
-use frame_support::sp_runtime::traits::Convert;
+use frame_support::sp_runtime::traits::{Convert, One, Saturating, Zero};
 use frame_support::{dispatch::DispatchResult, pallet_prelude::*, traits::Currency};
 use frame_system::pallet_prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use sp_std::convert::TryInto;
 use sp_std::vec::Vec;
 
@@ -13,7 +14,9 @@ use sp_std::vec::Vec;
 pub use pallet::*;
 
 use primitives::p_provider;
-pub use primitives::{p_market::*, p_provider::*, p_resource_order::*, EraIndex};
+pub use primitives::{
+    p_market::*, p_peer_id::PeerId, p_provider::*, p_resource_order::*, EraIndex,
+};
 
 #[cfg(test)]
 mod mock;
@@ -49,6 +52,40 @@ pub mod pallet {
 
         /// market interface
         type MarketInterface: MarketInterface<Self::AccountId>;
+
+        /// resource order interface
+        type ResourceOrderInterface: ResourceOrderInterface<Self::AccountId, Self::BlockNumber>;
+
+        /// operator interface, resolves the accounts allowed to act for a provider
+        type OperatorInterface: OperatorInterface<Self::AccountId>;
+
+        /// maximum number of resources expired in a block, the rest is carried over
+        #[pallet::constant]
+        type MaxExpirationsPerBlock: Get<u32>;
+
+        /// the reputation moves a tenth of the way back to neutral every period
+        #[pallet::constant]
+        type ReputationDecayPeriod: Get<Self::BlockNumber>;
+
+        /// maximum number of resources registered in one batch
+        #[pallet::constant]
+        type MaxBatchResources: Get<u32>;
+
+        /// blocks a recovering resource heartbeats before it is unused again
+        #[pallet::constant]
+        type ProbationPeriod: Get<Self::BlockNumber>;
+
+        /// longest gap between two probation heartbeats, the probation restarts after it
+        #[pallet::constant]
+        type ProbationHeartbeatInterval: Get<Self::BlockNumber>;
+
+        /// number of status changes kept for a resource
+        #[pallet::constant]
+        type MaxStatusHistory: Get<u32>;
+
+        /// blocks before the end of rent the auto extension of a resource is attempted
+        #[pallet::constant]
+        type AutoExtendWindow: Get<Self::BlockNumber>;
     }
 
     #[pallet::pallet]
@@ -91,12 +128,63 @@ pub mod pallet {
     #[pallet::getter(fn provider_online_list)]
     pub(super) type ProviderOnlineList<T: Config> = StorageValue<_, Vec<T::AccountId>, ValueQuery>;
 
+    /// resources sorted by rental unit price then index [(price, index)]
+    #[pallet::storage]
+    #[pallet::getter(fn resource_price_index)]
+    pub(super) type ResourcePriceIndex<T: Config> = StorageValue<_, Vec<(u128, u64)>, ValueQuery>;
+
     /// Association between future block numbers and expired resource indexes
     #[pallet::storage]
     #[pallet::getter(fn future_expired_resource)]
     pub(super) type FutureExpiredResource<T: Config> =
         StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, OptionQuery>;
 
+    /// block from which the resource accrues duration points, the resources offline do not accrue
+    #[pallet::storage]
+    #[pallet::getter(fn resource_online_since)]
+    pub(super) type ResourceOnlineSince<T: Config> =
+        StorageMap<_, Twox64Concat, u64, T::BlockNumber, OptionQuery>;
+
+    /// probation of the recovering resources
+    #[pallet::storage]
+    #[pallet::getter(fn resource_probations)]
+    pub(super) type ResourceProbations<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Probation<T::BlockNumber>, OptionQuery>;
+
+    /// latest status changes of the resource, oldest first
+    #[pallet::storage]
+    #[pallet::getter(fn resource_status_history)]
+    pub(super) type ResourceStatusHistory<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Vec<StatusChange<T::BlockNumber>>, ValueQuery>;
+
+    /// hours the resource is extended by when it expires
+    #[pallet::storage]
+    #[pallet::getter(fn auto_extend_hours)]
+    pub(super) type AutoExtendHours<T: Config> = StorageMap<_, Twox64Concat, u64, u32, OptionQuery>;
+
+    /// resource transfers waiting for the recipient to accept [index, recipient]
+    #[pallet::storage]
+    #[pallet::getter(fn pending_transfers)]
+    pub(super) type PendingTransfers<T: Config> =
+        StorageMap<_, Twox64Concat, u64, T::AccountId, OptionQuery>;
+
+    /// resource reputation, the resources without one are neutral
+    #[pallet::storage]
+    #[pallet::getter(fn resource_reputations)]
+    pub(super) type ResourceReputations<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Reputation<T::BlockNumber>, OptionQuery>;
+
+    /// provider reputation, the providers without one are neutral
+    #[pallet::storage]
+    #[pallet::getter(fn provider_reputations)]
+    pub(super) type ProviderReputations<T: Config> =
+        StorageMap<_, Twox64Concat, T::AccountId, Reputation<T::BlockNumber>, OptionQuery>;
+
+    /// The first block whose expired resources have not all been processed
+    #[pallet::storage]
+    #[pallet::getter(fn expiry_cursor)]
+    pub(super) type ExpiryCursor<T: Config> = StorageValue<_, T::BlockNumber, ValueQuery>;
+
     /// resource provider and resource association
     #[pallet::storage]
     #[pallet::getter(fn provider)]
@@ -109,6 +197,40 @@ pub mod pallet {
     pub(super) type ProviderTotalPoints<T: Config> =
         StorageMap<_, Twox64Concat, T::AccountId, p_provider::ProviderPoints, OptionQuery>;
 
+    /// accepted ranges of the registered resources
+    #[pallet::storage]
+    #[pallet::getter(fn resource_limits)]
+    pub(super) type ProviderResourceLimits<T: Config> = StorageValue<_, ResourceLimits, ValueQuery>;
+
+    /// weights of the resource dimensions in the staked amount
+    #[pallet::storage]
+    #[pallet::getter(fn stake_weights)]
+    pub(super) type StakeWeights<T: Config> = StorageValue<_, ResourceWeights, ValueQuery>;
+
+    /// weights of the resource dimensions in the provider points
+    #[pallet::storage]
+    #[pallet::getter(fn point_weights)]
+    pub(super) type PointWeights<T: Config> = StorageValue<_, ResourceWeights, ValueQuery>;
+
+    /// stake and points recorded for each resource at registration,
+    /// the resources without a record use the current weights
+    #[pallet::storage]
+    #[pallet::getter(fn resource_lock)]
+    pub(super) type ResourceLocks<T: Config> =
+        StorageMap<_, Twox64Concat, u64, ResourceLock, OptionQuery>;
+
+    /// resources registered before the peer ids were validated whose peer id is not valid
+    #[pallet::storage]
+    #[pallet::getter(fn invalid_peer_ids)]
+    pub(super) type InvalidPeerIds<T: Config> = StorageMap<_, Twox64Concat, u64, bool, ValueQuery>;
+
+    /// Storage version of the pallet.
+    ///
+    /// This is set to v7.0.0 for new networks.
+    #[pallet::storage]
+    #[pallet::getter(fn storage_version)]
+    pub(crate) type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
     // The genesis config type.
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
@@ -141,6 +263,7 @@ pub mod pallet {
             <ResourceIndex<T>>::put(&self.resource_index);
             for (a, b) in &self.resource {
                 <Resources<T>>::insert(a, b);
+                Pallet::<T>::insert_price_index(*a, b.rental_info.rent_unit_price);
             }
             for (a, b) in &self.future_expired_resource {
                 <FutureExpiredResource<T>>::insert(a, b);
@@ -148,6 +271,7 @@ pub mod pallet {
             for (a, b) in &self.provider {
                 <Providers<T>>::insert(a, b);
             }
+            <StorageVersion<T>>::put(Releases::V7_0_0);
         }
     }
 
@@ -158,95 +282,73 @@ pub mod pallet {
     #[pallet::generate_deposit(pub (super) fn deposit_event)]
     pub enum Event<T: Config> {
         /// successfully registered resources
-        /// [accountId, index, peerId, cpu, memory, system, cpu_model, price_hour, rent_duration_hour]
-        RegisterResourceSuccess(
-            T::AccountId,
-            u64,
-            Vec<u8>,
-            u64,
-            u64,
-            Vec<u8>,
-            Vec<u8>,
-            Balance,
-            u32,
-        ),
+        /// [accountId, index, peerId, config, price_hour, rent_duration_hour]
+        RegisterResourceSuccess(T::AccountId, u64, PeerId, ResourceConfig, Balance, u32),
         /// modify the resource unit price successfully [accountId, index, balance]
         ModifyResourceUnitPrice(T::AccountId, u64, u128),
         /// successfully added resource rental duration
         AddingResourceDurationSuccess(T::AccountId, u32),
         /// successfully deleted
         RemoveSuccess(T::AccountId, u64),
+        /// resource limits updated [limits]
+        ResourceLimitsUpdated(ResourceLimits),
+        /// stake weights updated [weights]
+        StakeWeightsUpdated(ResourceWeights),
+        /// point weights updated [weights]
+        PointWeightsUpdated(ResourceWeights),
+        /// resource config updated [accountId, index, config]
+        ResourceConfigUpdated(T::AccountId, u64, ResourceConfig),
+        /// resource is draining, it is offlined when its agreement finishes [accountId, index]
+        ResourceDraining(T::AccountId, u64),
+        /// resource expired after its expiration block [index, expiration block]
+        ResourceExpiredLate(u64, T::BlockNumber),
+        /// successfully registered a batch of resources [accountId, first index, last index]
+        RegisterResourcesSuccess(T::AccountId, u64, u64),
+        /// resource offered to another account [accountId, index, recipient]
+        ResourceTransferOffered(T::AccountId, u64, T::AccountId),
+        /// resource moved to another account [previous owner, index, new owner]
+        ResourceTransferred(T::AccountId, u64, T::AccountId),
+        /// offline resource put on probation [accountId, index]
+        ResourceOnProbation(T::AccountId, u64),
+        /// a probation heartbeat was late, the probation restarted [index, block]
+        ProbationRestarted(u64, T::BlockNumber),
+        /// resource passed its probation and is unused again [accountId, index]
+        ResourceRecovered(T::AccountId, u64),
+        /// auto extension of the resource set, zero is off [accountId, index, hours]
+        AutoExtendSet(T::AccountId, u64, u32),
+        /// resource extended before it expired [index, end of rent]
+        ResourceAutoExtended(u64, T::BlockNumber),
+        /// the provider does not have the stake to extend the resource, it expires [index, stake needed]
+        AutoExtendSkipped(u64, Balance),
     }
 
     #[pallet::hooks]
     impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
         fn on_initialize(now: T::BlockNumber) -> Weight {
-            //Determine whether there is a current block in the block association information
-            if FutureExpiredResource::<T>::contains_key(now) {
-                //Determine whether the expired resource corresponding to the current block is empty
-                match FutureExpiredResource::<T>::get(now) {
-                    Some(t) => {
-                        t.into_iter().for_each(|resource_index| {
-                            // Determine whether there is a resource in the Vec of an expired resource
-                            // get the resource corresponding to the index
-                            let resource_option = Resources::<T>::get(resource_index);
-                            if resource_option.is_some() {
-                                let resource = resource_option.unwrap();
-                                let account_id = resource.account_id;
-                                // delete associated resource
-                                let account_resources = Providers::<T>::get(&account_id);
-                                if account_resources.is_some() {
-                                    let _resource: Vec<u64> = account_resources
-                                        .unwrap()
-                                        .into_iter()
-                                        .filter(|x| *x != resource_index.clone())
-                                        .collect();
-                                    Providers::<T>::insert(account_id.clone(), _resource);
-                                }
-
-                                // check the user has other resource
-                                if let Some(list) = Providers::<T>::get(account_id.clone()) {
-                                    if list.len() == 0 {
-                                        // delete the user
-                                        Providers::<T>::remove(account_id.clone());
-                                        // update the provider online list
-                                        let mut provider_list = ProviderOnlineList::<T>::get();
-                                        if let Ok(index) = provider_list.binary_search(&account_id)
-                                        {
-                                            provider_list.remove(index);
-                                            ProviderOnlineList::<T>::set(provider_list);
-                                        }
-                                    }
-                                }
-                                //remove resource
-                                Resources::<T>::remove(resource_index);
-                                // reduce count
-                                let count = ResourceCount::<T>::get();
-                                ResourceCount::<T>::set(count - 1);
-
-                                // update provider points
-                                let cpu = resource.config.cpu;
-                                let memory = resource.config.memory;
-                                Self::sub_provider_points(account_id.clone(), cpu, memory);
-
-                                // unlock the staking
-                                T::MarketInterface::change_stake_amount(
-                                    account_id.clone(),
-                                    ChangeAmountType::Unlock,
-                                    T::BalanceToNumber::convert(
-                                        Self::compute_provider_staked_amount(cpu, memory),
-                                    ),
-                                    MarketUserStatus::Provider,
-                                );
-                            }
-                        });
-                        // delete expired resource mappings
-                        FutureExpiredResource::<T>::remove(now);
-                    }
-                    None => (),
-                }
+            Self::extend_expiring_resources(now).saturating_add(Self::expire_resources(now))
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            let mut weight = T::DbWeight::get().reads(1);
+            if StorageVersion::<T>::get() == Releases::V1_0_0 {
+                weight = weight.saturating_add(migrations::v2::migrate::<T>());
+            }
+            if StorageVersion::<T>::get() == Releases::V2_0_0 {
+                weight = weight.saturating_add(migrations::v3::migrate::<T>());
+            }
+            if StorageVersion::<T>::get() == Releases::V3_0_0 {
+                weight = weight.saturating_add(migrations::v4::migrate::<T>());
+            }
+            if StorageVersion::<T>::get() == Releases::V4_0_0 {
+                weight = weight.saturating_add(migrations::v5::migrate::<T>());
+            }
+            if StorageVersion::<T>::get() == Releases::V5_0_0 {
+                weight = weight.saturating_add(migrations::v6::migrate::<T>());
             }
-            0
+            if StorageVersion::<T>::get() == Releases::V6_0_0 {
+                weight = weight.saturating_add(migrations::v7::migrate::<T>());
+            }
+            weight
         }
     }
 
@@ -271,6 +373,26 @@ pub mod pallet {
         StakingNotExit,
 
         ResourceAlreadyExist,
+        /// the resource limits are not valid
+        InvalidResourceLimits,
+        /// the resource weights are not valid
+        InvalidResourceWeights,
+        /// the batch has no resources
+        NoResources,
+        /// the batch has more than MaxBatchResources resources
+        TooManyResources,
+        /// no transfer of the resource is offered to the account
+        TransferNotFound,
+        /// the slashed stake must be topped up before the recovery
+        InsufficientStake,
+        /// the peer id is not a libp2p peer id
+        InvalidPeerId,
+        /// failed to unlock the stake of the previous owner
+        UnlockAmountFailed,
+        /// the call is deprecated
+        CallDeprecated,
+        /// the rental duration overflows
+        InvalidRentDuration,
     }
 
     // Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -287,10 +409,7 @@ pub mod pallet {
         pub fn register_resource(
             account_id: OriginFor<T>,
             peer_id: Vec<u8>,
-            cpu: u64,
-            memory: u64,
-            system: Vec<u8>,
-            cpu_model: Vec<u8>,
+            config: ResourceConfig,
             price: BalanceOf<T>,
             rent_duration_hour: u32,
             new_index: u64,
@@ -298,9 +417,7 @@ pub mod pallet {
             let who = ensure_signed(account_id)?;
 
             // check the resource specifications do not match
-            if cpu <= 0 || memory <= 0 || cpu > 64 || memory > 256 {
-                Err(Error::<T>::IllegalRequest)?
-            }
+            Self::check_resource_config(&config)?;
 
             // 0. check the user has staking
             ensure!(
@@ -308,126 +425,38 @@ pub mod pallet {
                 Error::<T>::StakingNotExit
             );
 
-            // 1. check the index and get the right index
-            // if new index < current index and the compute resource exit
-            // and the accountid is same, return error
-            // or change the new index to current index
-            let current_index = ResourceIndex::<T>::get();
-            if new_index < current_index {
-                if Resources::<T>::contains_key(new_index) {
-                    // get the history registraton info
-                    let compute_resource = Resources::<T>::get(new_index).unwrap();
-                    // check the peerid is eq
-                    if peer_id.eq(&compute_resource.peer_id) {
-                        // repeat Registration, return err
-                        Err(Error::<T>::ResourceAlreadyExist)?
-                    }
-                }
-            }
+            // 1. check the peer id and the index and get the right index
+            let peer_id = Self::parse_peer_id(&peer_id)?;
+            Self::check_new_index(&peer_id, new_index)?;
             // now need to make the new registration
             // Get the resource index
-            let index = current_index;
-
-            // 2. compute the staking amount
-            let staking_amount = Self::compute_provider_staked_amount(cpu, memory);
-
-            // 2. lock the staking amount
-            ensure!(
-                T::MarketInterface::change_stake_amount(
-                    who.clone(),
-                    ChangeAmountType::Lock,
-                    T::BalanceToNumber::convert(staking_amount),
-                    MarketUserStatus::Provider,
-                ),
-                Error::<T>::LockAmountFailed,
-            );
-
-            // 3. update the ProviderOnlineList
-            // if user not in the online list , add the user to the list
-            let mut provider_online_list = ProviderOnlineList::<T>::get();
-            if let Err(index) = provider_online_list.binary_search(&who) {
-                provider_online_list.insert(index, who.clone());
-            }
-            ProviderOnlineList::<T>::set(provider_online_list);
+            let index = ResourceIndex::<T>::get();
 
-            // 4. compute and update the provider points
-            Self::update_provider_points(who.clone(), cpu, memory);
+            // 2. compute the staking amount and the points, and lock the staking amount
+            let lock = Self::compute_resource_lock(&config);
+            Self::lock_provider_stake(who.clone(), lock.staked, lock.points)?;
 
-            // crate the resource config, use cpu, memory, system, cpu_model
-            let resource_config = ResourceConfig::new(
-                cpu.clone(),
-                memory.clone(),
-                system.clone(),
-                cpu_model.clone(),
-            );
-            // create the statistice
-            let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
-
-            // compute the Expiration time of the source
-            // get the current block height
+            // 3. create the resource and associate the block number and the resource id to expire
             let block_number = <frame_system::Pallet<T>>::block_number();
-            // calculate persistent blocks
-            let rent_blocks = TryInto::<T::BlockNumber>::try_into(&rent_duration_hour * 600)
-                .ok()
-                .unwrap();
-            // the block number at which the calculation ends
-            let end_of_block = block_number + rent_blocks;
-            // create the resource rental information
-            let resource_rental_info = ResourceRentalInfo::new(
-                T::BalanceToNumber::convert(price.clone()),
-                rent_blocks,
-                end_of_block,
-            );
-            // create the computing resource: include all the info(resource, statistics, rental_info, and source status)
-            let computing_resource = ComputingResource::new(
-                index,
-                who.clone(),
+            let spec = ResourceSpec::new(
                 peer_id.clone(),
-                resource_config,
-                statistics,
-                resource_rental_info,
-                ResourceStatus::Unused,
+                config.clone(),
+                price.clone(),
+                rent_duration_hour,
+                new_index,
             );
-
-            //Associate the block number and the resource id to expire
-            if !FutureExpiredResource::<T>::contains_key(end_of_block) {
-                // init
-                let vec: Vec<u64> = Vec::new();
-                FutureExpiredResource::<T>::insert(end_of_block, vec);
-            }
-
-            let mut expired_resource = FutureExpiredResource::<T>::get(end_of_block).unwrap();
-
-            // determine whether to associate too many resources
-            ensure!(expired_resource.len() < 400, Error::<T>::TryAgain);
-
-            expired_resource.push(index);
-            FutureExpiredResource::<T>::insert(end_of_block, expired_resource);
-            // increase resources
-            Resources::<T>::insert(index, computing_resource.clone());
-            // increase the total
-            let count = ResourceCount::<T>::get();
-            ResourceCount::<T>::set(count + 1);
+            let end_of_block = Self::insert_resource(who.clone(), index, spec, block_number, lock);
+            Self::add_expiring_resources(end_of_block, &[index]);
             // index auto increment
             ResourceIndex::<T>::set(index + 1);
             // update publisher associated resource
-            if !Providers::<T>::contains_key(who.clone()) {
-                // Initialize
-                let vec: Vec<u64> = Vec::new();
-                Providers::<T>::insert(who.clone(), vec);
-            }
-            let mut resources = Providers::<T>::get(who.clone()).unwrap();
-            resources.push(index);
-            Providers::<T>::insert(who.clone(), resources);
+            Self::add_provider_resources(who.clone(), &[index]);
 
             Self::deposit_event(Event::RegisterResourceSuccess(
                 who,
                 index,
                 peer_id,
-                cpu,
-                memory,
-                system,
-                cpu_model,
+                config,
                 T::BalanceToNumber::convert(price),
                 rent_duration_hour,
             ));
@@ -456,7 +485,9 @@ pub mod pallet {
                 Error::<T>::IllegalRequest
             );
 
+            Self::remove_price_index(index, resource.rental_info.rent_unit_price);
             resource.update_resource_price(T::BalanceToNumber::convert(unit_price.clone()));
+            Self::insert_price_index(index, resource.rental_info.rent_unit_price);
             Resources::<T>::insert(&index, resource);
 
             Self::deposit_event(Event::ModifyResourceUnitPrice(
@@ -496,41 +527,16 @@ pub mod pallet {
                 Error::<T>::IllegalRequest
             );
 
-            let duration_add = TryInto::<T::BlockNumber>::try_into(duration * 600)
-                .ok()
-                .unwrap();
-
-            // Delete the resource associated with the previous expired block
-            let option = FutureExpiredResource::<T>::get(resource.rental_info.end_of_rent.clone());
-            if option.is_some() {
-                let resource_list: Vec<u64> = option
-                    .unwrap()
-                    .into_iter()
-                    .filter(|x| *x != resource.index.clone())
-                    .collect();
-                FutureExpiredResource::<T>::insert(
-                    resource.rental_info.end_of_rent.clone(),
-                    resource_list,
-                );
-            }
+            let duration_add = duration
+                .checked_mul(600)
+                .and_then(|x| TryInto::<T::BlockNumber>::try_into(x).ok())
+                .ok_or(Error::<T>::InvalidRentDuration)?;
 
+            // move the resource from the previous expired block to the new one
+            Self::remove_expiring_resource(resource.rental_info.end_of_rent, index);
             resource.add_resource_duration(duration_add);
+            Self::add_expiring_resources(resource.rental_info.end_of_rent, &[index]);
             Resources::<T>::insert(&index, resource);
-            // get the modified resource
-            let changed_resource = Resources::<T>::get(index.clone()).unwrap();
-
-            let rent_block = changed_resource.rental_info.end_of_rent;
-            // add_new_expired_block_associated_resource
-            if !FutureExpiredResource::<T>::contains_key(rent_block) {
-                // init
-                let vec: Vec<u64> = Vec::new();
-                FutureExpiredResource::<T>::insert(rent_block, vec);
-            }
-            let mut expired_resource = FutureExpiredResource::<T>::get(rent_block).unwrap();
-            // determine whether to associate too many resources
-            ensure!(expired_resource.len() < 400, Error::<T>::TryAgain);
-            expired_resource.push(changed_resource.index.clone());
-            FutureExpiredResource::<T>::insert(rent_block, expired_resource);
 
             Self::deposit_event(Event::AddingResourceDurationSuccess(who, duration));
 
@@ -558,210 +564,1410 @@ pub mod pallet {
             // 3. ensure the resource's status can be remove
             ensure!(
                 resource.status == ResourceStatus::Unused
-                    || resource.status == ResourceStatus::Offline,
+                    || resource.status == ResourceStatus::Offline
+                    || resource.status == ResourceStatus::Probation,
                 Error::<T>::CannotBeDeleted
             );
 
-            // delete associated resource
-            let option = Providers::<T>::get(who.clone());
-            if option.is_some() {
-                let resource_vec: Vec<u64> = option
-                    .unwrap()
-                    .into_iter()
-                    .filter(|x| *x != index.clone())
-                    .collect();
-                Providers::<T>::insert(who.clone(), resource_vec);
-            }
+            Self::do_offline(who, index, resource);
 
-            // check the user has other resources
-            if let Some(list) = Providers::<T>::get(who.clone()) {
-                if list.len() == 0 {
-                    // delete the user
-                    Providers::<T>::remove(who.clone());
-                    // update the provider online list
-                    let mut provider_list = ProviderOnlineList::<T>::get();
-                    if let Ok(index) = provider_list.binary_search(&who) {
-                        provider_list.remove(index);
-                        ProviderOnlineList::<T>::set(provider_list);
-                    }
-                }
-            }
+            Ok(())
+        }
 
-            // reduce count
-            let count = ResourceCount::<T>::get();
-            ResourceCount::<T>::set(count.saturating_sub(1));
+        /// deprecated, an offline resource is recovered with request_recovery.
+        /// the call is kept so that the indices of the following calls do not change
+        #[pallet::weight(10_000)]
+        pub fn change_resource_status(account_id: OriginFor<T>, _index: u64) -> DispatchResult {
+            ensure_signed(account_id)?;
+            Err(Error::<T>::CallDeprecated.into())
+        }
 
-            // Delete resources associated with future expirations
-            let end_of_rent = resource.rental_info.end_of_rent;
-            let option = FutureExpiredResource::<T>::get(&end_of_rent);
-            if option.is_some() {
-                let new_resource: Vec<u64> = option
-                    .unwrap()
-                    .into_iter()
-                    .filter(|x| *x != index.clone())
-                    .collect();
-                FutureExpiredResource::<T>::insert(end_of_rent, new_resource);
-            }
+        /// Change the accepted ranges of the registered resources, only call by root
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_resource_limits(origin: OriginFor<T>, limits: ResourceLimits) -> DispatchResult {
+            ensure_root(origin)?;
+            ensure!(limits.is_valid(), Error::<T>::InvalidResourceLimits);
 
-            //delete resource
-            Resources::<T>::remove(&index);
+            ProviderResourceLimits::<T>::put(limits.clone());
 
-            // update provider points
-            Self::sub_provider_points(who.clone(), resource.config.cpu, resource.config.memory);
+            Self::deposit_event(Event::ResourceLimitsUpdated(limits));
+            Ok(())
+        }
+
+        /// Change the stake weights of the resource dimensions, only call by root.
+        /// the registered resources keep their locked stake
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_stake_weights(origin: OriginFor<T>, weights: ResourceWeights) -> DispatchResult {
+            ensure_root(origin)?;
+            ensure!(weights.is_valid(), Error::<T>::InvalidResourceWeights);
+
+            StakeWeights::<T>::put(weights.clone());
+
+            Self::deposit_event(Event::StakeWeightsUpdated(weights));
+            Ok(())
+        }
+
+        /// Change the point weights of the resource dimensions, only call by root.
+        /// the registered resources keep their points
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_point_weights(origin: OriginFor<T>, weights: ResourceWeights) -> DispatchResult {
+            ensure_root(origin)?;
+            ensure!(weights.is_valid(), Error::<T>::InvalidResourceWeights);
+
+            PointWeights::<T>::put(weights.clone());
+
+            Self::deposit_event(Event::PointWeightsUpdated(weights));
+            Ok(())
+        }
+
+        /// update the config of an unused resource in place, the rental statistics are kept.
+        /// the stake difference is locked or unlocked and the points are adjusted
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn update_resource_config(
+            account_id: OriginFor<T>,
+            index: u64,
+            config: ResourceConfig,
+        ) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
+
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let mut resource = Resources::<T>::get(index).unwrap();
 
-            // if the resource is not the offline, unlock the staking
-            if resource.status != primitives::p_provider::ResourceStatus::Offline {
+            // 1. only the owner can update an unused resource
+            ensure!(resource.account_id == who, Error::<T>::IllegalRequest);
+            ensure!(
+                resource.status == ResourceStatus::Unused,
+                Error::<T>::UnmodifiableStatusNow
+            );
+            Self::check_resource_config(&config)?;
+
+            // 2. lock or unlock the stake difference
+            let old_lock = Self::take_resource_lock(index, &resource.config);
+            let new_lock = Self::compute_resource_lock(&config);
+            if new_lock.staked > old_lock.staked {
+                ensure!(
+                    T::MarketInterface::change_stake_amount(
+                        who.clone(),
+                        ChangeAmountType::Lock,
+                        new_lock.staked - old_lock.staked,
+                        MarketUserStatus::Provider,
+                    ),
+                    Error::<T>::LockAmountFailed,
+                );
+            } else if new_lock.staked < old_lock.staked {
                 T::MarketInterface::change_stake_amount(
                     who.clone(),
                     ChangeAmountType::Unlock,
-                    T::BalanceToNumber::convert(Self::compute_provider_staked_amount(
-                        resource.config.cpu,
-                        resource.config.memory,
-                    )),
+                    old_lock.staked - new_lock.staked,
                     MarketUserStatus::Provider,
                 );
             }
 
-            Self::deposit_event(Event::RemoveSuccess(who, index));
+            // 3. replace the points
+            Self::sub_provider_points(who.clone(), old_lock.points);
+            Self::update_provider_points(who.clone(), new_lock.points);
+
+            // 4. save the config, the rental statistics are unchanged
+            resource.config = config.clone();
+            Resources::<T>::insert(index, resource);
+            ResourceLocks::<T>::insert(index, new_lock);
+
+            Self::deposit_event(Event::ResourceConfigUpdated(who, index, config));
 
             Ok(())
         }
 
-        /// change resource status to unused
+        /// stop accepting orders for a resource in use, the running agreement is completed
+        /// and the resource is offlined when it finishes
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
-        pub fn change_resource_status(account_id: OriginFor<T>, index: u64) -> DispatchResult {
+        pub fn start_draining(account_id: OriginFor<T>, index: u64) -> DispatchResult {
             let who = ensure_signed(account_id)?;
-            
+
             ensure!(
                 Resources::<T>::contains_key(index),
                 Error::<T>::ResourceNotFound
             );
-            let mut resource = Resources::<T>::get(index.clone()).unwrap();
-            
+            let mut resource = Resources::<T>::get(index).unwrap();
+
+            // the resource is offlined when it drains, so only the owner can start it
             ensure!(
                 resource.account_id == who.clone(),
                 Error::<T>::IllegalRequest
             );
+            // an unused resource can be offlined directly
             ensure!(
-                resource.status == ResourceStatus::Offline,
+                resource.status == ResourceStatus::Inuse,
                 Error::<T>::UnmodifiableStatusNow
             );
-            resource.status = ResourceStatus::Unused;
 
-            // relock amount 
-            let staking_amount = Self::compute_provider_staked_amount(
-                resource.config.cpu, 
-                resource.config.memory,
+            let resource_owner = resource.account_id.clone();
+            resource.update_status(ResourceStatus::Draining);
+            Resources::<T>::insert(index, resource);
+            Self::record_status(
+                index,
+                ResourceStatus::Draining,
+                StatusChangeReason::Draining,
             );
 
-            ensure!(
-                T::MarketInterface::change_stake_amount(
-                    who.clone(),
-                    ChangeAmountType::Lock,
-                    T::BalanceToNumber::convert(staking_amount),
-                    MarketUserStatus::Provider,
-                ),
-                Error::<T>::LockAmountFailed,
-            );
-            
-            Self::update_computing_resource(index, resource).ok();
+            Self::deposit_event(Event::ResourceDraining(resource_owner, index));
 
             Ok(())
         }
-    }
-}
 
-impl<T: Config> Pallet<T> {
-    ///
-    fn sub_provider_points(who: T::AccountId, cpu: u64, memory: u64) {
-        // 1. get the provider points
-        let provider_points = ProviderTotalPoints::<T>::get(who.clone());
+        /// register several resources at once, they get consecutive indexes
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2 + 4 * specs.len() as u64))]
+        pub fn register_resources(
+            account_id: OriginFor<T>,
+            specs: Vec<ResourceSpec<BalanceOf<T>>>,
+        ) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
 
-        // 2. sub the points
-        if let Some(mut points) = provider_points {
-            points.sub_points(cpu + memory, 0);
-            ProviderTotalPoints::<T>::insert(who.clone(), points);
-        }
+            ensure!(!specs.is_empty(), Error::<T>::NoResources);
+            ensure!(
+                specs.len() as u32 <= T::MaxBatchResources::get(),
+                Error::<T>::TooManyResources
+            );
 
-        // 3. update total provider resource points
-        let points = cpu + memory;
-        let mut provider_total_resource_points = ProviderTotalResourcePoints::<T>::get();
-        provider_total_resource_points = provider_total_resource_points.saturating_sub(points as u128);
-        ProviderTotalResourcePoints::<T>::set(provider_total_resource_points);
-    }
+            // 0. check the user has staking
+            ensure!(
+                T::MarketInterface::staking_exit(who.clone()),
+                Error::<T>::StakingNotExit
+            );
 
-    /// update provider points
-    fn update_provider_points(who: T::AccountId, cpu: u64, mem: u64) {
-        // 1. get provider total points
-        let mut provider_total_points: ProviderPoints;
-        if ProviderTotalPoints::<T>::contains_key(who.clone()) {
-            provider_total_points = ProviderTotalPoints::<T>::get(who.clone()).unwrap();
-        } else {
-            provider_total_points = ProviderPoints::new(0, 0, 0);
+            // 1. check every spec and compute the aggregate lock
+            let mut locks: Vec<ResourceLock> = Vec::with_capacity(specs.len());
+            let mut staked: u128 = 0;
+            let mut points: u64 = 0;
+            for spec in specs.iter() {
+                Self::check_resource_config(&spec.config)?;
+                // the peer ids are given in their stored form, bounded and canonical
+                ensure!(spec.peer_id.is_valid(), Error::<T>::InvalidPeerId);
+                Self::check_new_index(&spec.peer_id, spec.new_index)?;
+
+                let lock = Self::compute_resource_lock(&spec.config);
+                staked = staked.saturating_add(lock.staked);
+                points = points.saturating_add(lock.points);
+                locks.push(lock);
+            }
+
+            // 2. lock the staking amount once
+            Self::lock_provider_stake(who.clone(), staked, points)?;
+
+            // 3. create the resources, grouped by the block they expire at
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            let first = ResourceIndex::<T>::get();
+            let mut expirations: BTreeMap<T::BlockNumber, Vec<u64>> = BTreeMap::new();
+            let mut indexes: Vec<u64> = Vec::with_capacity(specs.len());
+            for (i, (spec, lock)) in specs.into_iter().zip(locks.into_iter()).enumerate() {
+                let index = first + i as u64;
+                let end_of_block =
+                    Self::insert_resource(who.clone(), index, spec, block_number, lock);
+                expirations.entry(end_of_block).or_default().push(index);
+                indexes.push(index);
+            }
+            for (end_of_block, expiring) in expirations {
+                Self::add_expiring_resources(end_of_block, &expiring);
+            }
+            let last = first + indexes.len() as u64 - 1;
+            ResourceIndex::<T>::set(last + 1);
+            Self::add_provider_resources(who.clone(), &indexes);
+
+            Self::deposit_event(Event::RegisterResourcesSuccess(who, first, last));
+
+            Ok(())
         }
 
-        // 2. update provider total points and resource points
-        let points = cpu + mem;
-        provider_total_points.add_points(points, 0);
-        ProviderTotalPoints::<T>::insert(who.clone(), provider_total_points);
+        /// offer the resource to another account, it is moved when the account accepts
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn transfer_resource(
+            account_id: OriginFor<T>,
+            index: u64,
+            to: T::AccountId,
+        ) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
 
-        // 3. update total provider resource points
-        let mut provider_total_resource_points = ProviderTotalResourcePoints::<T>::get();
-        provider_total_resource_points += points as u128;
-        ProviderTotalResourcePoints::<T>::set(provider_total_resource_points);
-    }
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let resource = Resources::<T>::get(index).unwrap();
 
-    /// modify resources
-    fn update_computing_resource(
-        index: u64,
-        resource: ComputingResource<T::BlockNumber, T::AccountId>,
-    ) -> Result<(), Error<T>> {
-        ensure!(
-            Resources::<T>::contains_key(index),
-            Error::<T>::ResourceNotFound
-        );
+            ensure!(resource.account_id == who, Error::<T>::IllegalRequest);
+            ensure!(to != who, Error::<T>::IllegalRequest);
 
-        Resources::<T>::insert(index, resource);
-        Ok(())
-    }
+            // a new offer replaces the previous one
+            PendingTransfers::<T>::insert(index, to.clone());
 
-    /// compute the staked from cpus and memorys
-    /// * base_cpu = 100 UNIT base_memory = 100 UNIT
-    fn compute_provider_staked_amount(cpus: u64, memory: u64) -> BalanceOf<T> {
-        // Set the base staked fee
-        let base_staked = T::MarketInterface::provider_staking_fee();
-        // compute the staked from cpus and memory
-        let staked: u128 = (cpus.saturating_mul(base_staked as u64) as u128
-            + memory.saturating_mul(base_staked as u64) as u128) as u128;
-        // return the staked
-        T::NumberToBalance::convert(staked)
-    }
-}
+            Self::deposit_event(Event::ResourceTransferOffered(who, index, to));
 
-impl<T: Config> OrderInterface for Pallet<T> {
-    type AccountId = T::AccountId;
-    type BlockNumber = T::BlockNumber;
+            Ok(())
+        }
 
-    fn get_computing_resource_info(
-        index: u64,
-    ) -> Option<ComputingResource<Self::BlockNumber, Self::AccountId>> {
-        Resources::<T>::get(index)
-    }
+        /// withdraw the transfer offer of the resource
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn cancel_resource_transfer(account_id: OriginFor<T>, index: u64) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
 
-    fn update_computing_resource(
-        index: u64,
-        resource_info: ComputingResource<Self::BlockNumber, Self::AccountId>,
-    ) {
-        Self::update_computing_resource(index, resource_info).ok();
-    }
-}
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let resource = Resources::<T>::get(index).unwrap();
 
-impl<T: Config> ProviderInterface<<T as frame_system::Config>::AccountId> for Pallet<T> {
-    fn get_providers_points() -> (Vec<(T::AccountId, p_provider::ProviderPoints)>, u128, u128) {
-        (
-            ProviderTotalPoints::<T>::iter().collect(),
+            ensure!(resource.account_id == who, Error::<T>::IllegalRequest);
+            ensure!(
+                PendingTransfers::<T>::contains_key(index),
+                Error::<T>::TransferNotFound
+            );
+
+            PendingTransfers::<T>::remove(index);
+
+            Ok(())
+        }
+
+        /// accept the resource offered to the caller, the stake lock, the points and
+        /// the running agreements are moved from the previous owner
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn accept_resource_transfer(account_id: OriginFor<T>, index: u64) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
+
+            ensure!(
+                PendingTransfers::<T>::get(index) == Some(who.clone()),
+                Error::<T>::TransferNotFound
+            );
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let mut resource = Resources::<T>::get(index).unwrap();
+            let from = resource.account_id.clone();
+
+            // 0. check the user has staking
+            ensure!(
+                T::MarketInterface::staking_exit(who.clone()),
+                Error::<T>::StakingNotExit
+            );
+
+            // 1. the uptime so far is the previous owner's
+            Self::accrue_resource_duration(index, &from);
+
+            // 2. move the stake lock, an offline resource has no stake locked and
+            // earns its points again when it is recovered
+            let mut lock = Self::take_resource_lock(index, &resource.config);
+            let old_points = lock.points;
+            if resource.status == ResourceStatus::Offline {
+                lock.points = 0;
+                Self::lock_provider_stake(who.clone(), 0, 0)?;
+            } else {
+                Self::lock_provider_stake(who.clone(), lock.staked, lock.points)?;
+                ensure!(
+                    T::MarketInterface::change_stake_amount(
+                        from.clone(),
+                        ChangeAmountType::Unlock,
+                        lock.staked,
+                        MarketUserStatus::Provider,
+                    ),
+                    Error::<T>::UnlockAmountFailed
+                );
+            }
+            ResourceLocks::<T>::insert(index, lock);
+
+            // 3. move the points and the resource
+            Self::sub_provider_points(from.clone(), old_points);
+            Self::remove_provider_resource(from.clone(), index);
+            Self::add_provider_resources(who.clone(), &[index]);
+            resource.account_id = who.clone();
+            Resources::<T>::insert(index, resource);
+            PendingTransfers::<T>::remove(index);
+
+            // 4. the running agreements are served by the new owner
+            T::ResourceOrderInterface::transfer_agreements(index, from.clone(), who.clone());
+
+            Self::deposit_event(Event::ResourceTransferred(from, index, who));
+
+            Ok(())
+        }
+
+        /// request the recovery of an offline resource, the stake is locked again
+        /// and the resource is on probation until it heartbeats for the probation period
+        #[frame_support::transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn request_recovery(account_id: OriginFor<T>, index: u64) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
+
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let mut resource = Resources::<T>::get(index.clone()).unwrap();
+
+            ensure!(
+                resource.account_id == who.clone(),
+                Error::<T>::IllegalRequest
+            );
+            ensure!(
+                resource.status == ResourceStatus::Offline,
+                Error::<T>::UnmodifiableStatusNow
+            );
+
+            // relock amount, the stake slashed by the fault must have been topped up
+            let staking_amount = Self::get_resource_staked_amount(index);
+            ensure!(
+                T::MarketInterface::change_stake_amount(
+                    who.clone(),
+                    ChangeAmountType::Lock,
+                    staking_amount,
+                    MarketUserStatus::Provider,
+                ),
+                Error::<T>::InsufficientStake,
+            );
+            // a resource transferred while offline earns its points again
+            let mut lock = Self::take_resource_lock(index, &resource.config);
+            if lock.points == 0 {
+                lock.points = Self::compute_resource_lock(&resource.config).points;
+                Self::update_provider_points(who.clone(), lock.points);
+            }
+            ResourceLocks::<T>::insert(index, lock);
+
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            resource.update_status(ResourceStatus::Probation);
+            Self::update_computing_resource(index, resource).ok();
+            ResourceProbations::<T>::insert(index, Probation::new(block_number));
+            Self::record_status(
+                index,
+                ResourceStatus::Probation,
+                StatusChangeReason::RecoveryRequested,
+            );
+
+            Self::deposit_event(Event::ResourceOnProbation(who, index));
+
+            Ok(())
+        }
+
+        /// heartbeat of a resource on probation, it is unused again once the heartbeats
+        /// cover the probation period
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn probation_heartbeat(account_id: OriginFor<T>, index: u64) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
+
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let mut resource = Resources::<T>::get(index).unwrap();
+
+            // determine whether it is the owner or its operator
+            ensure!(
+                T::OperatorInterface::is_authorized(
+                    &resource.account_id,
+                    &who,
+                    OperatorScope::Resource(index),
+                ),
+                Error::<T>::IllegalRequest
+            );
+            ensure!(
+                resource.status == ResourceStatus::Probation,
+                Error::<T>::UnmodifiableStatusNow
+            );
+
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            let mut probation =
+                ResourceProbations::<T>::get(index).unwrap_or_else(|| Probation::new(block_number));
+
+            // a late heartbeat restarts the probation
+            if block_number.saturating_sub(probation.last_heartbeat)
+                > T::ProbationHeartbeatInterval::get()
+            {
+                probation = Probation::new(block_number);
+                Self::deposit_event(Event::ProbationRestarted(index, block_number));
+            }
+            probation.last_heartbeat = block_number;
+
+            if block_number.saturating_sub(probation.start) < T::ProbationPeriod::get() {
+                ResourceProbations::<T>::insert(index, probation);
+                return Ok(());
+            }
+
+            // the probation is passed
+            let resource_owner = resource.account_id.clone();
+            ResourceProbations::<T>::remove(index);
+            resource.update_status(ResourceStatus::Unused);
+            Self::update_computing_resource(index, resource).ok();
+            Self::record_status(
+                index,
+                ResourceStatus::Unused,
+                StatusChangeReason::ProbationPassed,
+            );
+            // accrue duration points again
+            ResourceOnlineSince::<T>::insert(index, block_number);
+
+            Self::deposit_event(Event::ResourceRecovered(resource_owner, index));
+
+            Ok(())
+        }
+
+        /// extend the resource by the hours when it is about to expire, zero hours turn it off.
+        /// the extension is attempted AutoExtendWindow blocks before the end of rent and locks
+        /// the stake the resource needs under the current weights
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_auto_extend(account_id: OriginFor<T>, index: u64, hours: u32) -> DispatchResult {
+            let who = ensure_signed(account_id)?;
+
+            ensure!(
+                Resources::<T>::contains_key(index),
+                Error::<T>::ResourceNotFound
+            );
+            let resource = Resources::<T>::get(index).unwrap();
+
+            ensure!(resource.account_id == who, Error::<T>::IllegalRequest);
+
+            if hours == 0 {
+                AutoExtendHours::<T>::remove(index);
+            } else {
+                AutoExtendHours::<T>::insert(index, hours);
+            }
+
+            Self::deposit_event(Event::AutoExtendSet(who, index, hours));
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Config> Pallet<T> {
+    ///
+    fn sub_provider_points(who: T::AccountId, points: u64) {
+        // 1. get the provider points
+        let provider_points = ProviderTotalPoints::<T>::get(who.clone());
+
+        // 2. sub the points
+        if let Some(mut provider_points) = provider_points {
+            provider_points.sub_points(points, 0);
+            ProviderTotalPoints::<T>::insert(who.clone(), provider_points);
+        }
+
+        // 3. update total provider resource points
+        let mut provider_total_resource_points = ProviderTotalResourcePoints::<T>::get();
+        provider_total_resource_points =
+            provider_total_resource_points.saturating_sub(points as u128);
+        ProviderTotalResourcePoints::<T>::set(provider_total_resource_points);
+    }
+
+    /// update provider points
+    fn update_provider_points(who: T::AccountId, points: u64) {
+        // 1. get provider total points
+        let mut provider_total_points: ProviderPoints;
+        if ProviderTotalPoints::<T>::contains_key(who.clone()) {
+            provider_total_points = ProviderTotalPoints::<T>::get(who.clone()).unwrap();
+        } else {
+            provider_total_points = ProviderPoints::new(0, 0, 0);
+        }
+
+        // 2. update provider total points and resource points
+        provider_total_points.add_points(points, 0);
+        ProviderTotalPoints::<T>::insert(who.clone(), provider_total_points);
+
+        // 3. update total provider resource points
+        let mut provider_total_resource_points = ProviderTotalResourcePoints::<T>::get();
+        provider_total_resource_points += points as u128;
+        ProviderTotalResourcePoints::<T>::set(provider_total_resource_points);
+    }
+
+    /// expire the resources due up to now, at most MaxExpirationsPerBlock of them.
+    /// the sweep starts from the cursor, so the resources left over are expired in the next blocks
+    fn expire_resources(now: T::BlockNumber) -> Weight {
+        let mut budget = T::MaxExpirationsPerBlock::get();
+        let mut cursor = ExpiryCursor::<T>::get();
+        let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+        while cursor <= now && budget > 0 {
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(1, 1));
+            let mut expired = FutureExpiredResource::<T>::get(cursor).unwrap_or_default();
+            // an empty block costs as much as an expiration, so the catch up is bounded
+            if expired.is_empty() {
+                budget -= 1;
+                FutureExpiredResource::<T>::remove(cursor);
+                cursor += One::one();
+                continue;
+            }
+
+            let rest = expired.split_off(expired.len().min(budget as usize));
+            budget -= expired.len() as u32;
+            for resource_index in expired {
+                weight = weight.saturating_add(T::DbWeight::get().reads(1));
+                // an entry left behind by an extension, the resource expires at its new end
+                let stale = Resources::<T>::get(resource_index)
+                    .map_or(false, |x| x.rental_info.end_of_rent > cursor);
+                if stale {
+                    continue;
+                }
+                // resource, provider list, points and staking changes
+                weight = weight.saturating_add(T::DbWeight::get().reads_writes(6, 6));
+                Self::expire_resource(resource_index);
+                if cursor < now {
+                    Self::deposit_event(Event::ResourceExpiredLate(resource_index, cursor));
+                }
+            }
+
+            if rest.is_empty() {
+                // delete expired resource mappings
+                FutureExpiredResource::<T>::remove(cursor);
+                cursor += One::one();
+            } else {
+                FutureExpiredResource::<T>::insert(cursor, rest);
+            }
+        }
+
+        ExpiryCursor::<T>::put(cursor);
+        weight
+    }
+
+    /// extend the resources expiring AutoExtendWindow blocks from now whose provider turned it on,
+    /// the extended resources move to their new expiry
+    fn extend_expiring_resources(now: T::BlockNumber) -> Weight {
+        let end = now.saturating_add(T::AutoExtendWindow::get());
+        let mut weight = T::DbWeight::get().reads(1);
+        let expiring = match FutureExpiredResource::<T>::get(end) {
+            Some(x) => x,
+            None => return weight,
+        };
+
+        let mut remaining = Vec::new();
+        for index in expiring {
+            weight = weight.saturating_add(T::DbWeight::get().reads(1));
+            if AutoExtendHours::<T>::contains_key(index) {
+                // resource, locks, points and staking changes
+                weight = weight.saturating_add(T::DbWeight::get().reads_writes(6, 6));
+                if Self::auto_extend(index, end) {
+                    continue;
+                }
+            }
+            remaining.push(index);
+        }
+
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+        if remaining.is_empty() {
+            FutureExpiredResource::<T>::remove(end);
+        } else {
+            FutureExpiredResource::<T>::insert(end, remaining);
+        }
+        weight
+    }
+
+    /// extend the resource expiring at `end` if its provider turned it on, return whether it
+    /// was extended. the offline and draining resources are not extended
+    fn auto_extend(index: u64, end: T::BlockNumber) -> bool {
+        let hours = match AutoExtendHours::<T>::get(index) {
+            Some(x) => x,
+            None => return false,
+        };
+        let mut resource = match Resources::<T>::get(index) {
+            Some(x) => x,
+            None => return false,
+        };
+        if resource.status == ResourceStatus::Offline || resource.status == ResourceStatus::Draining
+        {
+            return false;
+        }
+        let who = resource.account_id.clone();
+
+        // 1. relock the stake the resource needs under the current weights,
+        // a provider whose locked stake was slashed does not have it anymore
+        let old_lock = Self::take_resource_lock(index, &resource.config);
+        let new_lock = Self::compute_resource_lock(&resource.config);
+        let unlocked = T::MarketInterface::change_stake_amount(
+            who.clone(),
+            ChangeAmountType::Unlock,
+            old_lock.staked,
+            MarketUserStatus::Provider,
+        );
+        if !unlocked
+            || !T::MarketInterface::change_stake_amount(
+                who.clone(),
+                ChangeAmountType::Lock,
+                new_lock.staked,
+                MarketUserStatus::Provider,
+            )
+        {
+            if unlocked {
+                T::MarketInterface::change_stake_amount(
+                    who.clone(),
+                    ChangeAmountType::Lock,
+                    old_lock.staked,
+                    MarketUserStatus::Provider,
+                );
+            }
+            ResourceLocks::<T>::insert(index, old_lock);
+            Self::deposit_event(Event::AutoExtendSkipped(index, new_lock.staked));
+            return false;
+        }
+        Self::sub_provider_points(who.clone(), old_lock.points);
+        Self::update_provider_points(who, new_lock.points);
+        ResourceLocks::<T>::insert(index, new_lock);
+
+        // 2. move the resource to its new expiry, the caller removes it from the current one
+        let duration_add = TryInto::<T::BlockNumber>::try_into(hours.saturating_mul(600))
+            .unwrap_or_else(|_| Zero::zero());
+        let end_of_rent = end + duration_add;
+        resource.add_resource_duration(duration_add);
+        Resources::<T>::insert(index, resource);
+        Self::add_expiring_resources(end_of_rent, &[index]);
+
+        Self::deposit_event(Event::ResourceAutoExtended(index, end_of_rent));
+        true
+    }
+
+    /// delete an expired resource, unlock its staking and remove its points
+    fn expire_resource(resource_index: u64) {
+        let resource_option = Resources::<T>::get(resource_index);
+        if resource_option.is_some() {
+            let resource = resource_option.unwrap();
+            let account_id = resource.account_id.clone();
+            // delete associated resource
+            let account_resources = Providers::<T>::get(&account_id);
+            if account_resources.is_some() {
+                let _resource: Vec<u64> = account_resources
+                    .unwrap()
+                    .into_iter()
+                    .filter(|x| *x != resource_index.clone())
+                    .collect();
+                Providers::<T>::insert(account_id.clone(), _resource);
+            }
+
+            // check the user has other resource
+            if let Some(list) = Providers::<T>::get(account_id.clone()) {
+                if list.len() == 0 {
+                    // delete the user
+                    Providers::<T>::remove(account_id.clone());
+                    // update the provider online list
+                    let mut provider_list = ProviderOnlineList::<T>::get();
+                    if let Ok(index) = provider_list.binary_search(&account_id) {
+                        provider_list.remove(index);
+                        ProviderOnlineList::<T>::set(provider_list);
+                    }
+                }
+            }
+            //remove resource
+            Self::accrue_resource_duration(resource_index, &account_id);
+            ResourceOnlineSince::<T>::remove(resource_index);
+            Resources::<T>::remove(resource_index);
+            Self::remove_price_index(resource_index, resource.rental_info.rent_unit_price);
+            ResourceReputations::<T>::remove(resource_index);
+            PendingTransfers::<T>::remove(resource_index);
+            AutoExtendHours::<T>::remove(resource_index);
+            ResourceProbations::<T>::remove(resource_index);
+            ResourceStatusHistory::<T>::remove(resource_index);
+            InvalidPeerIds::<T>::remove(resource_index);
+            // reduce count
+            let count = ResourceCount::<T>::get();
+            ResourceCount::<T>::set(count - 1);
+
+            // update provider points
+            let lock = Self::take_resource_lock(resource_index, &resource.config);
+            Self::sub_provider_points(account_id.clone(), lock.points);
+
+            // unlock the staking, it was unlocked when the resource went offline
+            if resource.status != ResourceStatus::Offline {
+                T::MarketInterface::change_stake_amount(
+                    account_id.clone(),
+                    ChangeAmountType::Unlock,
+                    lock.staked,
+                    MarketUserStatus::Provider,
+                );
+            }
+        }
+    }
+
+    /// delete the resource, unlock its staking and remove its points
+    /// delete associated resource, the provider without resources leaves the online list
+    fn remove_provider_resource(who: T::AccountId, index: u64) {
+        let option = Providers::<T>::get(who.clone());
+        if option.is_some() {
+            let resource_vec: Vec<u64> = option
+                .unwrap()
+                .into_iter()
+                .filter(|x| *x != index.clone())
+                .collect();
+            Providers::<T>::insert(who.clone(), resource_vec);
+        }
+
+        // check the user has other resources
+        if let Some(list) = Providers::<T>::get(who.clone()) {
+            if list.len() == 0 {
+                // delete the user
+                Providers::<T>::remove(who.clone());
+                // update the provider online list
+                let mut provider_list = ProviderOnlineList::<T>::get();
+                if let Ok(index) = provider_list.binary_search(&who) {
+                    provider_list.remove(index);
+                    ProviderOnlineList::<T>::set(provider_list);
+                }
+            }
+        }
+    }
+
+    fn do_offline(
+        who: T::AccountId,
+        index: u64,
+        resource: ComputingResource<T::BlockNumber, T::AccountId>,
+    ) {
+        // delete associated resource
+        Self::remove_provider_resource(who.clone(), index);
+
+        // reduce count
+        let count = ResourceCount::<T>::get();
+        ResourceCount::<T>::set(count.saturating_sub(1));
+
+        // Delete resources associated with future expirations
+        Self::remove_expiring_resource(resource.rental_info.end_of_rent, index);
+
+        //delete resource
+        Self::accrue_resource_duration(index, &who);
+        ResourceOnlineSince::<T>::remove(&index);
+        Resources::<T>::remove(&index);
+        Self::remove_price_index(index, resource.rental_info.rent_unit_price);
+        PendingTransfers::<T>::remove(&index);
+        AutoExtendHours::<T>::remove(&index);
+        ResourceProbations::<T>::remove(&index);
+        ResourceStatusHistory::<T>::remove(&index);
+        ResourceReputations::<T>::remove(&index);
+        InvalidPeerIds::<T>::remove(&index);
+        let lock = Self::take_resource_lock(index, &resource.config);
+
+        // update provider points
+        Self::sub_provider_points(who.clone(), lock.points);
+
+        // if the resource is not the offline, unlock the staking
+        if resource.status != primitives::p_provider::ResourceStatus::Offline {
+            T::MarketInterface::change_stake_amount(
+                who.clone(),
+                ChangeAmountType::Unlock,
+                lock.staked,
+                MarketUserStatus::Provider,
+            );
+        }
+
+        Self::deposit_event(Event::RemoveSuccess(who, index));
+    }
+
+    /// the reputation decayed up to now
+    fn decayed_reputation(
+        reputation: Option<Reputation<T::BlockNumber>>,
+    ) -> Reputation<T::BlockNumber> {
+        let now = <frame_system::Pallet<T>>::block_number();
+        match reputation {
+            Some(mut reputation) => {
+                let period = T::ReputationDecayPeriod::get().max(One::one());
+                let periods = (now.saturating_sub(reputation.updated) / period)
+                    .try_into()
+                    .unwrap_or(u32::MAX);
+                reputation.decay(periods);
+                // keep the rest of the period for the next decay
+                reputation.updated = reputation
+                    .updated
+                    .saturating_add(period.saturating_mul(periods.into()));
+                reputation
+            }
+            None => Reputation::new(now),
+        }
+    }
+
+    /// current reputation of the resource
+    pub fn resource_reputation(index: u64) -> u32 {
+        Self::decayed_reputation(ResourceReputations::<T>::get(index)).score
+    }
+
+    /// current reputation of the provider
+    pub fn provider_reputation(who: &T::AccountId) -> u32 {
+        Self::decayed_reputation(ProviderReputations::<T>::get(who)).score
+    }
+
+    /// parse the base58 or raw multihash peer id of a resource
+    fn parse_peer_id(peer_id: &[u8]) -> Result<PeerId, DispatchError> {
+        PeerId::parse(peer_id).map_err(|_| Error::<T>::InvalidPeerId.into())
+    }
+
+    /// check the peer is not registered again at an index it already has
+    fn check_new_index(peer_id: &PeerId, new_index: u64) -> DispatchResult {
+        // if new index < current index and the compute resource exit
+        // and the peer id is same, return error
+        if new_index < ResourceIndex::<T>::get() {
+            if let Some(compute_resource) = Resources::<T>::get(new_index) {
+                // repeat Registration, return err
+                ensure!(
+                    !peer_id.eq(&compute_resource.peer_id),
+                    Error::<T>::ResourceAlreadyExist
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// lock the provider stake and add the points of new resources
+    fn lock_provider_stake(who: T::AccountId, staked: u128, points: u64) -> DispatchResult {
+        ensure!(
+            T::MarketInterface::change_stake_amount(
+                who.clone(),
+                ChangeAmountType::Lock,
+                staked,
+                MarketUserStatus::Provider,
+            ),
+            Error::<T>::LockAmountFailed,
+        );
+
+        // if user not in the online list , add the user to the list
+        let mut provider_online_list = ProviderOnlineList::<T>::get();
+        if let Err(index) = provider_online_list.binary_search(&who) {
+            provider_online_list.insert(index, who.clone());
+        }
+        ProviderOnlineList::<T>::set(provider_online_list);
+
+        // compute and update the provider points
+        Self::update_provider_points(who, points);
+
+        Ok(())
+    }
+
+    /// create the resource at the index and return the block it expires at,
+    /// the expiry bucket and the provider resources are left to the caller.
+    /// the peer id of the spec is validated by the caller
+    fn insert_resource(
+        who: T::AccountId,
+        index: u64,
+        spec: ResourceSpec<BalanceOf<T>>,
+        block_number: T::BlockNumber,
+        lock: ResourceLock,
+    ) -> T::BlockNumber {
+        // create the statistice
+        let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
+        // calculate persistent blocks
+        let rent_blocks = TryInto::<T::BlockNumber>::try_into(&spec.rent_duration_hour * 600)
+            .ok()
+            .unwrap();
+        // the block number at which the calculation ends
+        let end_of_block = block_number + rent_blocks;
+        // create the resource rental information
+        let resource_rental_info = ResourceRentalInfo::new(
+            T::BalanceToNumber::convert(spec.price),
+            rent_blocks,
+            end_of_block,
+        );
+        // create the computing resource: include all the info(resource, statistics, rental_info, and source status)
+        let computing_resource = ComputingResource::new(
+            index,
+            who,
+            spec.peer_id,
+            spec.config,
+            statistics,
+            resource_rental_info,
+            ResourceStatus::Unused,
+        );
+
+        // increase resources
+        Self::insert_price_index(index, computing_resource.rental_info.rent_unit_price);
+        Resources::<T>::insert(index, computing_resource);
+        Self::record_status(
+            index,
+            ResourceStatus::Unused,
+            StatusChangeReason::Registered,
+        );
+        ResourceOnlineSince::<T>::insert(index, block_number);
+        ResourceLocks::<T>::insert(index, lock);
+        // increase the total
+        ResourceCount::<T>::mutate(|count| *count += 1);
+
+        end_of_block
+    }
+
+    /// add the resource to the price index
+    fn insert_price_index(index: u64, price: u128) {
+        ResourcePriceIndex::<T>::mutate(|prices| {
+            if let Err(position) = prices.binary_search(&(price, index)) {
+                prices.insert(position, (price, index));
+            }
+        });
+    }
+
+    /// remove the resource from the price index
+    fn remove_price_index(index: u64, price: u128) {
+        ResourcePriceIndex::<T>::mutate(|prices| {
+            if let Ok(position) = prices.binary_search(&(price, index)) {
+                prices.remove(position);
+            }
+        });
+    }
+
+    /// associate the block number and the resource ids to expire.
+    /// the blocks the sweep has passed are not processed again, the resources due there
+    /// expire at the cursor
+    fn add_expiring_resources(end_of_block: T::BlockNumber, indexes: &[u64]) {
+        let end_of_block = end_of_block.max(ExpiryCursor::<T>::get());
+        FutureExpiredResource::<T>::mutate(end_of_block, |expired_resource| {
+            expired_resource
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(indexes)
+        });
+    }
+
+    /// remove the resource id from the block it expires at
+    fn remove_expiring_resource(end_of_block: T::BlockNumber, index: u64) {
+        let end_of_block = end_of_block.max(ExpiryCursor::<T>::get());
+        FutureExpiredResource::<T>::mutate_exists(end_of_block, |expired_resource| {
+            if let Some(resources) = expired_resource {
+                resources.retain(|x| *x != index);
+                if resources.is_empty() {
+                    *expired_resource = None;
+                }
+            }
+        });
+    }
+
+    /// update publisher associated resource
+    fn add_provider_resources(who: T::AccountId, indexes: &[u64]) {
+        Providers::<T>::mutate(who, |resources| {
+            resources
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(indexes)
+        });
+    }
+
+    /// add the status change to the resource history, the oldest change is dropped
+    /// once the history is full
+    fn record_status(index: u64, status: ResourceStatus, reason: StatusChangeReason) {
+        let block_number = <frame_system::Pallet<T>>::block_number();
+        ResourceStatusHistory::<T>::mutate(index, |history| {
+            history.push(StatusChange::new(status, reason, block_number));
+            let max = T::MaxStatusHistory::get() as usize;
+            if history.len() > max {
+                history.drain(..history.len() - max);
+            }
+        });
+    }
+
+    /// the resources matching the query, sorted and paged
+    pub fn query_resources(
+        query: ResourceQuery,
+    ) -> Vec<ComputingResource<T::BlockNumber, T::AccountId>> {
+        let now = <frame_system::Pallet<T>>::block_number();
+        let mut resources: Vec<ComputingResource<T::BlockNumber, T::AccountId>> =
+            Resources::<T>::iter_values()
+                .filter(|resource| {
+                    let remaining: u64 = resource
+                        .rental_info
+                        .end_of_rent
+                        .saturating_sub(now)
+                        .try_into()
+                        .unwrap_or(u64::MAX);
+                    query.matches(
+                        &resource.config,
+                        &resource.status,
+                        resource.rental_info.rent_unit_price,
+                        remaining,
+                    )
+                })
+                .collect();
+
+        match query.sort {
+            ResourceSort::Index => resources.sort_by_key(|x| x.index),
+            ResourceSort::PriceAsc => {
+                resources.sort_by_key(|x| (x.rental_info.rent_unit_price, x.index))
+            }
+            ResourceSort::PriceDesc => resources.sort_by(|a, b| {
+                b.rental_info
+                    .rent_unit_price
+                    .cmp(&a.rental_info.rent_unit_price)
+                    .then(a.index.cmp(&b.index))
+            }),
+        }
+
+        resources
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(match query.limit {
+                0 => MAX_QUERY_LIMIT,
+                x => x.min(MAX_QUERY_LIMIT),
+            } as usize)
+            .collect()
+    }
+
+    /// add the whole hours the resource has been online to its provider duration points,
+    /// the rest of the hour is kept for the next accrual
+    fn accrue_resource_duration(index: u64, who: &T::AccountId) {
+        let since = match ResourceOnlineSince::<T>::get(index) {
+            Some(x) => x,
+            None => return,
+        };
+        let now = <frame_system::Pallet<T>>::block_number();
+        let online: u64 = now.saturating_sub(since).try_into().unwrap_or(u64::MAX);
+        let hours = online / 600;
+        if hours == 0 {
+            return;
+        }
+
+        // the total is the sum of the points of the providers
+        if let Some(mut points) = ProviderTotalPoints::<T>::get(who) {
+            points.add_points(0, hours);
+            ProviderTotalPoints::<T>::insert(who, points);
+            ProviderTotalDurationPoints::<T>::mutate(|total| {
+                *total = total.saturating_add(hours as u128)
+            });
+        }
+
+        let accrued: T::BlockNumber = (hours.saturating_mul(600)).try_into().unwrap_or(now);
+        ResourceOnlineSince::<T>::insert(index, since.saturating_add(accrued));
+    }
+
+    /// check the config is within the limits and its descriptions are well formed
+    fn check_resource_config(config: &ResourceConfig) -> DispatchResult {
+        ensure!(
+            ProviderResourceLimits::<T>::get().contains(config),
+            Error::<T>::IllegalRequest
+        );
+
+        // check the region code and the accelerator description
+        ensure!(
+            !config.region.is_empty() && config.region.len() <= MAX_REGION_LEN,
+            Error::<T>::IllegalRequest
+        );
+        if let Some(accelerator) = &config.accelerator {
+            ensure!(
+                !accelerator.is_empty() && accelerator.len() <= MAX_ACCELERATOR_LEN,
+                Error::<T>::IllegalRequest
+            );
+        }
+
+        Ok(())
+    }
+
+    /// modify resources
+    fn update_computing_resource(
+        index: u64,
+        resource: ComputingResource<T::BlockNumber, T::AccountId>,
+    ) -> Result<(), Error<T>> {
+        ensure!(
+            Resources::<T>::contains_key(index),
+            Error::<T>::ResourceNotFound
+        );
+
+        Resources::<T>::insert(index, resource);
+        Ok(())
+    }
+
+    /// compute the staked from the resource points under the stake weights
+    /// * every point (a cpu, a GB of memory, ...) stakes base_staked = 100 UNIT
+    fn compute_provider_staked_amount(config: &ResourceConfig) -> BalanceOf<T> {
+        // Set the base staked fee
+        let base_staked = T::MarketInterface::provider_staking_fee();
+        // compute the staked from the resource points
+        let points = config.resource_points(&StakeWeights::<T>::get());
+        let staked: u128 = (points as u128).saturating_mul(base_staked);
+        // return the staked
+        T::NumberToBalance::convert(staked)
+    }
+
+    /// the stake and the points of a resource registered now
+    fn compute_resource_lock(config: &ResourceConfig) -> ResourceLock {
+        ResourceLock::new(
+            T::BalanceToNumber::convert(Self::compute_provider_staked_amount(config)),
+            config.resource_points(&PointWeights::<T>::get()),
+        )
+    }
+
+    /// remove the record of a resource that is deleted
+    fn take_resource_lock(index: u64, config: &ResourceConfig) -> ResourceLock {
+        ResourceLocks::<T>::take(index).unwrap_or_else(|| Self::compute_resource_lock(config))
+    }
+}
+
+impl<T: Config> OrderInterface for Pallet<T> {
+    type AccountId = T::AccountId;
+    type BlockNumber = T::BlockNumber;
+
+    fn get_computing_resource_info(
+        index: u64,
+    ) -> Option<ComputingResource<Self::BlockNumber, Self::AccountId>> {
+        Resources::<T>::get(index)
+    }
+
+    fn update_computing_resource(
+        index: u64,
+        resource_info: ComputingResource<Self::BlockNumber, Self::AccountId>,
+    ) {
+        // a faulty resource loses the duration it has not accrued yet
+        if resource_info.status == ResourceStatus::Offline {
+            ResourceOnlineSince::<T>::remove(index);
+        }
+
+        // the orders only move the resource to these statuses
+        let reason = match resource_info.status {
+            ResourceStatus::Locked => Some(StatusChangeReason::Ordered),
+            ResourceStatus::Inuse => Some(StatusChangeReason::Rented),
+            ResourceStatus::Unused => Some(StatusChangeReason::Released),
+            ResourceStatus::Offline => Some(StatusChangeReason::Faulted),
+            _ => None,
+        };
+        let changed = Resources::<T>::get(index)
+            .map_or(false, |resource| resource.status != resource_info.status);
+        let status = resource_info.status;
+
+        if Self::update_computing_resource(index, resource_info).is_ok() && changed {
+            if let Some(reason) = reason {
+                Self::record_status(index, status, reason);
+            }
+        }
+    }
+
+    fn offline_computing_resource(index: u64) {
+        if let Some(resource) = Resources::<T>::get(index) {
+            Self::do_offline(resource.account_id.clone(), index, resource);
+        }
+    }
+
+    fn update_reputation(index: u64, event: ReputationEvent) {
+        let resource = match Resources::<T>::get(index) {
+            Some(x) => x,
+            None => return,
+        };
+
+        let mut reputation = Self::decayed_reputation(ResourceReputations::<T>::get(index));
+        reputation.apply(event);
+        ResourceReputations::<T>::insert(index, reputation);
+
+        let mut reputation =
+            Self::decayed_reputation(ProviderReputations::<T>::get(&resource.account_id));
+        reputation.apply(event);
+        ProviderReputations::<T>::insert(&resource.account_id, reputation);
+    }
+
+    fn match_resource(
+        requirement: &ResourceRequirement,
+        end: Self::BlockNumber,
+    ) -> (Option<u64>, u64) {
+        let mut read = 0;
+        // the index is sorted by price, the first resource matching is the cheapest
+        let index = ResourcePriceIndex::<T>::get()
+            .into_iter()
+            .take_while(|(price, _)| *price <= requirement.max_price)
+            .find(|(_, index)| {
+                read += 1;
+                match Resources::<T>::get(index) {
+                    Some(resource) => {
+                        resource.status == ResourceStatus::Unused
+                            && requirement.matches(&resource.config)
+                            && end < resource.rental_info.end_of_rent
+                    }
+                    None => false,
+                }
+            })
+            .map(|(_, index)| index);
+        (index, read)
+    }
+
+    fn get_resource_staked_amount(index: u64) -> u128 {
+        match ResourceLocks::<T>::get(index) {
+            Some(lock) => lock.staked,
+            None => Resources::<T>::get(index)
+                .map(|resource| Self::compute_resource_lock(&resource.config).staked)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl<T: Config> ProviderInterface<<T as frame_system::Config>::AccountId> for Pallet<T> {
+    fn accrue_duration_points() {
+        let online: Vec<u64> = ResourceOnlineSince::<T>::iter_keys().collect();
+        for index in online {
+            match Resources::<T>::get(index) {
+                Some(resource) => Self::accrue_resource_duration(index, &resource.account_id),
+                None => ResourceOnlineSince::<T>::remove(index),
+            }
+        }
+    }
+
+    fn get_providers_points() -> (Vec<(T::AccountId, p_provider::ProviderPoints)>, u128, u128) {
+        (
+            ProviderTotalPoints::<T>::iter().collect(),
             ProviderTotalResourcePoints::<T>::get(),
-            ResourceCount::<T>::get() as u128,
+            ProviderTotalDurationPoints::<T>::get(),
         )
     }
+
+    fn reset_duration_points() {
+        let providers: Vec<(T::AccountId, ProviderPoints)> =
+            ProviderTotalPoints::<T>::iter().collect();
+        for (who, mut points) in providers {
+            if points.duration_points > 0 {
+                let duration_points = points.duration_points;
+                points.sub_points(0, duration_points);
+                ProviderTotalPoints::<T>::insert(who, points);
+            }
+        }
+        ProviderTotalDurationPoints::<T>::set(0);
+    }
+
+    fn get_provider_reputation(who: &T::AccountId) -> u32 {
+        Self::provider_reputation(who)
+    }
+}
+
+// Determine whether we run the storage migration logic
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Releases {
+    V1_0_0,
+    V2_0_0,
+    V3_0_0,
+    V4_0_0,
+    V5_0_0,
+    V6_0_0,
+    V7_0_0,
+}
+
+impl Default for Releases {
+    fn default() -> Self {
+        Releases::V1_0_0
+    }
+}
+
+pub mod migrations {
+
+    pub mod v2 {
+        use super::super::*;
+
+        /// computing resource before the storage, bandwidth, architecture, region and accelerator were added
+        #[derive(Decode)]
+        struct OldComputingResource<BlockNumber, AccountId> {
+            index: u64,
+            account_id: AccountId,
+            peer_id: Vec<u8>,
+            config: ResourceConfigV1,
+            rental_statistics: ResourceRentalStatistics,
+            rental_info: ResourceRentalInfo<BlockNumber>,
+            status: ResourceStatus,
+        }
+
+        /// extend the resource config, the new fields are unknown for the registered resources.
+        /// the resource points are unchanged, so are the staked amounts and the provider points
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+
+            Resources::<T>::translate::<OldComputingResource<T::BlockNumber, T::AccountId>, _>(
+                |_, old| {
+                    count += 1;
+                    Some(ComputingResource::new(
+                        old.index,
+                        old.account_id,
+                        PeerId::new_unchecked(old.peer_id),
+                        old.config.into(),
+                        old.rental_statistics,
+                        old.rental_info,
+                        old.status,
+                    ))
+                },
+            );
+
+            StorageVersion::<T>::put(Releases::V2_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, count + 1)
+        }
+    }
+    pub mod v3 {
+        use super::super::*;
+
+        /// record the stake and the points of the registered resources, they are computed
+        /// with the default weights which are the formula they were locked with
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+
+            for (index, resource) in Resources::<T>::iter() {
+                count += 1;
+                ResourceLocks::<T>::insert(
+                    index,
+                    Pallet::<T>::compute_resource_lock(&resource.config),
+                );
+            }
+
+            StorageVersion::<T>::put(Releases::V3_0_0);
+
+            T::DbWeight::get().reads_writes(count + 3, count + 1)
+        }
+    }
+    pub mod v4 {
+        use super::super::*;
+
+        /// start the expiry sweep from the current block, the earlier blocks have been processed
+        pub fn migrate<T: Config>() -> Weight {
+            ExpiryCursor::<T>::put(<frame_system::Pallet<T>>::block_number());
+            StorageVersion::<T>::put(Releases::V4_0_0);
+
+            T::DbWeight::get().reads_writes(2, 2)
+        }
+    }
+    pub mod v5 {
+        use super::super::*;
+
+        /// the resources that are not offline accrue duration points from now
+        pub fn migrate<T: Config>() -> Weight {
+            let now = <frame_system::Pallet<T>>::block_number();
+            let mut count: u64 = 0;
+
+            for (index, resource) in Resources::<T>::iter() {
+                count += 1;
+                if resource.status != ResourceStatus::Offline {
+                    ResourceOnlineSince::<T>::insert(index, now);
+                }
+            }
+
+            StorageVersion::<T>::put(Releases::V5_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, count + 1)
+        }
+    }
+    pub mod v6 {
+        use super::super::*;
+
+        /// flag the resources whose peer id is not a valid libp2p peer id.
+        /// they are kept, the provider takes them offline and registers them again
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+            let mut flagged: u64 = 0;
+
+            for (index, resource) in Resources::<T>::iter() {
+                count += 1;
+                if !resource.peer_id.is_valid() {
+                    flagged += 1;
+                    InvalidPeerIds::<T>::insert(index, true);
+                }
+            }
+
+            StorageVersion::<T>::put(Releases::V6_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, flagged + 1)
+        }
+    }
+    pub mod v7 {
+        use super::super::*;
+
+        /// index the registered resources by price
+        pub fn migrate<T: Config>() -> Weight {
+            let mut prices: Vec<(u128, u64)> = Resources::<T>::iter()
+                .map(|(index, resource)| (resource.rental_info.rent_unit_price, index))
+                .collect();
+            prices.sort();
+            let count = prices.len() as u64;
+            ResourcePriceIndex::<T>::put(prices);
+
+            StorageVersion::<T>::put(Releases::V7_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, 2)
+        }
+    }
 }
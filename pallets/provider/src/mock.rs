@@ -24,6 +24,16 @@ pub const HOURS: BlockNumber = MINUTES * 60;
 parameter_types! {
     // polling interval
     pub const ResourceInterval: BlockNumber = 3 * HOURS;
+    // maximum number of resources expired in a block
+    pub const MaxExpirationsPerBlock: u32 = 2;
+    // the reputation decays a tenth of the way to neutral every period
+    pub const ReputationDecayPeriod: BlockNumber = HOURS;
+    pub const MaxBatchResources: u32 = 3;
+    pub const ProbationPeriod: BlockNumber = HOURS;
+    pub const ProbationHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // the auto extension is attempted this long before the end of rent
+    pub const AutoExtendWindow: BlockNumber = 10 * MINUTES;
+    pub const MaxStatusHistory: u32 = 3;
     // health check interval
     pub const HealthCheckInterval: BlockNumber = 10 * MINUTES;
 }
@@ -112,6 +122,7 @@ impl pallet_gateway::Config for Test {
     type GatewayNodeHeartbeatInterval = GatewayNodeHeartbeatInterval;
 
     type MarketInterface = Market;
+    type OperatorInterface = Market;
 }
 
 impl pallet_market::Config for Test {
@@ -133,6 +144,7 @@ impl pallet_chunkcycle::Config for Test {
     type NumberToBalance = ConvertInto;
     type BalanceToNumber = ConvertInto;
     type MarketInterface = Market;
+    type ProviderInterface = Provider;
 }
 
 impl pallet_provider::Config for Test {
@@ -142,6 +154,15 @@ impl pallet_provider::Config for Test {
     type NumberToBalance = ConvertInto;
     type ResourceInterval = ResourceInterval;
     type MarketInterface = Market;
+    type MaxExpirationsPerBlock = MaxExpirationsPerBlock;
+    type ReputationDecayPeriod = ReputationDecayPeriod;
+    type MaxBatchResources = MaxBatchResources;
+    type ProbationPeriod = ProbationPeriod;
+    type ProbationHeartbeatInterval = ProbationHeartbeatInterval;
+    type MaxStatusHistory = MaxStatusHistory;
+    type AutoExtendWindow = AutoExtendWindow;
+    type ResourceOrderInterface = ();
+    type OperatorInterface = Market;
 }
 
 parameter_types! {
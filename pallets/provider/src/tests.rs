@@ -1,8 +1,20 @@
 use crate::mock::*;
-use frame_support::assert_ok;
+use crate::{
+    Error, FutureExpiredResource, OrderInterface, PeerId, ProviderInterface, Releases, Resources,
+    StorageVersion,
+};
+use frame_support::{assert_noop, assert_ok, traits::Currency};
 
-use primitives::p_market::{StakingAmount, TotalStakingAmount};
-use primitives::p_provider::ProviderPoints;
+use primitives::p_market::{
+    ChangeAmountType, MarketInterface, MarketUserStatus, OperatorScope, StakingAmount,
+    TotalStakingAmount,
+};
+use primitives::p_peer_id::MAX_PEER_ID_LEN;
+use primitives::p_provider::{
+    CpuArch, ProviderPoints, ReputationEvent, ResourceConfig, ResourceConfigV1, ResourceLimit,
+    ResourceLimits, ResourceLock, ResourceQuery, ResourceSort, ResourceSpec, ResourceStatus,
+    ResourceWeights, StatusChange, StatusChangeReason, StorageType,
+};
 
 #[test]
 fn register() {
@@ -39,16 +51,36 @@ fn register() {
     });
 }
 
+/// resource config whose storage and bandwidth are below one point
+fn resource_config(cpu: u64, memory: u64) -> ResourceConfig {
+    ResourceConfig::new(
+        cpu,
+        memory,
+        "linux".as_bytes().to_vec(),
+        "Intel 8700k".as_bytes().to_vec(),
+        40,
+        StorageType::Ssd,
+        10,
+        CpuArch::X86_64,
+        "CN-SH".as_bytes().to_vec(),
+        None,
+    )
+}
+
+/// raw sha2-256 multihash peer id
+fn peer_id(n: u8) -> Vec<u8> {
+    let mut multihash = vec![0x12, 0x20];
+    multihash.extend_from_slice(&[n; 32]);
+    multihash
+}
+
 fn register_resource_fn() {
     let price = 1 as u64;
 
     assert_ok!(Provider::register_resource(
         Origin::signed(1),
-        "peer_id1".as_bytes().to_vec(),
-        1,
-        1,
-        "linux".as_bytes().to_vec(),
-        "Intel 8700k".as_bytes().to_vec(),
+        peer_id(1),
+        resource_config(1, 1),
         price.into(),
         1,
         0,
@@ -60,11 +92,8 @@ fn register_some_fn() {
 
     assert_ok!(Provider::register_resource(
         Origin::signed(1),
-        "peer_id1".as_bytes().to_vec(),
-        1,
-        1,
-        "linux".as_bytes().to_vec(),
-        "Intel 8700k".as_bytes().to_vec(),
+        peer_id(1),
+        resource_config(1, 1),
         price.into(),
         1,
         0,
@@ -72,11 +101,8 @@ fn register_some_fn() {
 
     if let Err(e) = Provider::register_resource(
         Origin::signed(1),
-        "peer_id2".as_bytes().to_vec(),
-        1,
-        1,
-        "linux".as_bytes().to_vec(),
-        "Intel 8700k".as_bytes().to_vec(),
+        peer_id(2),
+        resource_config(1, 1),
         price.into(),
         1,
         0,
@@ -86,11 +112,8 @@ fn register_some_fn() {
 
     if let Err(e) = Provider::register_resource(
         Origin::signed(2),
-        "peer_id3".as_bytes().to_vec(),
-        1,
-        1,
-        "linux".as_bytes().to_vec(),
-        "Intel 8700k".as_bytes().to_vec(),
+        peer_id(3),
+        resource_config(1, 1),
         price.into(),
         1,
         0,
@@ -99,6 +122,790 @@ fn register_some_fn() {
     }
 }
 
+#[test]
+fn register_with_storage_and_bandwidth() {
+    StakingBuilder::default().build().execute_with(|| {
+        // 200 GB storage and 100 Mbps bandwidth are three more points
+        let mut config = resource_config(1, 1);
+        config.storage = 200;
+        config.bandwidth = 100;
+        assert_eq!(config.resource_points(&ResourceWeights::default()), 5);
+
+        // the region code is required
+        let mut no_region = config.clone();
+        no_region.region = vec![];
+        assert_noop!(
+            Provider::register_resource(
+                Origin::signed(1),
+                peer_id(1),
+                no_region,
+                1u64.into(),
+                1,
+                0,
+            ),
+            Error::<Test>::IllegalRequest
+        );
+
+        assert_ok!(Provider::register_resource(
+            Origin::signed(1),
+            peer_id(1),
+            config.clone(),
+            1u64.into(),
+            1,
+            0,
+        ));
+
+        // the config is stored with the resource
+        assert_eq!(Provider::resource(0).unwrap().config, config);
+
+        // the points and the staking follow the resource points
+        assert_eq!(
+            Provider::provider_points(1).unwrap(),
+            ProviderPoints::new(5, 5, 0)
+        );
+        assert_eq!(Provider::provider_total_resource_points(), 5);
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 500_000_000_000_000);
+
+        // an accelerator is counted too
+        config.accelerator = Some("NVIDIA A100".as_bytes().to_vec());
+        assert_eq!(config.resource_points(&ResourceWeights::default()), 13);
+    });
+}
+
+#[test]
+fn governance_limits_and_weights() {
+    StakingBuilder::default().build().execute_with(|| {
+        // 128 cpus are over the default limit
+        assert_noop!(
+            Provider::register_resource(
+                Origin::signed(1),
+                peer_id(1),
+                resource_config(128, 1),
+                1u64.into(),
+                1,
+                0,
+            ),
+            Error::<Test>::IllegalRequest
+        );
+
+        let mut limits = ResourceLimits::default();
+        limits.cpu = ResourceLimit::new(1, 256);
+        assert_noop!(
+            Provider::set_resource_limits(Origin::signed(1), limits.clone()),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        let mut invalid = limits.clone();
+        invalid.memory = ResourceLimit::new(0, 256);
+        assert_noop!(
+            Provider::set_resource_limits(Origin::root(), invalid),
+            Error::<Test>::InvalidResourceLimits
+        );
+        assert_ok!(Provider::set_resource_limits(
+            Origin::root(),
+            limits.clone()
+        ));
+        assert_eq!(Provider::resource_limits(), limits);
+
+        // the weights must count something
+        assert_noop!(
+            Provider::set_stake_weights(Origin::root(), ResourceWeights::new(0, 0, 0, 0, 0)),
+            Error::<Test>::InvalidResourceWeights
+        );
+
+        // register with the default weights
+        register_resource_fn();
+        assert_eq!(
+            Provider::resource_lock(0),
+            Some(ResourceLock::new(200_000_000_000_000, 2))
+        );
+
+        // a cpu counts double from now on
+        assert_ok!(Provider::set_stake_weights(
+            Origin::root(),
+            ResourceWeights::new(2000, 1000, 10, 10, 8000)
+        ));
+        assert_ok!(Provider::set_point_weights(
+            Origin::root(),
+            ResourceWeights::new(2000, 1000, 10, 10, 8000)
+        ));
+        assert_ok!(Provider::register_resource(
+            Origin::signed(1),
+            peer_id(2),
+            resource_config(2, 1),
+            1u64.into(),
+            1,
+            0,
+        ));
+        assert_eq!(
+            Provider::resource_lock(1),
+            Some(ResourceLock::new(500_000_000_000_000, 5))
+        );
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 700_000_000_000_000);
+        assert_eq!(Provider::provider_total_resource_points(), 7);
+
+        // the first resource unlocks what it locked
+        offline_resource_fn();
+        assert_eq!(Provider::resource_lock(0), None);
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 500_000_000_000_000);
+        assert_eq!(
+            Provider::provider_points(1).unwrap(),
+            ProviderPoints::new(5, 5, 0)
+        );
+    });
+}
+
+#[test]
+fn update_resource_config() {
+    StakingBuilder::default().build().execute_with(|| {
+        register_resource_fn();
+
+        // only the owner can update
+        assert_noop!(
+            Provider::update_resource_config(Origin::signed(2), 0, resource_config(2, 2)),
+            Error::<Test>::IllegalRequest
+        );
+        assert_noop!(
+            Provider::update_resource_config(Origin::signed(1), 0, resource_config(128, 2)),
+            Error::<Test>::IllegalRequest
+        );
+
+        // upgrade, the difference is locked
+        let mut resource = Provider::resource(0).unwrap();
+        resource.rental_statistics.add_rental_count();
+        assert_ok!(Provider::update_computing_resource(0, resource));
+        assert_ok!(Provider::update_resource_config(
+            Origin::signed(1),
+            0,
+            resource_config(2, 2)
+        ));
+        let resource = Provider::resource(0).unwrap();
+        assert_eq!(resource.config, resource_config(2, 2));
+        assert_eq!(resource.rental_statistics.rental_count, 1);
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 400_000_000_000_000);
+        assert_eq!(
+            Provider::provider_points(1).unwrap(),
+            ProviderPoints::new(4, 4, 0)
+        );
+        assert_eq!(Provider::provider_total_resource_points(), 4);
+
+        // downgrade, the difference is unlocked
+        assert_ok!(Provider::update_resource_config(
+            Origin::signed(1),
+            0,
+            resource_config(1, 2)
+        ));
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 300_000_000_000_000);
+        assert_eq!(Provider::provider_total_resource_points(), 3);
+
+        // a resource in use cannot be updated
+        let mut resource = Provider::resource(0).unwrap();
+        resource.update_status(ResourceStatus::Inuse);
+        assert_ok!(Provider::update_computing_resource(0, resource));
+        assert_noop!(
+            Provider::update_resource_config(Origin::signed(1), 0, resource_config(2, 2)),
+            Error::<Test>::UnmodifiableStatusNow
+        );
+    });
+}
+
+#[test]
+fn expiry_is_carried_over() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        // three resources expire at block 601, two are expired per block
+        register_some_fn();
+        assert_eq!(Provider::future_expired_resource(601), Some(vec![0, 1, 2]));
+
+        for block in 0..=601 {
+            System::set_block_number(block);
+            <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(block);
+        }
+        assert_eq!(Provider::resource_count(), 1);
+        assert_eq!(Provider::future_expired_resource(601), Some(vec![2]));
+        assert_eq!(Provider::expiry_cursor(), 601);
+
+        // the rest is expired in the next block and reported late
+        System::set_block_number(602);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(602);
+        assert_eq!(Provider::resource_count(), 0);
+        assert_eq!(Provider::future_expired_resource(601), None);
+        assert_eq!(Provider::expiry_cursor(), 603);
+        System::assert_last_event(Event::Provider(crate::Event::ResourceExpiredLate(2, 601)));
+        assert_eq!(Provider::provider_online_list().len(), 0);
+    });
+}
+
+#[test]
+fn expiry_is_scheduled_from_the_cursor() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(1);
+        assert_eq!(Provider::expiry_cursor(), 2);
+
+        // a resource of no hours ends in a block the sweep has passed
+        assert_ok!(Provider::register_resource(
+            Origin::signed(1),
+            peer_id(1),
+            resource_config(1, 1),
+            1,
+            0,
+            0,
+        ));
+        assert_eq!(Provider::future_expired_resource(1), None);
+        assert_eq!(Provider::future_expired_resource(2), Some(vec![0]));
+        assert_ok!(Provider::register_resource(
+            Origin::signed(1),
+            peer_id(2),
+            resource_config(1, 1),
+            1,
+            1,
+            0,
+        ));
+
+        System::set_block_number(2);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(2);
+        assert!(Provider::resource(0).is_none());
+        assert_eq!(Provider::resource_count(), 1);
+
+        // the extension moves the resource to its new end
+        assert_ok!(Provider::add_resource_duration(Origin::signed(1), 1, 1));
+        assert_eq!(Provider::future_expired_resource(601), None);
+        assert_eq!(Provider::future_expired_resource(1201), Some(vec![1]));
+        assert_noop!(
+            Provider::add_resource_duration(Origin::signed(1), 1, u32::MAX),
+            Error::<Test>::InvalidRentDuration
+        );
+
+        // a stale entry does not expire the extended resource
+        FutureExpiredResource::<Test>::insert(3, vec![1]);
+        System::set_block_number(3);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(3);
+        assert!(Provider::resource(1).is_some());
+        assert_eq!(Provider::future_expired_resource(3), None);
+        assert_eq!(Provider::resource_count(), 1);
+    });
+}
+
+#[test]
+fn reputation_decays_to_neutral() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+        assert_eq!(Provider::resource_reputation(0), 500);
+
+        <Provider as OrderInterface>::update_reputation(0, ReputationEvent::Faulted(0));
+        assert_eq!(Provider::resource_reputation(0), 400);
+        assert_eq!(Provider::provider_reputation(&1), 400);
+
+        // a tenth of the way back every period
+        System::set_block_number(1 + HOURS);
+        assert_eq!(Provider::provider_reputation(&1), 410);
+        <Provider as OrderInterface>::update_reputation(0, ReputationEvent::Completed);
+        assert_eq!(Provider::provider_reputation(&1), 430);
+
+        // the resource reputation is removed with the resource
+        offline_resource_fn();
+        assert_eq!(Provider::resource_reputations(0), None);
+        assert_eq!(Provider::provider_reputation(&1), 430);
+    });
+}
+
+#[test]
+fn duration_points_accrue_with_uptime() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+        let era_points = || {
+            <Provider as ProviderInterface<u64>>::accrue_duration_points();
+            <Provider as ProviderInterface<u64>>::get_providers_points()
+        };
+
+        // only whole hours are counted, the rest is rolled over
+        System::set_block_number(1 + 2 * HOURS + 10);
+        let (points, _, total_duration) = era_points();
+        assert_eq!(points[0].1.duration_points, 2);
+        assert_eq!(total_duration, 2);
+        assert_eq!(Provider::resource_online_since(0), Some(1 + 2 * HOURS));
+
+        // a new era starts from zero
+        <Provider as ProviderInterface<u64>>::reset_duration_points();
+        assert_eq!(Provider::provider_points(1).unwrap().duration_points, 0);
+        assert_eq!(Provider::provider_total_duration_points(), 0);
+
+        // reading the points does not accrue them
+        System::set_block_number(1 + 3 * HOURS);
+        let (_, _, total_duration) = <Provider as ProviderInterface<u64>>::get_providers_points();
+        assert_eq!(total_duration, 0);
+        let (points, _, total_duration) = era_points();
+        assert_eq!(points[0].1.duration_points, 1);
+        assert_eq!(total_duration, 1);
+
+        // a faulty resource stops accruing
+        let mut resource = Provider::resource(0).unwrap();
+        resource.status = ResourceStatus::Offline;
+        <Provider as OrderInterface>::update_computing_resource(0, resource);
+        assert_eq!(Provider::resource_online_since(0), None);
+
+        System::set_block_number(1 + 5 * HOURS);
+        let (_, _, total_duration) = era_points();
+        assert_eq!(total_duration, 1);
+    });
+}
+
+#[test]
+fn duration_points_removed_with_the_resource() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+
+        // the uptime is accrued when the resource goes offline
+        System::set_block_number(1 + HOURS);
+        assert_ok!(Provider::offline(Origin::signed(1), 0));
+        assert_eq!(Provider::resource_online_since(0), None);
+        assert_eq!(Provider::provider_points(1).unwrap().duration_points, 1);
+        assert_eq!(Provider::provider_total_duration_points(), 1);
+    });
+}
+
+#[test]
+fn register_resources_in_batch() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        let spec = |peer: u8, hours: u32, new_index: u64| {
+            ResourceSpec::new(
+                PeerId::parse(&peer_id(peer)).unwrap(),
+                resource_config(1, 1),
+                1,
+                hours,
+                new_index,
+            )
+        };
+
+        assert_noop!(
+            Provider::register_resources(Origin::signed(1), vec![]),
+            Error::<Test>::NoResources
+        );
+        // the peer ids are given in their stored form, not as raw multihashes
+        let mut raw = spec(1, 1, 0);
+        raw.peer_id = PeerId::new_unchecked(peer_id(1));
+        assert_noop!(
+            Provider::register_resources(Origin::signed(1), vec![raw]),
+            Error::<Test>::InvalidPeerId
+        );
+        assert_noop!(
+            Provider::register_resources(
+                Origin::signed(1),
+                vec![spec(1, 1, 0), spec(2, 1, 0), spec(3, 1, 0), spec(4, 1, 0),]
+            ),
+            Error::<Test>::TooManyResources
+        );
+
+        assert_ok!(Provider::register_resources(
+            Origin::signed(1),
+            vec![spec(1, 1, 0), spec(2, 2, 0), spec(3, 1, 0),]
+        ));
+
+        // consecutive indexes, expiries bucketed by block
+        assert_eq!(Provider::resource_index(), 3);
+        assert_eq!(Provider::resource_count(), 3);
+        assert_eq!(Provider::provider(1).unwrap(), vec![0, 1, 2]);
+        assert_eq!(Provider::future_expired_resource(601).unwrap(), vec![0, 2]);
+        assert_eq!(Provider::future_expired_resource(1201).unwrap(), vec![1]);
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 600_000_000_000_000);
+        assert_eq!(Provider::provider_total_resource_points(), 6);
+
+        // a peer registered again at its index fails the whole batch
+        assert_noop!(
+            Provider::register_resources(Origin::signed(1), vec![spec(4, 1, 0), spec(2, 1, 1)]),
+            Error::<Test>::ResourceAlreadyExist
+        );
+    });
+}
+
+#[test]
+fn transfer_resource() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+
+        assert_noop!(
+            Provider::transfer_resource(Origin::signed(2), 0, 3),
+            Error::<Test>::IllegalRequest
+        );
+        assert_noop!(
+            Provider::accept_resource_transfer(Origin::signed(2), 0),
+            Error::<Test>::TransferNotFound
+        );
+
+        assert_ok!(Provider::transfer_resource(Origin::signed(1), 0, 2));
+        // only the recipient can accept
+        assert_noop!(
+            Provider::accept_resource_transfer(Origin::signed(3), 0),
+            Error::<Test>::TransferNotFound
+        );
+        assert_ok!(Provider::accept_resource_transfer(Origin::signed(2), 0));
+
+        assert_eq!(Provider::resource(0).unwrap().account_id, 2);
+        assert_eq!(Provider::pending_transfers(0), None);
+        assert_eq!(Provider::provider(1), None);
+        assert_eq!(Provider::provider(2).unwrap(), vec![0]);
+        assert_eq!(Provider::provider_online_list(), vec![2]);
+
+        // the stake lock and the points move with the resource
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 0);
+        assert_eq!(Market::staking(2).unwrap().lock_amount, 200_000_000_000_000);
+        assert_eq!(Provider::provider_points(1).unwrap().resource_points, 0);
+        assert_eq!(Provider::provider_points(2).unwrap().resource_points, 2);
+        assert_eq!(Provider::provider_total_resource_points(), 2);
+    });
+}
+
+#[test]
+fn transfer_offline_resource() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+        let mut resource = Provider::resource(0).unwrap();
+        resource.status = ResourceStatus::Offline;
+        <Provider as OrderInterface>::update_computing_resource(0, resource);
+
+        assert_ok!(Provider::transfer_resource(Origin::signed(1), 0, 2));
+        assert_ok!(Provider::accept_resource_transfer(Origin::signed(2), 0));
+
+        // the offline resource earns no points for its new owner
+        assert_eq!(Provider::provider_points(1).unwrap().resource_points, 0);
+        assert_eq!(Provider::provider_points(2).unwrap().resource_points, 0);
+        assert_eq!(Provider::provider_total_resource_points(), 0);
+        assert_eq!(Market::staking(2).unwrap().lock_amount, 0);
+
+        // until it is recovered
+        assert_ok!(Provider::request_recovery(Origin::signed(2), 0));
+        assert_eq!(Market::staking(2).unwrap().lock_amount, 200_000_000_000_000);
+        assert_eq!(Provider::provider_points(2).unwrap().resource_points, 2);
+        assert_eq!(Provider::provider_total_resource_points(), 2);
+    });
+}
+
+#[test]
+fn recover_offline_resource() {
+    StakingBuilder::default().build().execute_with(|| {
+        // the five resources lock all the staking
+        for peer in 1..=5 {
+            assert_ok!(Provider::register_resource(
+                Origin::signed(1),
+                peer_id(peer),
+                resource_config(1, 1),
+                1,
+                1,
+                0,
+            ));
+        }
+
+        // the resource faults and its stake is slashed
+        <Market as MarketInterface<u64>>::change_stake_amount(
+            1,
+            ChangeAmountType::Penalty,
+            200_000_000_000_000,
+            MarketUserStatus::Provider,
+        );
+        let mut resource = Provider::resource(0).unwrap();
+        resource.status = ResourceStatus::Offline;
+        <Provider as OrderInterface>::update_computing_resource(0, resource);
+
+        assert_noop!(
+            Provider::change_resource_status(Origin::signed(1), 0),
+            Error::<Test>::CallDeprecated
+        );
+        assert_noop!(
+            Provider::probation_heartbeat(Origin::signed(1), 0),
+            Error::<Test>::UnmodifiableStatusNow
+        );
+        assert_noop!(
+            Provider::request_recovery(Origin::signed(1), 0),
+            Error::<Test>::InsufficientStake
+        );
+
+        // top up the staking
+        Balances::make_free_balance_be(&1, 300_000_000_000_000);
+        assert_ok!(Market::bond(Origin::signed(1), 200_000_000_000_000));
+        assert_ok!(Provider::request_recovery(Origin::signed(1), 0));
+        assert_eq!(
+            Provider::resource(0).unwrap().status,
+            ResourceStatus::Probation
+        );
+
+        // a late heartbeat restarts the probation
+        System::set_block_number(1 + 5 * MINUTES);
+        assert_ok!(Provider::probation_heartbeat(Origin::signed(1), 0));
+        System::set_block_number(1 + 16 * MINUTES);
+        assert_ok!(Provider::probation_heartbeat(Origin::signed(1), 0));
+        assert_eq!(
+            Provider::resource_probations(0).unwrap().start,
+            1 + 16 * MINUTES
+        );
+
+        // the operator of the resource heartbeats for the owner
+        assert_noop!(
+            Provider::probation_heartbeat(Origin::signed(3), 0),
+            Error::<Test>::IllegalRequest
+        );
+        assert_ok!(Market::authorize_operator(
+            Origin::signed(1),
+            3,
+            OperatorScope::Resource(0)
+        ));
+        let mut block = 1 + 16 * MINUTES;
+        while block < 1 + 16 * MINUTES + HOURS {
+            block += 10 * MINUTES;
+            System::set_block_number(block);
+            assert_ok!(Provider::probation_heartbeat(Origin::signed(3), 0));
+        }
+        assert_eq!(
+            Provider::resource(0).unwrap().status,
+            ResourceStatus::Unused
+        );
+        assert_eq!(Provider::resource_probations(0), None);
+
+        // the latest changes are kept
+        assert_eq!(
+            Provider::resource_status_history(0),
+            vec![
+                StatusChange::new(ResourceStatus::Offline, StatusChangeReason::Faulted, 1),
+                StatusChange::new(
+                    ResourceStatus::Probation,
+                    StatusChangeReason::RecoveryRequested,
+                    1
+                ),
+                StatusChange::new(
+                    ResourceStatus::Unused,
+                    StatusChangeReason::ProbationPassed,
+                    1 + 16 * MINUTES + HOURS
+                ),
+            ]
+        );
+    });
+}
+
+#[test]
+fn auto_extend_resource() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        register_resource_fn();
+
+        assert_noop!(
+            Provider::set_auto_extend(Origin::signed(2), 0, 2),
+            Error::<Test>::IllegalRequest
+        );
+        assert_ok!(Provider::set_auto_extend(Origin::signed(1), 0, 2));
+
+        // extended by two hours ten minutes before it expires
+        for block in 1..=500 {
+            System::set_block_number(block);
+            <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(block);
+        }
+        assert_eq!(Provider::resource(0).unwrap().rental_info.end_of_rent, 601);
+        System::set_block_number(501);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(501);
+        System::assert_last_event(Event::Provider(crate::Event::ResourceAutoExtended(0, 1801)));
+        assert_eq!(Provider::resource(0).unwrap().rental_info.end_of_rent, 1801);
+        assert_eq!(Provider::future_expired_resource(601), None);
+        assert_eq!(Provider::future_expired_resource(1801), Some(vec![0]));
+        assert_eq!(Provider::resource_count(), 1);
+
+        // the locked stake was slashed, the provider does not have the stake the resource needs
+        let staked = Provider::resource_lock(0).unwrap().staked;
+        assert_eq!(Market::staking(1).unwrap().lock_amount, staked);
+        assert!(<Market as MarketInterface<u64>>::change_stake_amount(
+            1,
+            ChangeAmountType::Penalty,
+            staked,
+            MarketUserStatus::Provider
+        ));
+        for block in 502..=1801 {
+            System::set_block_number(block);
+            <Provider as frame_support::traits::Hooks<BlockNumber>>::on_initialize(block);
+        }
+        System::assert_has_event(Event::Provider(crate::Event::AutoExtendSkipped(0, staked)));
+        assert_eq!(Provider::resource(0), None);
+        assert_eq!(Provider::auto_extend_hours(0), None);
+        assert_eq!(Market::staking(1).unwrap().lock_amount, 0);
+    });
+}
+
+#[test]
+fn validate_peer_id() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_noop!(
+            Provider::register_resource(
+                Origin::signed(1),
+                "peer_id1".as_bytes().to_vec(),
+                resource_config(1, 1),
+                1,
+                1,
+                0,
+            ),
+            Error::<Test>::InvalidPeerId
+        );
+        assert_noop!(
+            Provider::register_resources(
+                Origin::signed(1),
+                vec![ResourceSpec::new(
+                    PeerId::new_unchecked(peer_id(1)[..33].to_vec()),
+                    resource_config(1, 1),
+                    1,
+                    1,
+                    0
+                )]
+            ),
+            Error::<Test>::InvalidPeerId
+        );
+
+        // the raw multihash and the base58 peer id are the same peer
+        register_resource_fn();
+        let base58 = "QmNQa1FSTXNHmrjjfgUW3Px3Vkke4oKiFWdigWkYSux2Pi".as_bytes();
+        assert_eq!(Provider::resource(0).unwrap().peer_id.as_bytes(), base58);
+        assert_noop!(
+            Provider::register_resource(
+                Origin::signed(1),
+                base58.to_vec(),
+                resource_config(1, 1),
+                1,
+                1,
+                0,
+            ),
+            Error::<Test>::ResourceAlreadyExist
+        );
+
+        // the migration flags the resources registered with an invalid peer id
+        Resources::<Test>::mutate(0, |resource| {
+            resource.as_mut().unwrap().peer_id = PeerId::new_unchecked(b"peer_id1".to_vec())
+        });
+        StorageVersion::<Test>::put(Releases::V5_0_0);
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_runtime_upgrade();
+        assert_eq!(Provider::storage_version(), Releases::V6_0_0);
+        assert!(Provider::invalid_peer_ids(0));
+
+        // the flagged resource can be taken offline
+        assert_ok!(Provider::offline(Origin::signed(1), 0));
+        assert!(!Provider::invalid_peer_ids(0));
+    });
+}
+
+#[test]
+fn migration_keeps_long_peer_ids() {
+    StakingBuilder::default().build().execute_with(|| {
+        register_resource_fn();
+
+        // a resource stored with the first layout, the peer id was bytes of any length
+        let resource = Provider::resource(0).unwrap();
+        let long = vec![b'Q'; MAX_PEER_ID_LEN + 16];
+        let config = ResourceConfigV1 {
+            cpu: 1,
+            memory: 1,
+            system: "ubuntu".as_bytes().to_vec(),
+            cpu_model: "Intel 8700k".as_bytes().to_vec(),
+        };
+        frame_support::storage::unhashed::put(
+            &Resources::<Test>::hashed_key_for(0),
+            &(
+                resource.index,
+                resource.account_id,
+                long.clone(),
+                config,
+                resource.rental_statistics,
+                resource.rental_info,
+                resource.status,
+            ),
+        );
+        StorageVersion::<Test>::put(Releases::V1_0_0);
+
+        <Provider as frame_support::traits::Hooks<BlockNumber>>::on_runtime_upgrade();
+
+        // the resource is kept with its peer id and flagged
+        assert_eq!(Provider::storage_version(), Releases::V7_0_0);
+        assert_eq!(Provider::resource(0).unwrap().peer_id.as_bytes(), &long[..]);
+        assert!(Provider::invalid_peer_ids(0));
+
+        assert_ok!(Provider::offline(Origin::signed(1), 0));
+        assert!(!Provider::invalid_peer_ids(0));
+    });
+}
+
+#[test]
+fn query_resources() {
+    StakingBuilder::default().build().execute_with(|| {
+        System::set_block_number(1);
+        for &(who, peer, cpu, price, hours) in
+            [(1, 1, 1, 3u64, 1), (1, 2, 2, 1u64, 2), (2, 3, 1, 2u64, 1)].iter()
+        {
+            assert_ok!(Provider::register_resource(
+                Origin::signed(who),
+                peer_id(peer),
+                resource_config(cpu, cpu),
+                price.into(),
+                hours,
+                0,
+            ));
+        }
+        let indexes = |query: ResourceQuery| -> Vec<u64> {
+            Provider::query_resources(query)
+                .iter()
+                .map(|x| x.index)
+                .collect()
+        };
+
+        assert_eq!(indexes(ResourceQuery::default()), vec![0, 1, 2]);
+        assert_eq!(
+            indexes(ResourceQuery {
+                sort: ResourceSort::PriceAsc,
+                ..Default::default()
+            }),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            indexes(ResourceQuery {
+                min_cpu: Some(2),
+                ..Default::default()
+            }),
+            vec![1]
+        );
+        assert_eq!(
+            indexes(ResourceQuery {
+                max_price: Some(2),
+                sort: ResourceSort::PriceDesc,
+                ..Default::default()
+            }),
+            vec![2, 1]
+        );
+        assert_eq!(
+            indexes(ResourceQuery {
+                min_remaining_hours: Some(2),
+                ..Default::default()
+            }),
+            vec![1]
+        );
+        assert_eq!(
+            indexes(ResourceQuery {
+                status: Some(ResourceStatus::Inuse),
+                ..Default::default()
+            }),
+            Vec::<u64>::new()
+        );
+        // second page of one
+        assert_eq!(
+            indexes(ResourceQuery {
+                sort: ResourceSort::PriceAsc,
+                offset: 1,
+                limit: 1,
+                ..Default::default()
+            }),
+            vec![2]
+        );
+    });
+}
+
 fn offline_resource_fn() {
     if let Err(e) = Provider::offline(Origin::signed(1), 0) {
         println!("{:?}", e);
@@ -164,11 +971,8 @@ fn test_register() {
 
         if let Err(e) = Provider::register_resource(
             Origin::signed(1),
-            "peer_id1".as_bytes().to_vec(),
-            1,
-            1,
-            "linux".as_bytes().to_vec(),
-            "Intel 8700k".as_bytes().to_vec(),
+            peer_id(1),
+            resource_config(1, 1),
             price.into(),
             1,
             0,
@@ -178,11 +982,8 @@ fn test_register() {
 
         if let Err(e) = Provider::register_resource(
             Origin::signed(1),
-            "peer_id2".as_bytes().to_vec(),
-            1,
-            1,
-            "linux".as_bytes().to_vec(),
-            "Intel 8700k".as_bytes().to_vec(),
+            peer_id(2),
+            resource_config(1, 1),
             price.into(),
             1,
             0,
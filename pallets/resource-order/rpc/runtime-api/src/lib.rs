@@ -0,0 +1,15 @@
+//! Runtime API definition for the resource order pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    pub trait ResourceOrderApi<AccountId> where
+        AccountId: Codec,
+    {
+        /// agreements of the user with the cluster each one belongs to
+        fn user_cluster_agreements(who: AccountId) -> Vec<(u64, Option<u64>)>;
+    }
+}
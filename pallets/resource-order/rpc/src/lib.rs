@@ -0,0 +1,79 @@
+//! RPC interface for the resource order pallet.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+pub use pallet_resource_order_rpc_runtime_api::ResourceOrderApi as ResourceOrderRuntimeApi;
+
+#[rpc]
+pub trait ResourceOrderApi<BlockHash, AccountId> {
+    /// agreements of the user, paired with the cluster order of the ones rented as a cluster
+    #[rpc(name = "hamster_userClusterAgreements")]
+    fn user_cluster_agreements(
+        &self,
+        who: AccountId,
+        at: Option<BlockHash>,
+    ) -> Result<Vec<(u64, Option<u64>)>>;
+}
+
+/// A struct that implements the `ResourceOrderApi`.
+pub struct ResourceOrder<C, B> {
+    client: Arc<C>,
+    _marker: std::marker::PhantomData<B>,
+}
+
+impl<C, B> ResourceOrder<C, B> {
+    /// Create new `ResourceOrder` with the given reference to the client.
+    pub fn new(client: Arc<C>) -> Self {
+        ResourceOrder {
+            client,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+    /// The call to runtime failed.
+    RuntimeError,
+}
+
+impl From<Error> for i64 {
+    fn from(e: Error) -> i64 {
+        match e {
+            Error::RuntimeError => 1,
+        }
+    }
+}
+
+impl<C, Block, AccountId> ResourceOrderApi<<Block as BlockT>::Hash, AccountId>
+    for ResourceOrder<C, Block>
+where
+    Block: BlockT,
+    C: 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: ResourceOrderRuntimeApi<Block, AccountId>,
+    AccountId: Codec,
+{
+    fn user_cluster_agreements(
+        &self,
+        who: AccountId,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Vec<(u64, Option<u64>)>> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(||
+            // If the block hash is not supplied assume the best block.
+            self.client.info().best_hash));
+
+        api.user_cluster_agreements(&at, who).map_err(|e| RpcError {
+            code: ErrorCode::ServerError(Error::RuntimeError.into()),
+            message: "Unable to query cluster agreements.".into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+}
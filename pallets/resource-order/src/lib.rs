@@ -5,10 +5,16 @@ extern crate alloc;
 use frame_support::sp_runtime::traits::Convert;
 use frame_support::traits::UnixTime;
 use frame_support::transactional;
-use frame_support::{dispatch::DispatchResult, pallet_prelude::*, traits::Currency};
+use frame_support::{
+    dispatch::DispatchResult,
+    pallet_prelude::*,
+    traits::{Currency, ExistenceRequirement},
+    PalletId,
+};
 use frame_system::pallet_prelude::*;
 use sp_core::Bytes;
-use sp_runtime::traits::Zero;
+use sp_runtime::traits::{AccountIdConversion, One, Saturating, Zero};
+use sp_runtime::Perbill;
 use sp_std::convert::TryInto;
 use sp_std::vec::Vec;
 
@@ -19,6 +25,7 @@ pub use pallet::*;
 pub use pallet_market::MarketInterface;
 pub use primitives::p_market::MarketUserStatus;
 pub use primitives::p_market::*;
+pub use primitives::p_peer_id::{LivenessPayload, LivenessProof, PeerId};
 pub use primitives::p_provider::*;
 pub use primitives::p_resource_order::*;
 
@@ -34,6 +41,7 @@ mod benchmarking;
 type BalanceOf<T> =
     <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
 
+const PALLET_ID: PalletId = PalletId(*b"ttchain!");
 
 #[frame_support::pallet]
 pub mod pallet {
@@ -57,6 +65,9 @@ pub mod pallet {
         /// Market interface
         type MarketInterface: MarketInterface<Self::AccountId>;
 
+        /// operator interface, resolves the accounts allowed to act for a provider
+        type OperatorInterface: OperatorInterface<Self::AccountId>;
+
         /// block height to number
         type BlockNumberToNumber: Convert<Self::BlockNumber, u128> + Convert<u32, Self::BlockNumber>;
 
@@ -69,8 +80,55 @@ pub mod pallet {
         #[pallet::constant]
         type HealthCheckInterval: Get<Self::BlockNumber>;
 
+        /// how long a pending order waits for the provider before it is canceled
+        #[pallet::constant]
+        type OrderWaitingTime: Get<Self::BlockNumber>;
+
+        /// maximum number of agreements checked for missed heartbeats in one block
+        #[pallet::constant]
+        type MaxHealthChecksPerBlock: Get<u32>;
+
+        /// share of the remaining rent paid to the provider when the tenant terminates early
+        #[pallet::constant]
+        type EarlyTerminationFee: Get<Perbill>;
+
         /// time
         type UnixTime: UnixTime;
+
+        /// the origin that processes free resource applications
+        type FreeResourceOrigin: EnsureOrigin<Self::Origin>;
+
+        /// maximum duration of a free resource trial (hours)
+        #[pallet::constant]
+        type MaxFreeResourceDuration: Get<u32>;
+
+        /// how long an order created by requirement waits for a matching resource
+        #[pallet::constant]
+        type OrderMatchWaitingTime: Get<Self::BlockNumber>;
+
+        /// maximum number of orders waiting for a matching resource
+        #[pallet::constant]
+        type MaxWaitingOrders: Get<u32>;
+
+        /// maximum number of waiting orders matched in one block, the others wait for the next
+        #[pallet::constant]
+        type MaxWaitingOrderMatches: Get<u32>;
+
+        /// longest time providers may bid on a compute request
+        #[pallet::constant]
+        type MaxBiddingPeriod: Get<Self::BlockNumber>;
+
+        /// maximum number of bids on a compute request
+        #[pallet::constant]
+        type MaxBidsPerRequest: Get<u32>;
+
+        /// maximum number of compute requests settled in one block
+        #[pallet::constant]
+        type MaxComputeRequestsPerBlock: Get<u32>;
+
+        /// maximum number of resources in a cluster order
+        #[pallet::constant]
+        type MaxClusterSize: Get<u32>;
     }
 
     #[pallet::pallet]
@@ -133,6 +191,34 @@ pub mod pallet {
     pub(super) type BlockWithAgreement<T: Config> =
         StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, ValueQuery>;
 
+    /// The agreements whose heartbeat deadline is the block [block number, agreement number]
+    #[pallet::storage]
+    #[pallet::getter(fn heartbeat_deadlines)]
+    pub(super) type HeartbeatDeadlines<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, ValueQuery>;
+
+    /// The overdue agreements that have not been checked yet because of the per block limit
+    #[pallet::storage]
+    #[pallet::getter(fn pending_health_checks)]
+    pub(super) type PendingHealthChecks<T: Config> = StorageValue<_, Vec<u64>, ValueQuery>;
+
+    /// The block of the last liveness proof of the agreement [agreement number, block number]
+    #[pallet::storage]
+    #[pallet::getter(fn liveness_proofs)]
+    pub(super) type LivenessProofs<T: Config> =
+        StorageMap<_, Twox64Concat, u64, T::BlockNumber, OptionQuery>;
+
+    /// Storage version of the pallet.
+    #[pallet::storage]
+    #[pallet::getter(fn storage_version)]
+    pub(crate) type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
+    /// The pending orders timing out in the block [block number, order number]
+    #[pallet::storage]
+    #[pallet::getter(fn block_order)]
+    pub(super) type BlockWithOrder<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, ValueQuery>;
+
     /// the order number corresponding to the user
     #[pallet::storage]
     #[pallet::getter(fn user_orders)]
@@ -145,11 +231,103 @@ pub mod pallet {
     pub(super) type ApplyOrders<T: Config> =
         StorageMap<_, Twox64Concat, u64, ApplyOrder<T::AccountId, T::BlockNumber>, OptionQuery>;
 
+    /// the free resource apply order of the user
     #[pallet::storage]
     #[pallet::getter(fn apply_users)]
     pub(super) type ApplyUsers<T: Config> =
         StorageMap<_, Twox64Concat, T::AccountId, u64, ValueQuery>;
 
+    /// free resource apply order index
+    #[pallet::storage]
+    #[pallet::getter(fn apply_order_index)]
+    pub(super) type ApplyOrderIndex<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+    /// The free resource trials expiring in the block [block number, apply order number]
+    #[pallet::storage]
+    #[pallet::getter(fn block_apply_order)]
+    pub(super) type BlockWithApplyOrder<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, ValueQuery>;
+
+    /// orders waiting for a resource meeting their requirement
+    #[pallet::storage]
+    #[pallet::getter(fn waiting_orders)]
+    pub(super) type WaitingOrders<T: Config> =
+        StorageMap<_, Twox64Concat, u64, WaitingOrder<T::AccountId, T::BlockNumber>, OptionQuery>;
+
+    /// indexes of the waiting orders, matched first come first served
+    #[pallet::storage]
+    #[pallet::getter(fn waiting_order_queue)]
+    pub(super) type WaitingOrderQueue<T: Config> = StorageValue<_, Vec<u64>, ValueQuery>;
+
+    /// compute request index
+    #[pallet::storage]
+    #[pallet::getter(fn compute_request_index)]
+    pub(super) type ComputeRequestIndex<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+    /// compute requests the providers bid on
+    #[pallet::storage]
+    #[pallet::getter(fn compute_requests)]
+    pub(super) type ComputeRequests<T: Config> =
+        StorageMap<_, Twox64Concat, u64, ComputeRequest<T::AccountId, T::BlockNumber>, OptionQuery>;
+
+    /// bids of the compute requests, kept after the settlement as price history
+    #[pallet::storage]
+    #[pallet::getter(fn request_bids)]
+    pub(super) type RequestBids<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Vec<Bid<T::AccountId, T::BlockNumber>>, ValueQuery>;
+
+    /// The compute requests settled in the block [block number, request number]
+    #[pallet::storage]
+    #[pallet::getter(fn block_compute_request)]
+    pub(super) type BlockWithComputeRequest<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u64>, ValueQuery>;
+
+    /// cluster order index
+    #[pallet::storage]
+    #[pallet::getter(fn cluster_index)]
+    pub(super) type ClusterIndex<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+    /// orders of several resources rented together
+    #[pallet::storage]
+    #[pallet::getter(fn cluster_orders)]
+    pub(super) type ClusterOrders<T: Config> =
+        StorageMap<_, Twox64Concat, u64, ClusterOrder<T::AccountId, T::BlockNumber>, OptionQuery>;
+
+    /// cluster of the orders created by a cluster order [order number, cluster number]
+    #[pallet::storage]
+    #[pallet::getter(fn order_cluster)]
+    pub(super) type OrderCluster<T: Config> = StorageMap<_, Twox64Concat, u64, u64, OptionQuery>;
+
+    /// cluster of the agreements [agreement number, cluster number]
+    #[pallet::storage]
+    #[pallet::getter(fn agreement_cluster)]
+    pub(super) type AgreementCluster<T: Config> =
+        StorageMap<_, Twox64Concat, u64, u64, OptionQuery>;
+
+    /// cluster orders of the tenant
+    #[pallet::storage]
+    #[pallet::getter(fn user_clusters)]
+    pub(super) type UserClusters<T: Config> =
+        StorageMap<_, Twox64Concat, T::AccountId, Vec<u64>, ValueQuery>;
+
+    /// deployment manifests of the pending orders
+    #[pallet::storage]
+    #[pallet::getter(fn order_manifests)]
+    pub(super) type OrderManifests<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Manifest, OptionQuery>;
+
+    /// deployment manifests of the agreements
+    #[pallet::storage]
+    #[pallet::getter(fn agreement_manifests)]
+    pub(super) type AgreementManifests<T: Config> =
+        StorageMap<_, Twox64Concat, u64, Manifest, OptionQuery>;
+
+    /// latest access info of the agreements, sealed to the tenant
+    #[pallet::storage]
+    #[pallet::getter(fn access_infos)]
+    pub(super) type AccessInfos<T: Config> =
+        StorageMap<_, Twox64Concat, u64, AccessInfo<T::BlockNumber>, OptionQuery>;
+
     // The genesis config type.
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
@@ -191,6 +369,9 @@ pub mod pallet {
             <AgreementIndex<T>>::put(&self.agreement_index);
             for (a, b) in &self.rental_agreements {
                 <RentalAgreements<T>>::insert(a, b);
+                if b.status == AgreementStatus::Using {
+                    Pallet::<T>::do_insert_heartbeat_deadline(*a, b.calculation);
+                }
             }
             for (a, b) in &self.user_agreements {
                 <UserAgreements<T>>::insert(a, b);
@@ -204,6 +385,8 @@ pub mod pallet {
             for (a, b) in &self.block_agreement {
                 <BlockWithAgreement<T>>::insert(a, b);
             }
+            Pallet::<T>::endow_order_pool();
+            <StorageVersion<T>>::put(Releases::V3_0_0);
         }
     }
 
@@ -214,16 +397,18 @@ pub mod pallet {
     #[pallet::generate_deposit(pub (super) fn deposit_event)]
     pub enum Event<T: Config> {
         /// created order successfully
-        /// [account, order number, rental resource number, rental duration (h), user public key]
-        CreateOrderSuccess(T::AccountId, u64, u64, u32, Bytes),
+        /// [account, order number, rental resource number, rental duration (h), user public key,
+        /// deployment manifest]
+        CreateOrderSuccess(T::AccountId, u64, u64, u32, Bytes, Option<Manifest>),
 
         /// order renewal successful
         /// [account, order number, rental resource number, rental duration (h)]
         ReNewOrderSuccess(T::AccountId, u64, u64, u32),
 
         /// order executed successfully
-        /// [account, order number, rental resource number, rental agreement number]
-        OrderExecSuccess(T::AccountId, u64, u64, u64),
+        /// [account, order number, rental resource number, rental agreement number,
+        /// deployment manifest]
+        OrderExecSuccess(T::AccountId, u64, u64, u64, Option<Manifest>),
 
         /// health check reported successfully
         /// [account, agreement number, block number of the reported agreement]
@@ -236,16 +421,16 @@ pub mod pallet {
         WithdrawStakingSuccess(T::AccountId, BalanceOf<T>),
 
         /// successfully retrieve the rental reward amount
-        /// account agreement number amount
-        WithdrawRentalAmountSuccess(T::AccountId, u64),
+        /// [provider, agreement number, amount]
+        WithdrawRentalAmountSuccess(T::AccountId, u64, u128),
 
         /// retrieve the penalty amount successfully
-        /// account agreement number amount
-        WithdrawFaultExcutionSuccess(T::AccountId, u64),
+        /// [tenant, agreement number, amount]
+        WithdrawFaultExcutionSuccess(T::AccountId, u64, u128),
 
         /// The amount of the unstarted order was successfully recovered
-        /// account order number amount
-        WithdrawLockedOrderPriceSuccess(T::AccountId, u64),
+        /// [tenant, order number, amount]
+        WithdrawLockedOrderPriceSuccess(T::AccountId, u64, u128),
 
         /// agreement deleted successfully
         /// agreement number
@@ -264,27 +449,115 @@ pub mod pallet {
 
         /// free resource processed
         /// [order_index, peer_id]
-        FreeResourceProcessed(u64, Vec<u8>),
+        FreeResourceProcessed(u64, PeerId),
+
+        /// free resource trial expired
+        /// [order_index, peer_id]
+        FreeResourceExpired(u64, PeerId),
+
+        /// pending order was not executed in time and has been canceled
+        /// [tenant, order number, refunded amount]
+        OrderTimeout(T::AccountId, u64, u128),
+
+        /// agreement terminated by the tenant before the end
+        /// [tenant, agreement number, amount paid to the provider, refunded amount]
+        AgreementTerminated(T::AccountId, u64, u128, u128),
+
+        /// the machine of the agreement signed the heartbeat with its peer key
+        /// [agreement number, block of the proof]
+        LivenessProven(u64, T::BlockNumber),
+
+        /// no resource meets the requirement yet, the order waits for one
+        /// [tenant, order number, block after which it is canceled]
+        OrderWaiting(T::AccountId, u64, T::BlockNumber),
+
+        /// no resource met the requirement in time, the escrow has been refunded
+        /// [tenant, order number, refunded amount]
+        WaitingOrderExpired(T::AccountId, u64, u128),
+
+        /// compute request open to the bids of the providers
+        /// [tenant, request number, last block of the bidding]
+        ComputeRequestCreated(T::AccountId, u64, T::BlockNumber),
+
+        /// bid placed on a compute request
+        /// [bidder, request number, resource index, unit price]
+        BidPlaced(T::AccountId, u64, u64, u128),
+
+        /// the lowest valid bid won the compute request
+        /// [request number, order number, resource index, unit price]
+        ComputeRequestSettled(u64, u64, u64, u128),
+
+        /// no valid bid on the compute request, the escrow has been refunded
+        /// [tenant, request number, refunded amount]
+        ComputeRequestUnfilled(T::AccountId, u64, u128),
+
+        /// the resources of the cluster have been locked together
+        /// [tenant, cluster number, order numbers]
+        ClusterOrderCreated(T::AccountId, u64, Vec<u64>),
+
+        /// an order of the cluster has been executed
+        /// [cluster number, agreement number]
+        ClusterAgreementCreated(u64, u64),
+
+        /// the agreements of the cluster have been renewed together
+        /// [tenant, cluster number, renewal order numbers]
+        ClusterRenewed(T::AccountId, u64, Vec<u64>),
+
+        /// the agreements and the pending orders of the cluster have been terminated together
+        /// [tenant, cluster number]
+        ClusterTerminated(T::AccountId, u64),
+
+        /// the tenant changed the deployment manifest of the agreement, the provider redeploys
+        /// [tenant, agreement number, deployment manifest]
+        ManifestUpdated(T::AccountId, u64, Manifest),
+
+        /// the provider published a new version of the access info of the agreement
+        /// [tenant, agreement number, version]
+        AccessInfoPublished(T::AccountId, u64, u32),
+
+        /// the unreleased rent of a punished agreement has been returned to the tenant
+        /// [tenant, agreement number, amount]
+        RentRefunded(T::AccountId, u64, u128),
+
+        /// an order of the cluster timed out, its agreements and orders have been canceled
+        /// [tenant, cluster number]
+        ClusterFailed(T::AccountId, u64),
     }
 
     #[pallet::hooks]
     impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
         fn on_initialize(now: T::BlockNumber) -> Weight {
-            // check for expired agreements
-            Self::agreement_check(now);
+            // delete the expired agreements of the block in on_finalize
+            let mut weight = T::DbWeight::get().writes(1);
 
+            // check for expired agreements
+            weight = weight.saturating_add(Self::agreement_check(now));
+            // check for expired free resource trials
+            weight = weight.saturating_add(Self::apply_order_check(now));
+            // check for pending orders that have not been executed in time
+            weight = weight.saturating_add(Self::order_check(now));
+            // match the waiting orders to the resources freed since
+            weight = weight.saturating_add(Self::waiting_order_check(now));
+            // settle the compute requests whose bidding has ended
+            weight = weight.saturating_add(Self::compute_request_check(now));
             // health examination
-            if (now % T::HealthCheckInterval::get()).is_zero() {
-                Self::do_health_check(now).ok();
-            }
+            weight = weight.saturating_add(Self::do_health_check(now));
 
-            0
+            weight
         }
 
         fn on_finalize(now: BlockNumberFor<T>) {
             // delete
             BlockWithAgreement::<T>::remove(now);
         }
+
+        fn on_runtime_upgrade() -> Weight {
+            match StorageVersion::<T>::get() {
+                Releases::V1_0_0 => migrations::v2::migrate::<T>(),
+                Releases::V2_0_0 => migrations::v3::migrate::<T>(),
+                _ => T::DbWeight::get().reads(1),
+            }
+        }
     }
 
     // Errors inform users that something went wrong.
@@ -294,6 +567,8 @@ pub mod pallet {
         InsufficientCurrency,
         /// the resource has been leased
         ResourceHasBeenRented,
+        /// the resource is draining and takes no new orders
+        ResourceDraining,
         /// resource does not exist
         ResourceNotExist,
         /// exceeded rental period
@@ -336,6 +611,42 @@ pub mod pallet {
         PenaltyAmountFailed,
 
         UnlockAmountFailed,
+        /// the peer id is not a libp2p peer id
+        InvalidPeerId,
+        /// the peer id does not inline an ed25519 key to verify the proof with
+        PeerKeyUnavailable,
+        /// the proof is not signed by the peer key
+        InvalidLivenessProof,
+        /// the block of the proof is not recent or not after the last proof
+        StaleLivenessProof,
+        /// too many orders are waiting for a matching resource
+        TooManyWaitingOrders,
+        /// the bidding period is zero or longer than MaxBiddingPeriod
+        InvalidBiddingPeriod,
+        /// compute request does not exist
+        ComputeRequestNotExist,
+        /// the deadline of the compute request has passed
+        BiddingClosed,
+        /// the resource does not meet the requirement of the compute request
+        ResourceNotMatched,
+        /// the bid is above the highest unit price of the compute request
+        BidAboveMaxPrice,
+        /// the compute request has MaxBidsPerRequest bids
+        TooManyBids,
+        /// the cluster has no resource or more than MaxClusterSize
+        InvalidClusterSize,
+        /// no unused resource meets the spec of a cluster member
+        NoMatchingResource,
+        /// cluster order does not exist
+        ClusterDoesNotExist,
+        /// the owner of the cluster is not me
+        ClusterNotOwnedByYou,
+        /// the manifest is empty or out of bounds
+        InvalidManifest,
+        /// the access info is empty or longer than MAX_ACCESS_INFO_LEN
+        InvalidAccessInfo,
+        /// the lease duration is zero or its blocks overflow
+        InvalidRentDuration,
     }
 
     // Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -353,265 +664,33 @@ pub mod pallet {
             resource_index: u64,
             rent_duration: u32,
             public_key: Bytes,
+            manifest: Option<Manifest>,
         ) -> DispatchResult {
             let who = ensure_signed(origin)?;
 
-            // check user has staking
-            ensure!(
-                T::MarketInterface::staking_exit(who.clone()),
-                Error::<T>::StakingNotExist
-            );
-
-            // get resource information
-            let mut resource_info =
-                match T::OrderInterface::get_computing_resource_info(resource_index) {
-                    Some(x) => x,
-                    None => Err(Error::<T>::ResourceNotExist)?,
-                };
-            // determine if the resource is leased
-            ensure!(
-                resource_info.status == ResourceStatus::Unused,
-                Error::<T>::ResourceHasBeenRented
-            );
-
-            // get the current block height
-            let block_number = <frame_system::Pallet<T>>::block_number();
-            // calculate persistent blocks
-            let rent_blocks = TryInto::<T::BlockNumber>::try_into(rent_duration * 600)
-                .ok()
-                .unwrap();
-            // determine whether the rental period is exceeded
-            ensure!(
-                block_number + rent_blocks < resource_info.rental_info.end_of_rent,
-                Error::<T>::ExceedTheRentableTime
-            );
-
-            // get order length
-            let order_index = OrderIndex::<T>::get();
-            // create a tenant
-            let customer = TenantInfo::new(who.clone(), public_key.clone());
-            // get the current time
-            let now = T::UnixTime::now();
-            // create order
-            let order = ResourceOrder::new(
-                order_index,
-                customer,
-                resource_index,
-                block_number,
-                rent_blocks,
-                now,
-            );
-
-            // resource status changed from unused to locked
-            resource_info.update_status(ResourceStatus::Locked);
-
-            // save resource state
-            T::OrderInterface::update_computing_resource(resource_index, resource_info);
-            // add order to order collection
-            ResourceOrders::<T>::insert(order_index, order.clone());
-            // order length+1
-            OrderIndex::<T>::put(order_index + 1);
-            // save the order corresponding to the user
-            Self::do_insert_user_orders(who.clone(), order_index);
-
-            Self::deposit_event(Event::CreateOrderSuccess(
-                who,
-                order_index,
-                resource_index,
-                rent_duration,
-                public_key,
-            ));
+            Self::do_create_order_info(who, resource_index, rent_duration, public_key, manifest)?;
             Ok(())
         }
 
         /// order execution
-        /// Provider used this func to execute the order
+        /// Provider or its operator used this func to execute the order
         #[transactional]
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
         pub fn order_exec(origin: OriginFor<T>, order_index: u64) -> DispatchResult {
             let who = ensure_signed(origin)?;
 
-            // check user has staking
-            ensure!(
-                T::MarketInterface::staking_exit(who.clone()),
-                Error::<T>::StakingNotExist
-            );
-
-            // check if an order exists
-            ensure!(
-                ResourceOrders::<T>::contains_key(order_index),
-                Error::<T>::OrderDoesNotExist
-            );
-            // get order details
-            let mut order = ResourceOrders::<T>::get(order_index).unwrap();
-
-            // determine order status
-            ensure!(
-                order.status == OrderStatus::Pending,
-                Error::<T>::OrderStatusError
-            );
-
-            // lock the user staking
-            ensure!(
-                T::MarketInterface::change_stake_amount(
-                    order.tenant_info.account_id.clone(),
-                    ChangeAmountType::Lock,
-                    T::MarketInterface::client_staking_fee(),
-                    MarketUserStatus::Client,
-                ),
-                Error::<T>::LockAmountFailed,
-            );
-
-            // get resource information
-            let mut resource_info =
-                match T::OrderInterface::get_computing_resource_info(order.resource_index) {
-                    Some(x) => x,
-                    None => Err(Error::<T>::ResourceNotExist)?,
-                };
-            // determine whether it is me
-            ensure!(
-                who.clone() == resource_info.account_id,
-                Error::<T>::OrderNotOwnedByYou
-            );
-
-            // get the current block height
-            let block_number = <frame_system::Pallet<T>>::block_number();
-            // get resource number
-            let resource_index = order.resource_index;
-
-            // whether it is a renewal order
-            if order.clone().is_renew_order() {
-                // query resource agreement number
-                let agreement_index = order.agreement_index.unwrap();
-                let agreement_opt = RentalAgreements::<T>::get(agreement_index);
-                ensure!(
-                    agreement_opt.is_some(),
-                    Error::<T>::ResourceNotExist
-                );
-                // query protocol
-                let mut agreement = agreement_opt.unwrap();
-                // get order duration
-                let duration = order.rent_duration;
-                // get the end block of the old order
-                let old_end = agreement.end.clone();
-
-                // agreement renewal
-                agreement.renew(duration, resource_info.clone());
-                // order status changes to completed
-                order.finish_order();
-                // increase usage time
-                resource_info.rental_statistics.add_rental_duration(
-                    T::BlockNumberToNumber::convert(order.rent_duration) as u32 / 600,
-                );
-                // Remove the corresponding protocol number from the original block
-                let new_vec = BlockWithAgreement::<T>::get(old_end)
-                    .into_iter()
-                    .filter(|x| x != &agreement_index)
-                    .collect::<Vec<u64>>();
-
-                // If the protocol number is deleted, vec is not empty
-                if !new_vec.is_empty() {
-                    BlockWithAgreement::<T>::mutate(old_end, |vec| {
-                        *vec = new_vec;
-                    });
-                } else {
-                    BlockWithAgreement::<T>::remove(old_end);
-                }
-
-                // Save the new block number and the corresponding expiring agreement number
-                Self::do_insert_block_with_agreement(agreement.end, agreement_index).ok();
-                // save resource state
-                T::OrderInterface::update_computing_resource(resource_index, resource_info.clone());
-                // Add the agreement to the lease agreement collection
-                RentalAgreements::<T>::insert(agreement_index, agreement.clone());
-                // save order
-                ResourceOrders::<T>::insert(order_index, order.clone());
-                // save the pledge
-                // Staking::<T>::insert(who.clone(), staking_info);
-
-                Self::deposit_event(Event::OrderExecSuccess(
-                    who.clone(),
-                    order_index,
-                    resource_index,
-                    agreement_index,
-                ));
-            } else {
-                // get agreement number
-                let agreement_index = AgreementIndex::<T>::get();
-                // determine if the resource is locked
-                ensure!(
-                    resource_info.status == ResourceStatus::Locked,
-                    Error::<T>::ResourceHasBeenRented
-                );
-                // get peer id
-                let peer_id = resource_info.peer_id.clone();
-                // end block
-                let end = block_number + order.rent_duration;
-                // get the current time
-                let now = T::UnixTime::now();
-                // create a rental agreement
-                let agreement = RentalAgreement::new(
-                    agreement_index,
-                    who.clone(),
-                    order.clone().tenant_info,
-                    peer_id,
-                    resource_index,
-                    resource_info.config.clone(),
-                    resource_info.rental_info.clone(),
-                    0,
-                    0,
-                    block_number,
-                    end,
-                    block_number,
-                    now,
-                );
-
-                // order status changes to completed
-                order.finish_order();
-                // resource status changed from locked to in use
-                resource_info.update_status(ResourceStatus::Inuse);
-                // usage count+1
-                resource_info.rental_statistics.add_rental_count();
-                // increase usage time
-                resource_info.rental_statistics.add_rental_duration(
-                    T::BlockNumberToNumber::convert(order.rent_duration) as u32 / 600,
-                );
-
-                // Add protocol expiration block number and protocol number
-                Self::do_insert_block_with_agreement(end, agreement_index).ok();
-                // associate user and protocol number
-                Self::do_insert_user_agreements(
-                    agreement.tenant_info.account_id.clone(),
-                    agreement_index,
-                );
-                // associate provider and agreement number
-                Self::do_insert_provider_agreements(agreement.provider.clone(), agreement_index);
-                // agreement number+1
-                AgreementIndex::<T>::put(agreement_index + 1);
-                // Add the agreement to the lease agreement collection
-                RentalAgreements::<T>::insert(agreement_index, agreement.clone());
-                // save order
-                ResourceOrders::<T>::insert(order_index, order.clone());
-                // save the pledge
-                // Staking::<T>::insert(who.clone(), staking_info);
-                // save resource state
-                T::OrderInterface::update_computing_resource(resource_index, resource_info.clone());
-
-                Self::deposit_event(Event::OrderExecSuccess(
-                    who.clone(),
-                    order_index,
-                    resource_index,
-                    agreement_index,
-                ));
-            }
-
-            Ok(())
+            Self::do_order_exec(who, order_index)
         }
 
         /// protocol resource heartbeat report
+        /// * proof: optional payload signed by the peer key of the resource
         #[transactional]
         #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
-        pub fn heartbeat(origin: OriginFor<T>, agreement_index: u64) -> DispatchResult {
+        pub fn heartbeat(
+            origin: OriginFor<T>,
+            agreement_index: u64,
+            proof: Option<LivenessProof<T::BlockNumber>>,
+        ) -> DispatchResult {
             let who = ensure_signed(origin)?;
 
             // check the agree exit, and get agreement
@@ -620,28 +699,47 @@ pub mod pallet {
                 Error::<T>::ProtocolDoesNotExist
             );
             let mut agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
-            // determine whether it is me
+            // determine whether it is me or my operator
             ensure!(
-                who.clone() == agreement.provider,
+                T::OperatorInterface::is_authorized(
+                    &agreement.provider,
+                    &who,
+                    OperatorScope::Resource(agreement.resource_index),
+                ),
                 Error::<T>::ProtocolNotOwnedByYou
             );
             // get the current block height
             let block_number = <frame_system::Pallet<T>>::block_number();
+            // check the machine behind the peer id signed the heartbeat
+            if let Some(proof) = proof.as_ref() {
+                Self::check_liveness_proof(agreement_index, &agreement.peer_id, proof)?;
+            }
+            // get the last reported block
+            let last_calculation = agreement.calculation;
 
             // Execution Agreement, Current Release Amount
-            ensure!(
-                agreement.execution(&block_number),
-                Error::<T>::AgreementHasBeenPunished
-            );
+            let amount = match agreement.execution(&block_number) {
+                Some(x) => x,
+                None => Err(Error::<T>::AgreementHasBeenPunished)?,
+            };
 
+            // move the heartbeat deadline
+            Self::delete_heartbeat_deadline(agreement_index, last_calculation);
+            Self::do_insert_heartbeat_deadline(agreement_index, agreement.calculation);
             // save the agreement
             RentalAgreements::<T>::insert(agreement_index, agreement.clone());
+            // pay the provider for the period served
+            Self::release_rent(agreement_index, &agreement.provider, amount)?;
 
             Self::deposit_event(Event::HealthCheckSuccess(
-                who.clone(),
+                agreement.provider.clone(),
                 agreement_index,
                 block_number,
             ));
+            if let Some(proof) = proof {
+                LivenessProofs::<T>::insert(agreement_index, proof.block);
+                Self::deposit_event(Event::LivenessProven(agreement_index, proof.block));
+            }
             Ok(())
         }
 
@@ -662,55 +760,20 @@ pub mod pallet {
                 who.clone() == order.tenant_info.account_id,
                 Error::<T>::OrderNotOwnedByYou
             );
-            // get resource information
-            let mut resource =
-                match T::OrderInterface::get_computing_resource_info(order.resource_index) {
-                    Some(x) => x,
-                    None => Err(Error::<T>::ResourceNotExist)?,
-                };
-            // check order status
-            if order.clone().is_renew_order() && order.status == OrderStatus::Pending {
-                // cancel order
-                order.cancel_order();
-                // save order
-                ResourceOrders::<T>::insert(order_index, order);
-                Self::deposit_event(Event::WithdrawLockedOrderPriceSuccess(
-                    who.clone(),
-                    order_index,
-                ));
-            } else if !order.clone().is_renew_order() && order.status == OrderStatus::Pending {
-                // cancel order
-                order.cancel_order();
-                // change the resource state to unused
-                resource.status = ResourceStatus::Unused;
-                // get back the amount
-                // T::Currency::transfer(&Self::order_pool(), &who.clone(), price, ExistenceRequirement::AllowDeath)?;
-
-                // save order
-                ResourceOrders::<T>::insert(order_index, order);
-                // save resource state
-                T::OrderInterface::update_computing_resource(resource.index, resource);
-
-                Self::deposit_event(Event::WithdrawLockedOrderPriceSuccess(
-                    who.clone(),
-                    order_index,
-                ));
-            } else {
-                // cancel order
-                order.cancel_order();
-                // change the resource state to unused
-                resource.status = ResourceStatus::Unused;
-                // save order
-                ResourceOrders::<T>::insert(order_index, order);
-                // save resource state
-                T::OrderInterface::update_computing_resource(resource.index, resource);
-
-                Self::deposit_event(Event::WithdrawLockedOrderPriceSuccess(
-                    who.clone(),
-                    order_index,
-                ));
-            }
+            // only a pending order can be canceled, use terminate_agreement for an executed one
+            ensure!(
+                order.status == OrderStatus::Pending,
+                Error::<T>::OrderStatusError
+            );
+
+            // cancel order, get back the amount and release the resource
+            Self::do_cancel_order(order_index, &mut order)?;
 
+            Self::deposit_event(Event::WithdrawLockedOrderPriceSuccess(
+                who.clone(),
+                order_index,
+                order.price,
+            ));
             Ok(())
         }
 
@@ -724,68 +787,1264 @@ pub mod pallet {
         ) -> DispatchResult {
             let who = ensure_signed(origin)?;
 
-            // get agreement
+            Self::do_renew_agreement(who, agreement_index, duration)?;
+            Ok(())
+        }
+
+        /// terminate the agreement before the end
+        /// the tenant gets back the rent of the remaining blocks, less the early termination fee
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6, 10))]
+        pub fn terminate_agreement(origin: OriginFor<T>, agreement_index: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::do_terminate_agreement(who, agreement_index, T::EarlyTerminationFee::get())
+        }
+
+        /// apply for a free resource trial, every account can apply once
+        /// [cpu, memory, trial duration (hours), deploy type, public key]
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn apply_free_resource(
+            origin: OriginFor<T>,
+            cpu: u64,
+            memory: u64,
+            duration: u32,
+            deploy_type: u32,
+            public_key: Bytes,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // check whether the user has applied
             ensure!(
-                RentalAgreements::<T>::contains_key(agreement_index),
-                Error::<T>::ProtocolDoesNotExist
+                !ApplyUsers::<T>::contains_key(who.clone()),
+                Error::<T>::FreeResourceApplied
             );
-            let agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
+            // check the applied resource
             ensure!(
-                agreement.status == AgreementStatus::Using,
-                Error::<T>::AgreementHasBeenFinished
+                cpu > 0
+                    && memory > 0
+                    && duration > 0
+                    && duration <= T::MaxFreeResourceDuration::get(),
+                Error::<T>::FreeResourceForbidden
             );
-            // get resource number
-            let resource_index = agreement.resource_index;
-            // get resource information
-            let resource_info = match T::OrderInterface::get_computing_resource_info(resource_index)
-            {
-                Some(x) => x,
+
+            // get apply order length
+            let index = ApplyOrderIndex::<T>::get();
+            // get the current block height
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            // create apply order
+            let apply_order = ApplyOrder::new(
+                index,
+                TenantInfo::new(who.clone(), public_key.clone()),
+                cpu,
+                memory,
+                duration,
+                deploy_type,
+                block_number,
+                T::UnixTime::now(),
+            );
+
+            ApplyOrders::<T>::insert(index, apply_order);
+            ApplyOrderIndex::<T>::put(index + 1);
+            ApplyUsers::<T>::insert(who.clone(), index);
+
+            Self::deposit_event(Event::FreeResourceApplied(
+                who,
+                index,
+                cpu,
+                memory,
+                duration,
+                deploy_type,
+                public_key,
+            ));
+            Ok(())
+        }
+
+        /// process a free resource apply order
+        /// [apply order number, unused resource allocated to the trial]
+        /// the provider and the peer id of the trial are the ones of the resource
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2))]
+        pub fn process_apply_order(
+            origin: OriginFor<T>,
+            index: u64,
+            resource_index: u64,
+        ) -> DispatchResult {
+            T::FreeResourceOrigin::ensure_origin(origin)?;
+
+            // check if an apply order exists
+            ensure!(
+                ApplyOrders::<T>::contains_key(index),
+                Error::<T>::FreeResourceNotExists
+            );
+            let mut apply_order = ApplyOrders::<T>::get(index).unwrap();
+            // determine apply order status
+            ensure!(
+                apply_order.status == OrderStatus::Pending,
+                Error::<T>::FreeResourceHasBeDeal
+            );
+
+            // get resource information
+            let mut resource = match T::OrderInterface::get_computing_resource_info(resource_index)
+            {
+                Some(x) => x,
                 None => Err(Error::<T>::ResourceNotExist)?,
             };
+            ensure!(
+                resource.status == ResourceStatus::Unused,
+                Error::<T>::ResourceHasBeenRented
+            );
+            // the resource must provide what was applied for
+            ensure!(
+                resource.config.cpu >= apply_order.cpu
+                    && resource.config.memory >= apply_order.memory,
+                Error::<T>::FreeResourceForbidden
+            );
+
+            // get the current block height
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            // calculate the block at which the trial expires
+            let trial_blocks: T::BlockNumber =
+                T::BlockNumberToNumber::convert(apply_order.rent_duration * 600);
+            let end = block_number + trial_blocks;
+            ensure!(
+                end < resource.rental_info.end_of_rent,
+                Error::<T>::ExceedTheRentableTime
+            );
+
+            let peer_id = resource.peer_id.clone();
+            apply_order.processed(
+                resource.account_id.clone(),
+                peer_id.clone(),
+                resource_index,
+                end,
+            );
+            // the resource is in use until the trial expires
+            resource.update_status(ResourceStatus::Inuse);
+            T::OrderInterface::update_computing_resource(resource_index, resource);
+
+            ApplyOrders::<T>::insert(index, apply_order);
+            BlockWithApplyOrder::<T>::mutate(end, |vec| vec.push(index));
+
+            Self::deposit_event(Event::FreeResourceProcessed(index, peer_id));
+            Ok(())
+        }
+
+        /// create an order for the cheapest unused resource meeting the requirement
+        /// the most the order can cost is held in the order pool, the difference with the price
+        /// of the matched resource is refunded.
+        /// without a matching resource, the order waits for one until OrderMatchWaitingTime
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn create_order_by_spec(
+            origin: OriginFor<T>,
+            requirement: ResourceRequirement,
+            public_key: Bytes,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // check user has staking
+            ensure!(
+                T::MarketInterface::staking_exit(who.clone()),
+                Error::<T>::StakingNotExist
+            );
+            // the matching counts the lease in blocks
+            ensure!(
+                requirement.rent_duration > 0
+                    && requirement.rent_duration.checked_mul(600).is_some(),
+                Error::<T>::InvalidRentDuration
+            );
+
+            // hold the most the order can cost in the order pool
+            T::Currency::transfer(
+                &who.clone(),
+                &Self::order_pool(),
+                T::NumberToBalance::convert(requirement.max_cost()),
+                ExistenceRequirement::KeepAlive,
+            )
+            .map_err(|_| Error::<T>::InsufficientCurrency)?;
+
             // get the current block height
             let block_number = <frame_system::Pallet<T>>::block_number();
-            // get resource end time
-            let end_resource = resource_info.rental_info.end_of_rent;
-            // get rental block
-            let rent_duration = T::BlockNumberToNumber::convert(duration * 600);
+            // get order length
+            let order_index = OrderIndex::<T>::get();
+            // order length+1
+            OrderIndex::<T>::put(order_index + 1);
+
+            let order = WaitingOrder {
+                index: order_index,
+                tenant_info: TenantInfo::new(who.clone(), public_key),
+                requirement,
+                create: block_number,
+                deadline: block_number + T::OrderMatchWaitingTime::get(),
+            };
+
+            if Self::match_waiting_order(&order, block_number)?.0 {
+                return Ok(());
+            }
+
+            // no resource meets the requirement yet
+            let mut queue = WaitingOrderQueue::<T>::get();
+            ensure!(
+                (queue.len() as u32) < T::MaxWaitingOrders::get(),
+                Error::<T>::TooManyWaitingOrders
+            );
+            queue.push(order_index);
+            WaitingOrderQueue::<T>::put(queue);
+            WaitingOrders::<T>::insert(order_index, order.clone());
+
+            Self::deposit_event(Event::OrderWaiting(who, order_index, order.deadline));
+            Ok(())
+        }
+
+        /// post a compute request the providers bid on until the end of the bidding period
+        /// the most the order can cost is held in the order pool until the settlement
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn create_compute_request(
+            origin: OriginFor<T>,
+            requirement: ResourceRequirement,
+            bidding_period: T::BlockNumber,
+            public_key: Bytes,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // check user has staking
+            ensure!(
+                T::MarketInterface::staking_exit(who.clone()),
+                Error::<T>::StakingNotExist
+            );
+            ensure!(
+                !bidding_period.is_zero() && bidding_period <= T::MaxBiddingPeriod::get(),
+                Error::<T>::InvalidBiddingPeriod
+            );
+            // the bids and the settlement count the lease in blocks
+            ensure!(
+                requirement.rent_duration > 0
+                    && requirement.rent_duration.checked_mul(600).is_some(),
+                Error::<T>::InvalidRentDuration
+            );
+
+            // get the current block height
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            let deadline = block_number + bidding_period;
+            // the request is settled in the block after the deadline
+            let settlement = deadline + One::one();
+            ensure!(
+                (BlockWithComputeRequest::<T>::get(settlement).len() as u32)
+                    < T::MaxComputeRequestsPerBlock::get(),
+                Error::<T>::ExceedsMaximumQuantity
+            );
+
+            // hold the most the order can cost in the order pool
+            T::Currency::transfer(
+                &who.clone(),
+                &Self::order_pool(),
+                T::NumberToBalance::convert(requirement.max_cost()),
+                ExistenceRequirement::KeepAlive,
+            )
+            .map_err(|_| Error::<T>::InsufficientCurrency)?;
+
+            let request_index = ComputeRequestIndex::<T>::get();
+            let request = ComputeRequest::new(
+                request_index,
+                TenantInfo::new(who.clone(), public_key),
+                requirement,
+                block_number,
+                deadline,
+            );
+
+            ComputeRequests::<T>::insert(request_index, request);
+            ComputeRequestIndex::<T>::put(request_index + 1);
+            BlockWithComputeRequest::<T>::mutate(settlement, |vec| vec.push(request_index));
+
+            Self::deposit_event(Event::ComputeRequestCreated(who, request_index, deadline));
+            Ok(())
+        }
+
+        /// bid an unused resource on a compute request
+        /// Provider or its operator used this func, the resource is locked until the settlement
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn bid_compute_request(
+            origin: OriginFor<T>,
+            request_index: u64,
+            resource_index: u64,
+            unit_price: u128,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // check if a compute request exists
+            ensure!(
+                ComputeRequests::<T>::contains_key(request_index),
+                Error::<T>::ComputeRequestNotExist
+            );
+            let request = ComputeRequests::<T>::get(request_index).unwrap();
+
+            // get the current block height
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            ensure!(
+                request.status == ComputeRequestStatus::Bidding && block_number <= request.deadline,
+                Error::<T>::BiddingClosed
+            );
+
+            // get resource information
+            let mut resource_info =
+                match T::OrderInterface::get_computing_resource_info(resource_index) {
+                    Some(x) => x,
+                    None => Err(Error::<T>::ResourceNotExist)?,
+                };
+            // determine whether it is me or my operator
+            ensure!(
+                T::OperatorInterface::is_authorized(
+                    &resource_info.account_id,
+                    &who,
+                    OperatorScope::Resource(resource_index),
+                ),
+                Error::<T>::OrderNotOwnedByYou
+            );
+            // check provider has staking
+            ensure!(
+                T::MarketInterface::staking_exit(resource_info.account_id.clone()),
+                Error::<T>::StakingNotExist
+            );
+            ensure!(
+                resource_info.status == ResourceStatus::Unused,
+                Error::<T>::ResourceHasBeenRented
+            );
+            ensure!(
+                request.requirement.matches(&resource_info.config),
+                Error::<T>::ResourceNotMatched
+            );
+            ensure!(
+                unit_price <= request.requirement.max_price,
+                Error::<T>::BidAboveMaxPrice
+            );
+            // the resource must be rentable from the settlement to the end of the order
+            let rent_blocks: T::BlockNumber =
+                T::BlockNumberToNumber::convert(request.requirement.rent_duration * 600);
+            ensure!(
+                request.deadline + One::one() + rent_blocks < resource_info.rental_info.end_of_rent,
+                Error::<T>::ExceedTheRentableTime
+            );
+
+            let mut bids = RequestBids::<T>::get(request_index);
+            ensure!(
+                (bids.len() as u32) < T::MaxBidsPerRequest::get(),
+                Error::<T>::TooManyBids
+            );
+            bids.push(Bid {
+                bidder: who.clone(),
+                resource_index,
+                unit_price,
+                block: block_number,
+                status: BidStatus::Pending,
+            });
+
+            // resource status changed from unused to locked until the settlement
+            resource_info.update_status(ResourceStatus::Locked);
+            T::OrderInterface::update_computing_resource(resource_index, resource_info);
+            RequestBids::<T>::insert(request_index, bids);
+
+            Self::deposit_event(Event::BidPlaced(
+                who,
+                request_index,
+                resource_index,
+                unit_price,
+            ));
+            Ok(())
+        }
+
+        /// lock several resources for the same duration, all of them or none
+        /// every member is a resource index or the spec of the cheapest resource to match
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn create_cluster_order(
+            origin: OriginFor<T>,
+            members: Vec<ClusterMember>,
+            rent_duration: u32,
+            public_key: Bytes,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(
+                !members.is_empty() && members.len() as u32 <= T::MaxClusterSize::get(),
+                Error::<T>::InvalidClusterSize
+            );
+
+            // get the current block height
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            let rent_blocks = rent_duration
+                .checked_mul(600)
+                .ok_or(Error::<T>::InvalidRentDuration)?;
+            let rent_blocks: T::BlockNumber = T::BlockNumberToNumber::convert(rent_blocks);
+            let cluster_index = ClusterIndex::<T>::get();
+
+            let mut orders = Vec::new();
+            for member in members {
+                let resource_index = match member {
+                    ClusterMember::Resource(index) => index,
+                    ClusterMember::Spec {
+                        cpu,
+                        memory,
+                        system,
+                        max_price,
+                    } => {
+                        let requirement =
+                            ResourceRequirement::new(cpu, memory, system, max_price, rent_duration);
+                        T::OrderInterface::match_resource(&requirement, block_number + rent_blocks)
+                            .0
+                            .ok_or(Error::<T>::NoMatchingResource)?
+                    }
+                };
+
+                // the resource is locked before the next member is matched
+                let order_index = Self::do_create_order_info(
+                    who.clone(),
+                    resource_index,
+                    rent_duration,
+                    public_key.clone(),
+                    None,
+                )?;
+                OrderCluster::<T>::insert(order_index, cluster_index);
+                orders.push(order_index);
+            }
+
+            ClusterOrders::<T>::insert(
+                cluster_index,
+                ClusterOrder {
+                    index: cluster_index,
+                    tenant: who.clone(),
+                    orders: orders.clone(),
+                    agreements: Vec::new(),
+                    rent_duration,
+                    create: block_number,
+                    status: ClusterStatus::Active,
+                },
+            );
+            ClusterIndex::<T>::put(cluster_index + 1);
+            UserClusters::<T>::mutate(who.clone(), |vec| vec.push(cluster_index));
+
+            Self::deposit_event(Event::ClusterOrderCreated(who, cluster_index, orders));
+            Ok(())
+        }
+
+        /// renew the agreements of the cluster in use, all of them or none
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn renew_cluster(
+            origin: OriginFor<T>,
+            cluster_index: u64,
+            duration: u32,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(
+                ClusterOrders::<T>::contains_key(cluster_index),
+                Error::<T>::ClusterDoesNotExist
+            );
+            let cluster = ClusterOrders::<T>::get(cluster_index).unwrap();
+            ensure!(
+                who.clone() == cluster.tenant,
+                Error::<T>::ClusterNotOwnedByYou
+            );
+
+            let mut orders = Vec::new();
+            for agreement_index in cluster.agreements {
+                // the agreements that have ended are not renewed
+                if !RentalAgreements::<T>::get(agreement_index).map_or(false, |agreement| {
+                    agreement.status == AgreementStatus::Using
+                }) {
+                    continue;
+                }
+                orders.push(Self::do_renew_agreement(
+                    who.clone(),
+                    agreement_index,
+                    duration,
+                )?);
+            }
+            ensure!(!orders.is_empty(), Error::<T>::AgreementHasBeenFinished);
+
+            Self::deposit_event(Event::ClusterRenewed(who, cluster_index, orders));
+            Ok(())
+        }
+
+        /// terminate the agreements of the cluster in use and cancel its pending orders
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn terminate_cluster(origin: OriginFor<T>, cluster_index: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(
+                ClusterOrders::<T>::contains_key(cluster_index),
+                Error::<T>::ClusterDoesNotExist
+            );
+            let cluster = ClusterOrders::<T>::get(cluster_index).unwrap();
+            ensure!(
+                who.clone() == cluster.tenant,
+                Error::<T>::ClusterNotOwnedByYou
+            );
+
+            // 1. terminate the agreements in use
+            for agreement_index in cluster.agreements {
+                if RentalAgreements::<T>::get(agreement_index).map_or(false, |agreement| {
+                    agreement.status == AgreementStatus::Using
+                }) {
+                    Self::do_terminate_agreement(
+                        who.clone(),
+                        agreement_index,
+                        T::EarlyTerminationFee::get(),
+                    )?;
+                }
+            }
+
+            // 2. cancel the orders the providers have not executed yet
+            for order_index in cluster.orders {
+                if let Some(mut order) = ResourceOrders::<T>::get(order_index) {
+                    if order.status == OrderStatus::Pending {
+                        Self::do_cancel_order(order_index, &mut order)?;
+                    }
+                }
+            }
+
+            Self::deposit_event(Event::ClusterTerminated(who, cluster_index));
+            Ok(())
+        }
+
+        /// replace the deployment manifest of the agreement
+        /// the provider agent watches ManifestUpdated and redeploys
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn update_manifest(
+            origin: OriginFor<T>,
+            agreement_index: u64,
+            manifest: Manifest,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // get agreement
+            ensure!(
+                RentalAgreements::<T>::contains_key(agreement_index),
+                Error::<T>::ProtocolDoesNotExist
+            );
+            let agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
+            // only the tenant can change the manifest
+            ensure!(
+                who.clone() == agreement.tenant_info.account_id,
+                Error::<T>::ProtocolNotOwnedByYou
+            );
+            ensure!(
+                agreement.status == AgreementStatus::Using,
+                Error::<T>::AgreementHasBeenFinished
+            );
+            ensure!(manifest.is_valid(), Error::<T>::InvalidManifest);
+
+            AgreementManifests::<T>::insert(agreement_index, manifest.clone());
+
+            Self::deposit_event(Event::ManifestUpdated(who, agreement_index, manifest));
+            Ok(())
+        }
+
+        /// publish how the tenant reaches the machine of the agreement
+        /// Provider or its operator used this func, the payload is sealed to the public key of
+        /// the tenant (see primitives::p_sealed_box). publishing again rotates the access info
+        #[transactional]
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn publish_access_info(
+            origin: OriginFor<T>,
+            agreement_index: u64,
+            payload: Vec<u8>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            // get agreement
+            ensure!(
+                RentalAgreements::<T>::contains_key(agreement_index),
+                Error::<T>::ProtocolDoesNotExist
+            );
+            let agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
+            // determine whether it is me or my operator
+            ensure!(
+                T::OperatorInterface::is_authorized(
+                    &agreement.provider,
+                    &who,
+                    OperatorScope::Resource(agreement.resource_index),
+                ),
+                Error::<T>::ProtocolNotOwnedByYou
+            );
+            ensure!(
+                agreement.status == AgreementStatus::Using,
+                Error::<T>::AgreementHasBeenFinished
+            );
+            ensure!(
+                !payload.is_empty() && payload.len() <= MAX_ACCESS_INFO_LEN,
+                Error::<T>::InvalidAccessInfo
+            );
+
+            let version = AccessInfos::<T>::get(agreement_index).map_or(1, |x| x.version + 1);
+            AccessInfos::<T>::insert(
+                agreement_index,
+                AccessInfo {
+                    version,
+                    payload,
+                    block: <frame_system::Pallet<T>>::block_number(),
+                },
+            );
+
+            Self::deposit_event(Event::AccessInfoPublished(
+                agreement.tenant_info.account_id,
+                agreement_index,
+                version,
+            ));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Config> Pallet<T> {
+    // the account holding the rent of orders and agreements
+    pub fn order_pool() -> T::AccountId {
+        PALLET_ID.into_sub_account(b"order")
+    }
+
+    // keep the order pool above the existential deposit, the rent leaves it with KeepAlive
+    pub fn endow_order_pool() {
+        let pool = Self::order_pool();
+        if T::Currency::total_balance(&pool).is_zero() {
+            let _ = T::Currency::deposit_creating(&pool, T::Currency::minimum_balance());
+        }
+    }
+
+    // release the rent held in the order pool to the provider
+    pub fn release_rent(
+        agreement_index: u64,
+        provider: &T::AccountId,
+        amount: u128,
+    ) -> DispatchResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        T::Currency::transfer(
+            &Self::order_pool(),
+            provider,
+            T::NumberToBalance::convert(amount),
+            ExistenceRequirement::KeepAlive,
+        )?;
+
+        Self::deposit_event(Event::WithdrawRentalAmountSuccess(
+            provider.clone(),
+            agreement_index,
+            amount,
+        ));
+        Ok(())
+    }
+
+    // return the unused rent held in the order pool to the tenant
+    pub fn refund_rent(
+        agreement_index: u64,
+        tenant: &T::AccountId,
+        amount: u128,
+    ) -> DispatchResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        T::Currency::transfer(
+            &Self::order_pool(),
+            tenant,
+            T::NumberToBalance::convert(amount),
+            ExistenceRequirement::KeepAlive,
+        )?;
+
+        Self::deposit_event(Event::RentRefunded(tenant.clone(), agreement_index, amount));
+        Ok(())
+    }
+
+    // return the rent held in the order pool to the tenant
+    pub fn return_rent(tenant: &T::AccountId, price: u128) -> DispatchResult {
+        if price == 0 {
+            return Ok(());
+        }
+        T::Currency::transfer(
+            &Self::order_pool(),
+            tenant,
+            T::NumberToBalance::convert(price),
+            ExistenceRequirement::KeepAlive,
+        )?;
+        Ok(())
+    }
+
+    // lock the resource and create the order, the rent is held in the order pool
+    pub fn do_create_order_info(
+        who: T::AccountId,
+        resource_index: u64,
+        rent_duration: u32,
+        public_key: Bytes,
+        manifest: Option<Manifest>,
+    ) -> Result<u64, DispatchError> {
+        // check user has staking
+        ensure!(
+            T::MarketInterface::staking_exit(who.clone()),
+            Error::<T>::StakingNotExist
+        );
+        ensure!(
+            manifest.as_ref().map_or(true, |x| x.is_valid()),
+            Error::<T>::InvalidManifest
+        );
+
+        // get resource information
+        let resource_info = match T::OrderInterface::get_computing_resource_info(resource_index) {
+            Some(x) => x,
+            None => Err(Error::<T>::ResourceNotExist)?,
+        };
+        // determine if the resource is draining or leased
+        ensure!(
+            resource_info.status != ResourceStatus::Draining,
+            Error::<T>::ResourceDraining
+        );
+        ensure!(
+            resource_info.status == ResourceStatus::Unused,
+            Error::<T>::ResourceHasBeenRented
+        );
+
+        // get the current block height
+        let block_number = <frame_system::Pallet<T>>::block_number();
+        // calculate persistent blocks
+        let rent_blocks = TryInto::<T::BlockNumber>::try_into(rent_duration * 600)
+            .ok()
+            .unwrap();
+        // determine whether the rental period is exceeded
+        ensure!(
+            block_number + rent_blocks < resource_info.rental_info.end_of_rent,
+            Error::<T>::ExceedTheRentableTime
+        );
+
+        // calculate the order price: unit price (per hour) * rental duration (hours)
+        let price = resource_info
+            .rental_info
+            .rent_unit_price
+            .saturating_mul(rent_duration as u128);
+        // transfer the rent to the order pool, it is held there until the agreement is settled
+        T::Currency::transfer(
+            &who.clone(),
+            &Self::order_pool(),
+            T::NumberToBalance::convert(price),
+            ExistenceRequirement::KeepAlive,
+        )
+        .map_err(|_| Error::<T>::InsufficientCurrency)?;
+
+        // get order length
+        let order_index = OrderIndex::<T>::get();
+        // order length+1
+        OrderIndex::<T>::put(order_index + 1);
+        // create a tenant
+        let customer = TenantInfo::new(who, public_key);
+
+        Self::do_create_order(
+            order_index,
+            customer,
+            resource_index,
+            resource_info,
+            rent_duration,
+            price,
+            block_number,
+            manifest,
+        );
+        Ok(order_index)
+    }
+
+    // create the renewal order of the agreement, the rent is held in the order pool
+    pub fn do_renew_agreement(
+        who: T::AccountId,
+        agreement_index: u64,
+        duration: u32,
+    ) -> Result<u64, DispatchError> {
+        // get agreement
+        ensure!(
+            RentalAgreements::<T>::contains_key(agreement_index),
+            Error::<T>::ProtocolDoesNotExist
+        );
+        let agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
+        // only the tenant can renew the agreement
+        ensure!(
+            who.clone() == agreement.tenant_info.account_id,
+            Error::<T>::ProtocolNotOwnedByYou
+        );
+        ensure!(
+            agreement.status == AgreementStatus::Using,
+            Error::<T>::AgreementHasBeenFinished
+        );
+        // get resource number
+        let resource_index = agreement.resource_index;
+        // get resource information
+        let resource_info = match T::OrderInterface::get_computing_resource_info(resource_index) {
+            Some(x) => x,
+            None => Err(Error::<T>::ResourceNotExist)?,
+        };
+        // a draining resource is not renewed
+        ensure!(
+            resource_info.status != ResourceStatus::Draining,
+            Error::<T>::ResourceDraining
+        );
+        // get the current block height
+        let block_number = <frame_system::Pallet<T>>::block_number();
+        // get resource end time
+        let end_resource = resource_info.rental_info.end_of_rent;
+        // get rental block
+        let rent_duration = T::BlockNumberToNumber::convert(duration * 600);
+        ensure!(
+            rent_duration + agreement.end < end_resource,
+            Error::<T>::InsufficientTimeForResource
+        );
+        // calculate new order price
+        let price = resource_info
+            .rental_info
+            .rent_unit_price
+            .saturating_mul(duration as u128);
+        // transfer the renewal rent to the order pool
+        T::Currency::transfer(
+            &who.clone(),
+            &Self::order_pool(),
+            T::NumberToBalance::convert(price),
+            ExistenceRequirement::KeepAlive,
+        )
+        .map_err(|_| Error::<T>::InsufficientCurrency)?;
+
+        // get order length
+        let order_index = OrderIndex::<T>::get();
+        // get the current time
+        let now = T::UnixTime::now();
+
+        let order = ResourceOrder::renew(
+            order_index,
+            agreement.tenant_info.clone(),
+            price,
+            resource_index,
+            block_number,
+            rent_duration,
+            now,
+            Some(agreement_index),
+        );
+
+        ResourceOrders::<T>::insert(order_index, order.clone());
+        OrderIndex::<T>::put(order_index + 1);
+        // save the order corresponding to the user
+        Self::do_insert_user_orders(who.clone(), order_index);
+        // cancel the order if the provider does not execute it in time
+        BlockWithOrder::<T>::mutate(block_number + T::OrderWaitingTime::get(), |vec| {
+            vec.push(order_index)
+        });
+
+        Self::deposit_event(Event::ReNewOrderSuccess(
+            who.clone(),
+            order_index,
+            resource_index,
+            duration,
+        ));
+        Ok(order_index)
+    }
+
+    // settle the rent of the agreement up to now with the fee on the rest and release the resource
+    pub fn do_terminate_agreement(
+        who: T::AccountId,
+        agreement_index: u64,
+        fee: Perbill,
+    ) -> DispatchResult {
+        // get agreement
+        ensure!(
+            RentalAgreements::<T>::contains_key(agreement_index),
+            Error::<T>::ProtocolDoesNotExist
+        );
+        let mut agreement = RentalAgreements::<T>::get(agreement_index).unwrap();
+        // only the tenant can terminate the agreement
+        ensure!(
+            who.clone() == agreement.tenant_info.account_id,
+            Error::<T>::ProtocolNotOwnedByYou
+        );
+        ensure!(
+            agreement.status != AgreementStatus::Punished,
+            Error::<T>::AgreementHasBeenPunished
+        );
+        ensure!(
+            agreement.status == AgreementStatus::Using,
+            Error::<T>::AgreementHasBeenFinished
+        );
+
+        // get the current block height
+        let block_number = <frame_system::Pallet<T>>::block_number();
+        let old_end = agreement.end;
+        let last_calculation = agreement.calculation;
+
+        // 1. settle the rent
+        let (paid, refund) = match agreement.terminate(&block_number, fee) {
+            Some(x) => x,
+            None => Err(Error::<T>::AgreementHasBeenFinished)?,
+        };
+        Self::release_rent(agreement_index, &agreement.provider, paid)?;
+        Self::return_rent(&who, refund)?;
+
+        // 2. remove the agreement from the block indexes
+        Self::delete_block_with_agreement(agreement_index, old_end);
+        Self::delete_heartbeat_deadline(agreement_index, last_calculation);
+
+        // 3. remove the agreement from the user and provider lists, unlock the user staking
+        Self::close_agreement(agreement_index, agreement.provider.clone(), who.clone());
+
+        // 4. the resource can be rented again
+        Self::release_resource(agreement.resource_index);
+        Self::clear_agreement_data(agreement_index);
+
+        // 5. cancel the pending renewals of the agreement and refund them
+        for order_index in UserOrders::<T>::get(who.clone()) {
+            if let Some(mut order) = ResourceOrders::<T>::get(order_index) {
+                if order.agreement_index == Some(agreement_index)
+                    && order.status == OrderStatus::Pending
+                {
+                    Self::do_cancel_order(order_index, &mut order)?;
+                    Self::deposit_event(Event::WithdrawLockedOrderPriceSuccess(
+                        who.clone(),
+                        order_index,
+                        order.price,
+                    ));
+                }
+            }
+        }
+
+        // save the agreement
+        RentalAgreements::<T>::insert(agreement_index, agreement);
+
+        Self::deposit_event(Event::AgreementTerminated(
+            who,
+            agreement_index,
+            paid,
+            refund,
+        ));
+        Ok(())
+    }
+
+    // turn a pending order into an agreement, or renew the agreement of a renewal order
+    pub fn do_order_exec(who: T::AccountId, order_index: u64) -> DispatchResult {
+        // check if an order exists
+        ensure!(
+            ResourceOrders::<T>::contains_key(order_index),
+            Error::<T>::OrderDoesNotExist
+        );
+        // get order details
+        let mut order = ResourceOrders::<T>::get(order_index).unwrap();
+
+        // determine order status
+        ensure!(
+            order.status == OrderStatus::Pending,
+            Error::<T>::OrderStatusError
+        );
+
+        // lock the user staking
+        ensure!(
+            T::MarketInterface::change_stake_amount(
+                order.tenant_info.account_id.clone(),
+                ChangeAmountType::Lock,
+                T::MarketInterface::client_staking_fee(),
+                MarketUserStatus::Client,
+            ),
+            Error::<T>::LockAmountFailed,
+        );
+
+        // get resource information
+        let mut resource_info =
+            match T::OrderInterface::get_computing_resource_info(order.resource_index) {
+                Some(x) => x,
+                None => Err(Error::<T>::ResourceNotExist)?,
+            };
+        // determine whether it is me or my operator
+        ensure!(
+            T::OperatorInterface::is_authorized(
+                &resource_info.account_id,
+                &who,
+                OperatorScope::Resource(order.resource_index),
+            ),
+            Error::<T>::OrderNotOwnedByYou
+        );
+        // the provider of the resource
+        let provider = resource_info.account_id.clone();
+        // check provider has staking
+        ensure!(
+            T::MarketInterface::staking_exit(provider.clone()),
+            Error::<T>::StakingNotExist
+        );
+
+        // get the current block height
+        let block_number = <frame_system::Pallet<T>>::block_number();
+        // get resource number
+        let resource_index = order.resource_index;
+
+        // whether it is a renewal order
+        if order.clone().is_renew_order() {
+            // query resource agreement number
+            let agreement_index = order.agreement_index.unwrap();
+            let agreement_opt = RentalAgreements::<T>::get(agreement_index);
+            ensure!(agreement_opt.is_some(), Error::<T>::ResourceNotExist);
+            // query protocol
+            let mut agreement = agreement_opt.unwrap();
+            // the agreement must still be in use
+            ensure!(
+                agreement.status == AgreementStatus::Using,
+                Error::<T>::AgreementHasBeenFinished
+            );
+            // a draining resource is not renewed
+            ensure!(
+                resource_info.status != ResourceStatus::Draining,
+                Error::<T>::ResourceDraining
+            );
+            // get order duration
+            let duration = order.rent_duration;
+            // get the end block of the old order
+            let old_end = agreement.end.clone();
+
+            // agreement renewal, the renewal price is added to the escrow
+            agreement.renew(duration, order.price, resource_info.clone());
+            // order status changes to completed
+            order.finish_order();
+            // increase usage time
+            resource_info.rental_statistics.add_rental_duration(
+                T::BlockNumberToNumber::convert(order.rent_duration) as u32 / 600,
+            );
+            // Remove the corresponding protocol number from the original block
+            let new_vec = BlockWithAgreement::<T>::get(old_end)
+                .into_iter()
+                .filter(|x| x != &agreement_index)
+                .collect::<Vec<u64>>();
+
+            // If the protocol number is deleted, vec is not empty
+            if !new_vec.is_empty() {
+                BlockWithAgreement::<T>::mutate(old_end, |vec| {
+                    *vec = new_vec;
+                });
+            } else {
+                BlockWithAgreement::<T>::remove(old_end);
+            }
+
+            // Save the new block number and the corresponding expiring agreement number
+            Self::do_insert_block_with_agreement(agreement.end, agreement_index);
+            // save resource state
+            T::OrderInterface::update_computing_resource(resource_index, resource_info.clone());
+            // Add the agreement to the lease agreement collection
+            RentalAgreements::<T>::insert(agreement_index, agreement.clone());
+            // save order
+            ResourceOrders::<T>::insert(order_index, order.clone());
+            // save the pledge
+            // Staking::<T>::insert(who.clone(), staking_info);
+
+            Self::deposit_event(Event::OrderExecSuccess(
+                who.clone(),
+                order_index,
+                resource_index,
+                agreement_index,
+                AgreementManifests::<T>::get(agreement_index),
+            ));
+        } else {
+            // get agreement number
+            let agreement_index = AgreementIndex::<T>::get();
+            // determine if the resource is locked
             ensure!(
-                rent_duration + agreement.end < end_resource,
-                Error::<T>::InsufficientTimeForResource
+                resource_info.status == ResourceStatus::Locked,
+                Error::<T>::ResourceHasBeenRented
             );
-            // calculate new order price
-
-            // get order length
-            let order_index = OrderIndex::<T>::get();
+            // get peer id
+            let peer_id = resource_info.peer_id.clone();
+            // end block
+            let end = block_number + order.rent_duration;
             // get the current time
             let now = T::UnixTime::now();
-
-            let order = ResourceOrder::renew(
-                order_index,
-                agreement.tenant_info.clone(),
+            // create a rental agreement
+            let agreement = RentalAgreement::new(
+                agreement_index,
+                provider.clone(),
+                order.clone().tenant_info,
+                peer_id,
                 resource_index,
+                resource_info.config.clone(),
+                resource_info.rental_info.clone(),
+                order.price,
+                0,
+                0,
+                block_number,
+                end,
                 block_number,
-                rent_duration,
                 now,
-                Some(agreement_index),
             );
 
+            // order status changes to completed
+            order.finish_order();
+            // resource status changed from locked to in use
+            resource_info.update_status(ResourceStatus::Inuse);
+            // usage count+1
+            resource_info.rental_statistics.add_rental_count();
+            // increase usage time
+            resource_info.rental_statistics.add_rental_duration(
+                T::BlockNumberToNumber::convert(order.rent_duration) as u32 / 600,
+            );
+
+            // Add protocol expiration block number and protocol number
+            Self::do_insert_block_with_agreement(end, agreement_index);
+            // the first heartbeat is due within the health check interval
+            Self::do_insert_heartbeat_deadline(agreement_index, block_number);
+            // associate user and protocol number
+            Self::do_insert_user_agreements(
+                agreement.tenant_info.account_id.clone(),
+                agreement_index,
+            );
+            // associate provider and agreement number
+            Self::do_insert_provider_agreements(agreement.provider.clone(), agreement_index);
+            // agreement number+1
+            AgreementIndex::<T>::put(agreement_index + 1);
+            // Add the agreement to the lease agreement collection
+            RentalAgreements::<T>::insert(agreement_index, agreement.clone());
+            // save order
             ResourceOrders::<T>::insert(order_index, order.clone());
-            OrderIndex::<T>::put(order_index + 1);
-            // save the order corresponding to the user
-            Self::do_insert_user_orders(who.clone(), order_index);
+            // save the pledge
+            // Staking::<T>::insert(who.clone(), staking_info);
+            // save resource state
+            T::OrderInterface::update_computing_resource(resource_index, resource_info.clone());
+            // the manifest of the order moves to the agreement
+            let manifest = OrderManifests::<T>::take(order_index);
+            if let Some(manifest) = manifest.clone() {
+                AgreementManifests::<T>::insert(agreement_index, manifest);
+            }
 
-            Self::deposit_event(Event::ReNewOrderSuccess(
+            Self::deposit_event(Event::OrderExecSuccess(
                 who.clone(),
                 order_index,
                 resource_index,
-                duration,
+                agreement_index,
+                manifest,
             ));
-            Ok(())
+
+            // link the agreement to the cluster of the order
+            if let Some(cluster_index) = OrderCluster::<T>::get(order_index) {
+                AgreementCluster::<T>::insert(agreement_index, cluster_index);
+                ClusterOrders::<T>::mutate(cluster_index, |cluster| {
+                    if let Some(cluster) = cluster {
+                        cluster.agreements.push(agreement_index);
+                    }
+                });
+                Self::deposit_event(Event::ClusterAgreementCreated(
+                    cluster_index,
+                    agreement_index,
+                ));
+            }
         }
+
+        // the provider executed the order
+        T::OrderInterface::update_reputation(resource_index, ReputationEvent::Rented);
+
+        Ok(())
+    }
+
+    // lock the resource and save the order, the rent is already in the order pool
+    pub fn do_create_order(
+        order_index: u64,
+        customer: TenantInfo<T::AccountId>,
+        resource_index: u64,
+        mut resource_info: ComputingResource<T::BlockNumber, T::AccountId>,
+        rent_duration: u32,
+        price: u128,
+        block_number: T::BlockNumber,
+        manifest: Option<Manifest>,
+    ) {
+        let who = customer.account_id.clone();
+        let public_key = customer.public_key.clone();
+        // calculate persistent blocks
+        let rent_blocks = TryInto::<T::BlockNumber>::try_into(rent_duration * 600)
+            .ok()
+            .unwrap();
+        // get the current time
+        let now = T::UnixTime::now();
+        // create order
+        let order = ResourceOrder::new(
+            order_index,
+            customer,
+            price,
+            resource_index,
+            block_number,
+            rent_blocks,
+            now,
+        );
+
+        // resource status changed from unused to locked
+        resource_info.update_status(ResourceStatus::Locked);
+
+        // save resource state
+        T::OrderInterface::update_computing_resource(resource_index, resource_info);
+        // add order to order collection
+        ResourceOrders::<T>::insert(order_index, order);
+        // the provider deploys the manifest once it executes the order
+        if let Some(manifest) = manifest.clone() {
+            OrderManifests::<T>::insert(order_index, manifest);
+        }
+        // save the order corresponding to the user
+        Self::do_insert_user_orders(who.clone(), order_index);
+        // cancel the order if the provider does not execute it in time
+        BlockWithOrder::<T>::mutate(block_number + T::OrderWaitingTime::get(), |vec| {
+            vec.push(order_index)
+        });
+
+        Self::deposit_event(Event::CreateOrderSuccess(
+            who,
+            order_index,
+            resource_index,
+            rent_duration,
+            public_key,
+            manifest,
+        ));
+    }
+
+    // create the order of a waiting order with the cheapest resource meeting its requirement,
+    // false if there is none. the number of resources read to find it is returned too
+    pub fn match_waiting_order(
+        order: &WaitingOrder<T::AccountId, T::BlockNumber>,
+        now: T::BlockNumber,
+    ) -> Result<(bool, u64), DispatchError> {
+        let rent_duration = order.requirement.rent_duration;
+        let rent_blocks = rent_duration
+            .checked_mul(600)
+            .and_then(|x| TryInto::<T::BlockNumber>::try_into(x).ok())
+            .ok_or(Error::<T>::InvalidRentDuration)?;
+
+        let (resource_index, read) =
+            T::OrderInterface::match_resource(&order.requirement, now + rent_blocks);
+        let resource_index = match resource_index {
+            Some(x) => x,
+            None => return Ok((false, read)),
+        };
+        let resource_info = match T::OrderInterface::get_computing_resource_info(resource_index) {
+            Some(x) => x,
+            None => return Ok((false, read)),
+        };
+
+        // calculate the order price: unit price (per hour) * rental duration (hours)
+        let price = resource_info
+            .rental_info
+            .rent_unit_price
+            .saturating_mul(rent_duration as u128);
+        // refund what the resource costs less than the requirement allows
+        Self::return_rent(
+            &order.tenant_info.account_id,
+            order.requirement.max_cost().saturating_sub(price),
+        )?;
+
+        Self::do_create_order(
+            order.index,
+            order.tenant_info.clone(),
+            resource_index,
+            resource_info,
+            rent_duration,
+            price,
+            now,
+            None,
+        );
+        Ok((true, read))
     }
-}
 
-impl<T: Config> Pallet<T> {
     // associate user and protocol number
     pub fn do_insert_user_agreements(who: T::AccountId, agreement_count: u64) {
         // detects the existence of a user s protocol
@@ -816,28 +2075,36 @@ impl<T: Config> Pallet<T> {
         }
     }
 
-    // Associate the block number with the protocol number
-    pub fn do_insert_block_with_agreement(
-        end: T::BlockNumber,
-        agreement_index: u64,
-    ) -> DispatchResult {
-        if BlockWithAgreement::<T>::contains_key(end) {
-            // the maximum number of protocols in a block is 2000
-            ensure!(
-                BlockWithAgreement::<T>::get(end).len() > 2000,
-                Error::<T>::ExceedsMaximumQuantity
-            );
+    // associate the block number with the agreement that expires in it.
+    // a block holds at most 2000 agreements, the rest spill into the following blocks
+    pub fn do_insert_block_with_agreement(end: T::BlockNumber, agreement_index: u64) {
+        let mut block = end;
+        while BlockWithAgreement::<T>::get(block).len() >= 2000 {
+            block = block.saturating_add(One::one());
+        }
 
-            BlockWithAgreement::<T>::mutate(end, |vec| {
-                vec.push(agreement_index);
-            });
-        } else {
-            let mut vec = Vec::new();
+        BlockWithAgreement::<T>::mutate(block, |vec| {
             vec.push(agreement_index);
-            BlockWithAgreement::<T>::insert(end, vec);
+        });
+    }
+
+    // associate the block number with the compute request settled in it.
+    // a full block passes the request on to the following one, return the weight of the search
+    pub fn do_insert_block_with_compute_request(
+        settlement: T::BlockNumber,
+        request_index: u64,
+    ) -> Weight {
+        let mut block = settlement;
+        let mut weight = T::DbWeight::get().reads_writes(1, 1);
+        while BlockWithComputeRequest::<T>::get(block).len() as u32
+            >= T::MaxComputeRequestsPerBlock::get()
+        {
+            block = block.saturating_add(One::one());
+            weight = weight.saturating_add(T::DbWeight::get().reads(1));
         }
 
-        Ok(())
+        BlockWithComputeRequest::<T>::mutate(block, |vec| vec.push(request_index));
+        weight
     }
 
     // associate user and order number
@@ -853,6 +2120,66 @@ impl<T: Config> Pallet<T> {
 
     // delete agreement
     pub fn delete_agreement(agreement_index: u64, provider: T::AccountId, user: T::AccountId) {
+        Self::close_agreement(agreement_index, provider, user);
+        // delete agreement
+        RentalAgreements::<T>::remove(agreement_index);
+        AgreementCluster::<T>::remove(agreement_index);
+        Self::clear_agreement_data(agreement_index);
+    }
+
+    // drop the data only a running agreement needs, the finished agreement itself is kept
+    fn clear_agreement_data(agreement_index: u64) {
+        LivenessProofs::<T>::remove(agreement_index);
+        AgreementManifests::<T>::remove(agreement_index);
+        AccessInfos::<T>::remove(agreement_index);
+    }
+
+    // agreements of the user with the cluster each one belongs to,
+    // served by the hamster_userClusterAgreements rpc
+    pub fn user_cluster_agreements(who: T::AccountId) -> Vec<(u64, Option<u64>)> {
+        UserAgreements::<T>::get(who)
+            .into_iter()
+            .map(|x| (x, AgreementCluster::<T>::get(x)))
+            .collect()
+    }
+
+    // check the proof is signed by the peer key over a recent block after the last proof.
+    // the nonce is the hash of that block, so the proof cannot be made in advance
+    fn check_liveness_proof(
+        agreement_index: u64,
+        peer_id: &PeerId,
+        proof: &LivenessProof<T::BlockNumber>,
+    ) -> DispatchResult {
+        ensure!(
+            proof.block < <frame_system::Pallet<T>>::block_number(),
+            Error::<T>::StaleLivenessProof
+        );
+        if let Some(last) = LivenessProofs::<T>::get(agreement_index) {
+            ensure!(proof.block > last, Error::<T>::StaleLivenessProof);
+        }
+        // the hashes of the blocks older than BlockHashCount are pruned
+        let nonce = <frame_system::Pallet<T>>::block_hash(proof.block);
+        ensure!(nonce != Default::default(), Error::<T>::StaleLivenessProof);
+
+        ensure!(
+            peer_id.ed25519_public().is_some(),
+            Error::<T>::PeerKeyUnavailable
+        );
+        let payload = LivenessPayload {
+            index: agreement_index,
+            block: proof.block,
+            nonce,
+        };
+        ensure!(
+            peer_id.verify(&payload, &proof.signature),
+            Error::<T>::InvalidLivenessProof
+        );
+
+        Ok(())
+    }
+
+    // remove the agreement from the user and provider lists and unlock the user staking
+    pub fn close_agreement(agreement_index: u64, provider: T::AccountId, user: T::AccountId) {
         let new_vec = UserAgreements::<T>::get(user.clone())
             .into_iter()
             .filter(|x| if x == &agreement_index { false } else { true })
@@ -871,8 +2198,6 @@ impl<T: Config> Pallet<T> {
             *vec = new_vec;
         });
 
-        // delete agreement
-        RentalAgreements::<T>::remove(agreement_index);
         // unlock the user staking
         T::MarketInterface::change_stake_amount(
             user.clone(),
@@ -882,6 +2207,18 @@ impl<T: Config> Pallet<T> {
         );
     }
 
+    // the rented resource can be rented again, a draining resource is offlined
+    pub fn release_resource(resource_index: u64) {
+        if let Some(mut resource) = T::OrderInterface::get_computing_resource_info(resource_index) {
+            if resource.status == ResourceStatus::Inuse {
+                resource.update_status(ResourceStatus::Unused);
+                T::OrderInterface::update_computing_resource(resource_index, resource);
+            } else if resource.status == ResourceStatus::Draining {
+                T::OrderInterface::offline_computing_resource(resource_index);
+            }
+        }
+    }
+
     // delete the protocol corresponding to the block
     pub fn delete_block_with_agreement(agreement_index: u64, end: T::BlockNumber) {
         // Remove the corresponding protocol number from the original block
@@ -900,104 +2237,512 @@ impl<T: Config> Pallet<T> {
         }
     }
 
+    // the block at which the agreement is punished if no heartbeat is reported
+    pub fn heartbeat_deadline(calculation: T::BlockNumber) -> T::BlockNumber {
+        calculation + T::HealthCheckInterval::get() + One::one()
+    }
+
+    // Associate the heartbeat deadline with the protocol number
+    pub fn do_insert_heartbeat_deadline(agreement_index: u64, calculation: T::BlockNumber) {
+        HeartbeatDeadlines::<T>::mutate(Self::heartbeat_deadline(calculation), |vec| {
+            vec.push(agreement_index)
+        });
+    }
+
+    // delete the protocol from its heartbeat deadline
+    pub fn delete_heartbeat_deadline(agreement_index: u64, calculation: T::BlockNumber) {
+        let deadline = Self::heartbeat_deadline(calculation);
+        let new_vec = HeartbeatDeadlines::<T>::get(deadline)
+            .into_iter()
+            .filter(|x| x != &agreement_index)
+            .collect::<Vec<u64>>();
+
+        if !new_vec.is_empty() {
+            HeartbeatDeadlines::<T>::insert(deadline, new_vec);
+        } else {
+            HeartbeatDeadlines::<T>::remove(deadline);
+        }
+    }
+
     // health examination
-    pub fn do_health_check(now: T::BlockNumber) -> DispatchResult {
-        // get a list of protocols
-        let agreements = RentalAgreements::<T>::iter();
-
-        for (i, mut agreement) in agreements {
-            if agreement.status == AgreementStatus::Using {
-                // get resource number
-                let resource_index = agreement.resource_index;
-                // get resource information
-                let mut resource =
-                    match T::OrderInterface::get_computing_resource_info(resource_index) {
-                        Some(x) => x,
-                        None => Err(Error::<T>::ResourceNotExist)?,
-                    };
-
-                // get the interval from the last report
-                let duration = now - agreement.calculation;
-
-                // check whether the protocol reports a health check
-                if duration > T::HealthCheckInterval::get() {
-                    // number of resource failures+1
-                    resource.rental_statistics.add_fault_count();
-                    // resource set to unused
-                    resource.update_status(ResourceStatus::Offline);
-                    // protocol is set to penalized
-                    agreement.change_status(AgreementStatus::Punished);
-
-                    // Delete the protocol number in the corresponding block
-                    Self::delete_block_with_agreement(i, agreement.end.clone());
-
-                    // compute the penalty
-                    let base_staking = T::MarketInterface::provider_staking_fee();
-                    let penalty = resource.config.cpu.saturating_mul(base_staking as u64)
-                        + resource.config.memory.saturating_mul(base_staking as u64);
-
-                    ensure!(
-                        T::MarketInterface::change_stake_amount(
-                            resource.account_id.clone(),
-                            ChangeAmountType::Penalty,
-                            penalty as u128,
-                            MarketUserStatus::Provider,
-                        ),
-                        Error::<T>::PenaltyAmountFailed,
-                    );
+    // only the agreements whose heartbeat deadline has been reached are checked,
+    // the ones over the per block limit are carried over to the next block
+    pub fn do_health_check(now: T::BlockNumber) -> Weight {
+        // the agreements left over from the previous blocks go first
+        let mut agreements = PendingHealthChecks::<T>::take();
+        agreements.append(&mut HeartbeatDeadlines::<T>::take(now));
+        let mut weight = T::DbWeight::get().reads_writes(2, 2);
+
+        // carry the rest over to the next block
+        let max = T::MaxHealthChecksPerBlock::get() as usize;
+        if agreements.len() > max {
+            PendingHealthChecks::<T>::put(agreements.split_off(max));
+            weight = weight.saturating_add(T::DbWeight::get().writes(1));
+        }
 
-                    ensure!(
-                        T::MarketInterface::change_stake_amount(
-                            agreement.tenant_info.account_id.clone(),
-                            ChangeAmountType::Unlock,
-                            T::MarketInterface::client_staking_fee(),
-                            MarketUserStatus::Client,
-                        ),
-                        Error::<T>::UnlockAmountFailed,
-                    );
+        for i in agreements {
+            weight = weight.saturating_add(T::DbWeight::get().reads(1));
+            let agreement = match RentalAgreements::<T>::get(i) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            // check whether the protocol reports a health check
+            if agreement.status != AgreementStatus::Using
+                || now - agreement.calculation <= T::HealthCheckInterval::get()
+            {
+                continue;
+            }
+
+            if Self::punish_agreement(i, agreement).is_ok() {
+                Self::deposit_event(Event::PenaltyAgreementExcutionSuccess(i));
+            }
+            // resource, staking and balance changes of the punishment
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(6, 8));
+        }
+
+        weight
+    }
 
+    // punish the agreement that missed its heartbeat
+    #[transactional]
+    pub fn punish_agreement(
+        i: u64,
+        mut agreement: RentalAgreement<T::AccountId, T::BlockNumber>,
+    ) -> DispatchResult {
+        // get resource number
+        let resource_index = agreement.resource_index;
+        // get resource information
+        let mut resource = match T::OrderInterface::get_computing_resource_info(resource_index) {
+            Some(x) => x,
+            None => Err(Error::<T>::ResourceNotExist)?,
+        };
+
+        // number of resource failures+1, the fault lasts from the last heartbeat (rounded up to hours)
+        let fault_blocks = T::BlockNumberToNumber::convert(
+            <frame_system::Pallet<T>>::block_number().saturating_sub(agreement.calculation),
+        );
+        let fault_hours = ((fault_blocks + 599) / 600) as u32;
+        resource.rental_statistics.add_fault_count();
+        resource.rental_statistics.add_fault_duration(fault_hours);
+        // resource set to unused
+        resource.update_status(ResourceStatus::Offline);
+        // protocol is set to penalized, the provider forfeits the unreleased rent
+        let refund = agreement.punish();
+        // return the unreleased rent to the tenant
+        Self::refund_rent(i, &agreement.tenant_info.account_id, refund)?;
+
+        // Delete the protocol number in the corresponding block
+        Self::delete_block_with_agreement(i, agreement.end.clone());
+
+        // the penalty is the amount staked for the resource
+        let penalty = T::OrderInterface::get_resource_staked_amount(resource_index);
+
+        ensure!(
+            T::MarketInterface::change_stake_amount(
+                resource.account_id.clone(),
+                ChangeAmountType::Penalty,
+                penalty,
+                MarketUserStatus::Provider,
+            ),
+            Error::<T>::PenaltyAmountFailed,
+        );
+
+        ensure!(
+            T::MarketInterface::change_stake_amount(
+                agreement.tenant_info.account_id.clone(),
+                ChangeAmountType::Unlock,
+                T::MarketInterface::client_staking_fee(),
+                MarketUserStatus::Client,
+            ),
+            Error::<T>::UnlockAmountFailed,
+        );
 
-                    // save the agreement
-                    RentalAgreements::<T>::insert(i, agreement);
-                    // save resources
-                    T::OrderInterface::update_computing_resource(resource_index, resource);
+        // save the agreement
+        RentalAgreements::<T>::insert(i, agreement);
+        Self::clear_agreement_data(i);
+        // save resources
+        T::OrderInterface::update_computing_resource(resource_index, resource);
+        T::OrderInterface::update_reputation(resource_index, ReputationEvent::Faulted(fault_hours));
+
+        Ok(())
+    }
 
-                    Self::deposit_event(Event::PenaltyAgreementExcutionSuccess(i));
+    // cancel a pending order, return the price to the tenant and release the locked resource
+    pub fn do_cancel_order(
+        order_index: u64,
+        order: &mut ResourceOrder<T::AccountId, T::BlockNumber>,
+    ) -> DispatchResult {
+        // cancel order
+        order.cancel_order();
+        // get back the amount
+        Self::return_rent(&order.tenant_info.account_id, order.price)?;
+
+        // a new order holds the resource in the locked state
+        if !order.clone().is_renew_order() {
+            if let Some(mut resource) =
+                T::OrderInterface::get_computing_resource_info(order.resource_index)
+            {
+                if resource.status == ResourceStatus::Locked {
+                    // change the resource state to unused
+                    resource.update_status(ResourceStatus::Unused);
+                    // save resource state
+                    T::OrderInterface::update_computing_resource(resource.index, resource);
                 }
             }
         }
 
+        // save order
+        ResourceOrders::<T>::insert(order_index, order.clone());
+        OrderManifests::<T>::remove(order_index);
         Ok(())
     }
 
+    // cancel the pending orders that have not been executed in time
+    pub fn order_check(now: T::BlockNumber) -> Weight {
+        let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+        for i in BlockWithOrder::<T>::take(now) {
+            weight = weight.saturating_add(T::DbWeight::get().reads(1));
+            let mut order = match ResourceOrders::<T>::get(i) {
+                Some(x) => x,
+                None => continue,
+            };
+            // the order has been executed or canceled
+            if order.status != OrderStatus::Pending {
+                continue;
+            }
+
+            // refund, resource and order changes
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(3, 4));
+
+            if Self::do_cancel_order(i, &mut order).is_ok() {
+                Self::deposit_event(Event::OrderTimeout(
+                    order.tenant_info.account_id.clone(),
+                    i,
+                    order.price,
+                ));
+                // a cluster is rented whole, it fails with any of its orders
+                if let Some(cluster_index) = OrderCluster::<T>::get(i) {
+                    weight = weight.saturating_add(
+                        Self::fail_cluster(cluster_index).unwrap_or_else(|_| Zero::zero()),
+                    );
+                }
+            }
+        }
+
+        weight
+    }
+
+    // terminate the agreements in use and cancel the pending orders of the cluster,
+    // the tenant pays for the blocks served without the early termination fee
+    #[transactional]
+    pub fn fail_cluster(cluster_index: u64) -> Result<Weight, DispatchError> {
+        let mut cluster = match ClusterOrders::<T>::get(cluster_index) {
+            Some(x) => x,
+            None => return Ok(T::DbWeight::get().reads(1)),
+        };
+        if cluster.status == ClusterStatus::Failed {
+            return Ok(T::DbWeight::get().reads(1));
+        }
+
+        // agreement, resource, payment, staking and index changes
+        let members = (cluster.agreements.len() + cluster.orders.len()) as Weight;
+        let weight = T::DbWeight::get()
+            .reads_writes(6, 10)
+            .saturating_mul(members)
+            .saturating_add(T::DbWeight::get().reads_writes(1, 1));
+
+        for agreement_index in cluster.agreements.iter() {
+            if RentalAgreements::<T>::get(agreement_index).map_or(false, |agreement| {
+                agreement.status == AgreementStatus::Using
+            }) {
+                Self::do_terminate_agreement(
+                    cluster.tenant.clone(),
+                    *agreement_index,
+                    Perbill::zero(),
+                )?;
+            }
+        }
+        for order_index in cluster.orders.iter() {
+            if let Some(mut order) = ResourceOrders::<T>::get(order_index) {
+                if order.status == OrderStatus::Pending {
+                    Self::do_cancel_order(*order_index, &mut order)?;
+                }
+            }
+        }
+
+        cluster.status = ClusterStatus::Failed;
+        ClusterOrders::<T>::insert(cluster_index, cluster.clone());
+
+        Self::deposit_event(Event::ClusterFailed(cluster.tenant, cluster_index));
+        Ok(weight)
+    }
+
+    // match at most MaxWaitingOrderMatches waiting orders, cancel the ones that waited too long.
+    // the orders matched without success go to the back of the queue
+    pub fn waiting_order_check(now: T::BlockNumber) -> Weight {
+        let queue = WaitingOrderQueue::<T>::get();
+        let mut weight = T::DbWeight::get().reads(1);
+        if queue.is_empty() {
+            return weight;
+        }
+
+        let mut budget = T::MaxWaitingOrderMatches::get();
+        let mut remaining = Vec::new();
+        let mut unmatched = Vec::new();
+        for i in queue {
+            weight = weight.saturating_add(T::DbWeight::get().reads(1));
+            let order = match WaitingOrders::<T>::get(i) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if now > order.deadline {
+                // refund and order changes
+                weight = weight.saturating_add(T::DbWeight::get().reads_writes(2, 3));
+
+                let refund = order.requirement.max_cost();
+                if Self::return_rent(&order.tenant_info.account_id, refund).is_err() {
+                    remaining.push(i);
+                    continue;
+                }
+                WaitingOrders::<T>::remove(i);
+                Self::deposit_event(Event::WaitingOrderExpired(
+                    order.tenant_info.account_id,
+                    i,
+                    refund,
+                ));
+                continue;
+            }
+
+            if budget == 0 {
+                remaining.push(i);
+                continue;
+            }
+            budget -= 1;
+
+            // price index, resource, refund and order changes
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(4, 6));
+            match Self::match_waiting_order(&order, now) {
+                Ok((true, read)) => {
+                    weight = weight.saturating_add(T::DbWeight::get().reads(read));
+                    WaitingOrders::<T>::remove(i);
+                }
+                Ok((false, read)) => {
+                    weight = weight.saturating_add(T::DbWeight::get().reads(read));
+                    unmatched.push(i);
+                }
+                Err(_) => unmatched.push(i),
+            }
+        }
+
+        remaining.append(&mut unmatched);
+        WaitingOrderQueue::<T>::put(remaining);
+        weight.saturating_add(T::DbWeight::get().writes(1))
+    }
+
+    // create the order of the compute request with the bid and execute it
+    #[transactional]
+    pub fn do_settle_compute_request(
+        request: &ComputeRequest<T::AccountId, T::BlockNumber>,
+        bid: &Bid<T::AccountId, T::BlockNumber>,
+        now: T::BlockNumber,
+    ) -> Result<u64, DispatchError> {
+        // get resource information
+        let resource_info = match T::OrderInterface::get_computing_resource_info(bid.resource_index)
+        {
+            Some(x) => x,
+            None => Err(Error::<T>::ResourceNotExist)?,
+        };
+        // the resource is still locked by the bid
+        ensure!(
+            resource_info.status == ResourceStatus::Locked,
+            Error::<T>::ResourceHasBeenRented
+        );
+
+        let rent_duration = request.requirement.rent_duration;
+        // calculate the order price: bid unit price * rental duration (hours)
+        let price = bid.unit_price.saturating_mul(rent_duration as u128);
+        // refund what the bid costs less than the requirement allows
+        Self::return_rent(
+            &request.tenant_info.account_id,
+            request.requirement.max_cost().saturating_sub(price),
+        )?;
+
+        // get order length
+        let order_index = OrderIndex::<T>::get();
+        // order length+1
+        OrderIndex::<T>::put(order_index + 1);
+
+        Self::do_create_order(
+            order_index,
+            request.tenant_info.clone(),
+            bid.resource_index,
+            resource_info,
+            rent_duration,
+            price,
+            now,
+            None,
+        );
+        Self::do_order_exec(bid.bidder.clone(), order_index)?;
+
+        Ok(order_index)
+    }
+
+    // settle the compute requests whose bidding has ended, the lowest valid bid wins
+    pub fn compute_request_check(now: T::BlockNumber) -> Weight {
+        let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+        for i in BlockWithComputeRequest::<T>::take(now) {
+            weight = weight.saturating_add(T::DbWeight::get().reads(2));
+            let mut request = match ComputeRequests::<T>::get(i) {
+                Some(x) => x,
+                None => continue,
+            };
+            if request.status != ComputeRequestStatus::Bidding {
+                continue;
+            }
+            let mut bids = RequestBids::<T>::get(i);
+
+            // the lowest bid first, the earliest one first at the same price
+            let mut ranking: Vec<usize> = (0..bids.len()).collect();
+            ranking.sort_by_key(|x| bids[*x].unit_price);
+
+            let mut winner = None;
+            for x in ranking {
+                // order, agreement, resource, staking and refund changes
+                weight = weight.saturating_add(T::DbWeight::get().reads_writes(6, 8));
+                if let Ok(order_index) = Self::do_settle_compute_request(&request, &bids[x], now) {
+                    winner = Some((x, order_index));
+                    break;
+                }
+            }
+
+            // release the resources of the other bids
+            for (x, bid) in bids.iter_mut().enumerate() {
+                if winner.map_or(false, |(won, _)| won == x) {
+                    bid.status = BidStatus::Won;
+                    continue;
+                }
+                bid.status = BidStatus::Lost;
+
+                weight = weight.saturating_add(T::DbWeight::get().reads_writes(1, 1));
+                if let Some(mut resource) =
+                    T::OrderInterface::get_computing_resource_info(bid.resource_index)
+                {
+                    if resource.status == ResourceStatus::Locked {
+                        resource.update_status(ResourceStatus::Unused);
+                        T::OrderInterface::update_computing_resource(bid.resource_index, resource);
+                    }
+                }
+            }
+
+            match winner {
+                Some((x, order_index)) => {
+                    request.settle(order_index);
+                    Self::deposit_event(Event::ComputeRequestSettled(
+                        i,
+                        order_index,
+                        bids[x].resource_index,
+                        bids[x].unit_price,
+                    ));
+                }
+                None => {
+                    let refund = request.requirement.max_cost();
+                    if Self::return_rent(&request.tenant_info.account_id, refund).is_ok() {
+                        request.unfilled();
+                        Self::deposit_event(Event::ComputeRequestUnfilled(
+                            request.tenant_info.account_id.clone(),
+                            i,
+                            refund,
+                        ));
+                    } else {
+                        // try the refund again in the next block with room
+                        weight = weight.saturating_add(Self::do_insert_block_with_compute_request(
+                            now + One::one(),
+                            i,
+                        ));
+                    }
+                }
+            }
+
+            weight = weight.saturating_add(T::DbWeight::get().writes(2));
+            RequestBids::<T>::insert(i, bids);
+            ComputeRequests::<T>::insert(i, request);
+        }
+
+        weight
+    }
+
+    // check for expired free resource trials
+    pub fn apply_order_check(now: T::BlockNumber) -> Weight {
+        let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+        for i in BlockWithApplyOrder::<T>::take(now) {
+            // apply order, resource and apply user changes
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(2, 3));
+            if let Some(mut apply_order) = ApplyOrders::<T>::get(i) {
+                if apply_order.status != OrderStatus::Finished {
+                    continue;
+                }
+                // end the trial and free the resource
+                apply_order.expire();
+                Self::release_resource(apply_order.resource_index);
+                // the user can apply for another trial
+                ApplyUsers::<T>::remove(apply_order.tenant_info.account_id.clone());
+                ApplyOrders::<T>::insert(i, apply_order.clone());
+
+                Self::deposit_event(Event::FreeResourceExpired(i, apply_order.peer_id));
+            }
+        }
+
+        weight
+    }
+
     // check for expired agreements
-    pub fn agreement_check(now: T::BlockNumber) {
+    pub fn agreement_check(now: T::BlockNumber) -> Weight {
         // find if the current block has expired protocols
         let agreements_index = BlockWithAgreement::<T>::get(now);
+        let mut weight = T::DbWeight::get().reads(1);
 
         for i in agreements_index {
+            // agreement, resource, payment, staking and index changes
+            weight = weight.saturating_add(T::DbWeight::get().reads_writes(6, 8));
             // get agreement
-            let agreement_opt =  RentalAgreements::<T>::get(i);
+            let agreement_opt = RentalAgreements::<T>::get(i);
             if agreement_opt.is_none() {
                 continue;
             }
             let mut agreement = agreement_opt.unwrap();
+            // skip the stale entries left by renewals and by agreements spilled to a later block
+            if agreement.status != AgreementStatus::Using || agreement.end > now {
+                continue;
+            }
             // get resource number
             let resource_index = agreement.resource_index;
             // get resource information
-            let resource_opt =  T::OrderInterface::get_computing_resource_info(resource_index);
+            let resource_opt = T::OrderInterface::get_computing_resource_info(resource_index);
             if resource_opt.is_none() {
                 continue;
             }
-            let mut resource = resource_opt.unwrap();
 
-            // set resource to unused
-            resource.update_status(ResourceStatus::Unused);
+            // settle the rent for the rest of the agreement
+            let end = agreement.end;
+            if let Some(amount) = agreement.execution(&end) {
+                // the agreement is kept until the rent is released, retry in the next block
+                if Self::release_rent(i, &agreement.provider, amount).is_err() {
+                    Self::do_insert_block_with_agreement(now + One::one(), i);
+                    continue;
+                }
+            }
+
             // set the agreement as done
             agreement.change_status(AgreementStatus::Finished);
+            T::OrderInterface::update_reputation(resource_index, ReputationEvent::Completed);
 
-            // save resource state
-            T::OrderInterface::update_computing_resource(resource_index, resource);
+            // set resource to unused, or offline it when it is draining
+            Self::release_resource(resource_index);
             // save the agreement
             RentalAgreements::<T>::insert(i, agreement.clone());
 
@@ -1010,6 +2755,8 @@ impl<T: Config> Pallet<T> {
 
             Self::deposit_event(Event::ExpiredResourceStatusUpdatedSuccess(resource_index));
         }
+
+        weight
     }
 }
 
@@ -1021,7 +2768,208 @@ impl<T: Config>
 {
     fn get_rental_agreements() -> Vec<(u64, RentalAgreement<T::AccountId, T::BlockNumber>)> {
         RentalAgreements::<T>::iter()
-            .filter(|x| x.1.status != AgreementStatus::Punished)
+            .filter(|x| x.1.status == AgreementStatus::Using)
             .collect()
     }
+
+    fn transfer_agreements(resource_index: u64, from: T::AccountId, to: T::AccountId) {
+        let (moved, kept): (Vec<u64>, Vec<u64>) = ProviderAgreements::<T>::get(from.clone())
+            .into_iter()
+            .partition(|x| {
+                RentalAgreements::<T>::get(x).map_or(false, |agreement| {
+                    agreement.resource_index == resource_index
+                })
+            });
+
+        for agreement_index in moved.iter() {
+            RentalAgreements::<T>::mutate(agreement_index, |agreement| {
+                if let Some(agreement) = agreement {
+                    agreement.provider = to.clone();
+                }
+            });
+            Self::do_insert_provider_agreements(to.clone(), *agreement_index);
+        }
+        ProviderAgreements::<T>::insert(from, kept);
+    }
+}
+
+// Determine whether we run the storage migration logic
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Releases {
+    V1_0_0,
+    V2_0_0,
+    V3_0_0,
+}
+
+impl Default for Releases {
+    fn default() -> Self {
+        Releases::V1_0_0
+    }
+}
+
+pub mod migrations {
+
+    pub mod v2 {
+        use super::super::*;
+        use sp_core::sp_std::time::Duration;
+
+        /// resource order before the rent was held in the order pool
+        #[derive(Decode)]
+        struct OldResourceOrder<AccountId, BlockNumber> {
+            index: u64,
+            tenant_info: TenantInfo<AccountId>,
+            resource_index: u64,
+            create: BlockNumber,
+            rent_duration: BlockNumber,
+            time: Duration,
+            status: OrderStatus,
+            agreement_index: Option<u64>,
+        }
+
+        /// rental agreement before the rent was held in the order pool
+        #[derive(Decode)]
+        struct OldRentalAgreement<AccountId, BlockNumber> {
+            index: u64,
+            provider: AccountId,
+            tenant_info: TenantInfo<AccountId>,
+            peer_id: Vec<u8>,
+            resource_index: u64,
+            config: ResourceConfigV1,
+            rental_info: ResourceRentalInfo<BlockNumber>,
+            penalty_amount: u128,
+            receive_amount: u128,
+            start: BlockNumber,
+            end: BlockNumber,
+            calculation: BlockNumber,
+            time: Duration,
+            status: AgreementStatus,
+        }
+
+        /// add the escrowed price to orders and agreements (nothing was charged before, so it is zero)
+        /// and index the heartbeat deadline of the agreements in use.
+        /// the agreements are translated straight to the current layout, so this moves to v3.0.0
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+
+            ResourceOrders::<T>::translate::<OldResourceOrder<T::AccountId, T::BlockNumber>, _>(
+                |_, old| {
+                    count += 1;
+                    Some(ResourceOrder {
+                        index: old.index,
+                        tenant_info: old.tenant_info,
+                        price: 0,
+                        resource_index: old.resource_index,
+                        create: old.create,
+                        rent_duration: old.rent_duration,
+                        time: old.time,
+                        status: old.status,
+                        agreement_index: old.agreement_index,
+                    })
+                },
+            );
+
+            RentalAgreements::<T>::translate::<OldRentalAgreement<T::AccountId, T::BlockNumber>, _>(
+                |_, old| {
+                    count += 1;
+                    Some(RentalAgreement {
+                        index: old.index,
+                        provider: old.provider,
+                        tenant_info: old.tenant_info,
+                        peer_id: PeerId::new_unchecked(old.peer_id),
+                        resource_index: old.resource_index,
+                        config: old.config.into(),
+                        rental_info: old.rental_info,
+                        price: 0,
+                        penalty_amount: old.penalty_amount,
+                        receive_amount: old.receive_amount,
+                        start: old.start,
+                        end: old.end,
+                        calculation: old.calculation,
+                        time: old.time,
+                        status: old.status,
+                    })
+                },
+            );
+
+            // index the heartbeat deadlines, the overdue ones are checked from the next block
+            let now = <frame_system::Pallet<T>>::block_number();
+            let mut overdue = Vec::new();
+            for (index, agreement) in RentalAgreements::<T>::iter() {
+                count += 1;
+                if agreement.status != AgreementStatus::Using {
+                    continue;
+                }
+                if Pallet::<T>::heartbeat_deadline(agreement.calculation) <= now {
+                    overdue.push(index);
+                } else {
+                    Pallet::<T>::do_insert_heartbeat_deadline(index, agreement.calculation);
+                }
+            }
+            PendingHealthChecks::<T>::put(overdue);
+
+            // the rent is now held in the order pool
+            Pallet::<T>::endow_order_pool();
+
+            StorageVersion::<T>::put(Releases::V3_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, count + 2)
+        }
+    }
+
+    pub mod v3 {
+        use super::super::*;
+        use sp_core::sp_std::time::Duration;
+
+        /// rental agreement before the resource config snapshot was extended
+        #[derive(Decode)]
+        struct OldRentalAgreement<AccountId, BlockNumber> {
+            index: u64,
+            provider: AccountId,
+            tenant_info: TenantInfo<AccountId>,
+            peer_id: Vec<u8>,
+            resource_index: u64,
+            config: ResourceConfigV1,
+            rental_info: ResourceRentalInfo<BlockNumber>,
+            price: u128,
+            penalty_amount: u128,
+            receive_amount: u128,
+            start: BlockNumber,
+            end: BlockNumber,
+            calculation: BlockNumber,
+            time: Duration,
+            status: AgreementStatus,
+        }
+
+        /// extend the resource config snapshot of the agreements, the new fields are unknown
+        pub fn migrate<T: Config>() -> Weight {
+            let mut count: u64 = 0;
+
+            RentalAgreements::<T>::translate::<OldRentalAgreement<T::AccountId, T::BlockNumber>, _>(
+                |_, old| {
+                    count += 1;
+                    Some(RentalAgreement {
+                        index: old.index,
+                        provider: old.provider,
+                        tenant_info: old.tenant_info,
+                        peer_id: PeerId::new_unchecked(old.peer_id),
+                        resource_index: old.resource_index,
+                        config: old.config.into(),
+                        rental_info: old.rental_info,
+                        price: old.price,
+                        penalty_amount: old.penalty_amount,
+                        receive_amount: old.receive_amount,
+                        start: old.start,
+                        end: old.end,
+                        calculation: old.calculation,
+                        time: old.time,
+                        status: old.status,
+                    })
+                },
+            );
+
+            StorageVersion::<T>::put(Releases::V3_0_0);
+
+            T::DbWeight::get().reads_writes(count + 1, count + 1)
+        }
+    }
 }
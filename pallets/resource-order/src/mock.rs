@@ -30,8 +30,37 @@ pub const HOURS: BlockNumber = MINUTES * 60;
 parameter_types! {
     // polling interval
     pub const ResourceInterval: BlockNumber = 3 * HOURS;
+    // maximum number of resources expired in a block
+    pub const MaxExpirationsPerBlock: u32 = 100;
+    // the reputation decays a tenth of the way to neutral every period
+    pub const ReputationDecayPeriod: BlockNumber = HOURS;
+    pub const MaxBatchResources: u32 = 10;
+    pub const ProbationPeriod: BlockNumber = HOURS;
+    pub const ProbationHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // the auto extension is attempted this long before the end of rent
+    pub const AutoExtendWindow: BlockNumber = 10 * MINUTES;
+    pub const MaxStatusHistory: u32 = 10;
     // health check interval
     pub const HealthCheckInterval: BlockNumber = 10 * MINUTES;
+    // maximum duration of a free resource trial (hours)
+    pub const MaxFreeResourceDuration: u32 = 24;
+    // maximum number of agreements checked for missed heartbeats in one block
+    pub const MaxHealthChecksPerBlock: u32 = 100;
+    // share of the remaining rent paid to the provider on early termination
+    pub const EarlyTerminationFee: sp_runtime::Perbill = sp_runtime::Perbill::from_percent(10);
+    // waiting time of a pending order
+    pub const OrderWaitingTime: BlockNumber = 30 * MINUTES;
+    // how long an order waits for a resource meeting its requirement
+    pub const OrderMatchWaitingTime: BlockNumber = HOURS;
+    pub const MaxWaitingOrders: u32 = 2;
+    // waiting orders matched in a block
+    pub const MaxWaitingOrderMatches: u32 = 1;
+    // longest bidding on a compute request
+    pub const MaxBiddingPeriod: BlockNumber = HOURS;
+    pub const MaxBidsPerRequest: u32 = 3;
+    pub const MaxComputeRequestsPerBlock: u32 = 2;
+    // maximum number of resources in a cluster order
+    pub const MaxClusterSize: u32 = 3;
 }
 
 // Configure a mock runtime to test the pallet.
@@ -117,6 +146,15 @@ impl pallet_provider::Config for Test {
     type NumberToBalance = ConvertInto;
     type ResourceInterval = ResourceInterval;
     type MarketInterface = Market;
+    type MaxExpirationsPerBlock = MaxExpirationsPerBlock;
+    type ReputationDecayPeriod = ReputationDecayPeriod;
+    type MaxBatchResources = MaxBatchResources;
+    type ProbationPeriod = ProbationPeriod;
+    type ProbationHeartbeatInterval = ProbationHeartbeatInterval;
+    type MaxStatusHistory = MaxStatusHistory;
+    type AutoExtendWindow = AutoExtendWindow;
+    type ResourceOrderInterface = ResourceOrder;
+    type OperatorInterface = Market;
 }
 
 impl pallet_resource_order::Config for Test {
@@ -128,7 +166,20 @@ impl pallet_resource_order::Config for Test {
     type NumberToBalance = ConvertInto;
     type BalanceToNumber = ConvertInto;
     type HealthCheckInterval = HealthCheckInterval;
+    type OrderWaitingTime = OrderWaitingTime;
+    type MaxHealthChecksPerBlock = MaxHealthChecksPerBlock;
+    type EarlyTerminationFee = EarlyTerminationFee;
+    type OperatorInterface = Market;
     type UnixTime = Timestamp;
+    type FreeResourceOrigin = frame_system::EnsureRoot<u64>;
+    type MaxFreeResourceDuration = MaxFreeResourceDuration;
+    type OrderMatchWaitingTime = OrderMatchWaitingTime;
+    type MaxWaitingOrders = MaxWaitingOrders;
+    type MaxWaitingOrderMatches = MaxWaitingOrderMatches;
+    type MaxBiddingPeriod = MaxBiddingPeriod;
+    type MaxBidsPerRequest = MaxBidsPerRequest;
+    type MaxComputeRequestsPerBlock = MaxComputeRequestsPerBlock;
+    type MaxClusterSize = MaxClusterSize;
 }
 
 impl pallet_market::Config for Test {
@@ -160,6 +211,7 @@ impl pallet_gateway::Config for Test {
     type GatewayNodeHeartbeatInterval = GatewayNodeHeartbeatInterval;
 
     type MarketInterface = Market;
+    type OperatorInterface = Market;
 }
 
 impl pallet_chunkcycle::Config for Test {
@@ -169,6 +221,7 @@ impl pallet_chunkcycle::Config for Test {
     type NumberToBalance = ConvertInto;
     type BalanceToNumber = ConvertInto;
     type MarketInterface = Market;
+    type ProviderInterface = Provider;
 }
 
 // Build genesis storage according to the mock runtime.
@@ -213,7 +266,7 @@ pub fn new_test_pub() -> sp_io::TestExternalities {
 
     let resource_index: u64 = 1;
 
-    let peer_id = "abcd";
+    let peer_id = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e";
     let cpu: u64 = 1;
     let memory: u64 = 1;
     let system = "ubuntu";
@@ -226,6 +279,12 @@ pub fn new_test_pub() -> sp_io::TestExternalities {
         memory.clone(),
         system.as_bytes().to_vec(),
         cpu_model.as_bytes().to_vec(),
+        100,
+        StorageType::Ssd,
+        100,
+        CpuArch::X86_64,
+        "CN-SH".as_bytes().to_vec(),
+        None,
     );
     let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
     let resource_rental_info = ResourceRentalInfo::new(
@@ -237,7 +296,7 @@ pub fn new_test_pub() -> sp_io::TestExternalities {
     let computing_resource = ComputingResource::new(
         resource_index,
         1,
-        peer_id.as_bytes().to_vec(),
+        PeerId::parse(peer_id.as_bytes()).unwrap(),
         resource_config.clone(),
         statistics.clone(),
         resource_rental_info.clone(),
@@ -247,7 +306,7 @@ pub fn new_test_pub() -> sp_io::TestExternalities {
     let computing_resource_used = ComputingResource::new(
         resource_index,
         1,
-        peer_id.as_bytes().to_vec(),
+        PeerId::parse(peer_id.as_bytes()).unwrap(),
         resource_config,
         statistics,
         resource_rental_info,
@@ -314,6 +373,7 @@ pub fn new_test_order() -> sp_io::TestExternalities {
             public_key: Bytes(vec![1, 2, 3]),
         },
         1,
+        1,
         0,
         100,
         Default::default(),
@@ -334,7 +394,7 @@ pub fn new_test_order() -> sp_io::TestExternalities {
     .unwrap();
 
     let resource_index: u64 = 1;
-    let peer_id = "abcd";
+    let peer_id = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e";
     let cpu: u64 = 1;
     let memory: u64 = 1;
     let system = "ubuntu";
@@ -347,6 +407,12 @@ pub fn new_test_order() -> sp_io::TestExternalities {
         memory.clone(),
         system.as_bytes().to_vec(),
         cpu_model.as_bytes().to_vec(),
+        100,
+        StorageType::Ssd,
+        100,
+        CpuArch::X86_64,
+        "CN-SH".as_bytes().to_vec(),
+        None,
     );
     let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
     let resource_rental_info = ResourceRentalInfo::new(
@@ -358,7 +424,7 @@ pub fn new_test_order() -> sp_io::TestExternalities {
     let computing_resource = ComputingResource::new(
         resource_index,
         2,
-        peer_id.as_bytes().to_vec(),
+        PeerId::parse(peer_id.as_bytes()).unwrap(),
         resource_config.clone(),
         statistics.clone(),
         resource_rental_info.clone(),
@@ -427,6 +493,7 @@ pub fn new_test_agreement() -> sp_io::TestExternalities {
             public_key: Bytes(vec![1, 2, 3]),
         },
         1,
+        1,
         0,
         100,
         Default::default(),
@@ -442,19 +509,26 @@ pub fn new_test_agreement() -> sp_io::TestExternalities {
             account_id: 1,
             public_key: Bytes(vec![1, 2, 3]),
         },
-        vec![],
+        PeerId::default(),
         1,
         ResourceConfig {
             cpu: 0,
             memory: 0,
             system: vec![],
             cpu_model: vec![],
+            storage: 0,
+            storage_type: StorageType::Unknown,
+            bandwidth: 0,
+            arch: CpuArch::Unknown,
+            region: vec![],
+            accelerator: None,
         },
         ResourceRentalInfo {
             rent_unit_price: 1,
             rent_duration: 100,
             end_of_rent: 101,
         },
+        100,
         0,
         0,
         1,
@@ -477,7 +551,7 @@ pub fn new_test_agreement() -> sp_io::TestExternalities {
 
     let resource_index: u64 = 1;
 
-    let peer_id = "abcd";
+    let peer_id = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e";
     let cpu: u64 = 1;
     let memory: u64 = 1;
     let system = "ubuntu";
@@ -490,6 +564,12 @@ pub fn new_test_agreement() -> sp_io::TestExternalities {
         memory.clone(),
         system.as_bytes().to_vec(),
         cpu_model.as_bytes().to_vec(),
+        100,
+        StorageType::Ssd,
+        100,
+        CpuArch::X86_64,
+        "CN-SH".as_bytes().to_vec(),
+        None,
     );
     let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
     let resource_rental_info = ResourceRentalInfo::new(
@@ -501,11 +581,11 @@ pub fn new_test_agreement() -> sp_io::TestExternalities {
     let computing_resource = ComputingResource::new(
         resource_index,
         2,
-        peer_id.as_bytes().to_vec(),
+        PeerId::parse(peer_id.as_bytes()).unwrap(),
         resource_config.clone(),
         statistics.clone(),
         resource_rental_info.clone(),
-        ResourceStatus::Locked,
+        ResourceStatus::Inuse,
     );
 
     pallet_provider::GenesisConfig::<Test> {
@@ -580,6 +660,7 @@ pub fn new_test_health_check() -> sp_io::TestExternalities {
             public_key: Bytes(vec![1, 2, 3]),
         },
         1,
+        1,
         0,
         100,
         Default::default(),
@@ -595,19 +676,26 @@ pub fn new_test_health_check() -> sp_io::TestExternalities {
             account_id: 1,
             public_key: Bytes(vec![1, 2, 3]),
         },
-        vec![],
+        PeerId::default(),
         1,
         ResourceConfig {
             cpu: 0,
             memory: 0,
             system: vec![],
             cpu_model: vec![],
+            storage: 0,
+            storage_type: StorageType::Unknown,
+            bandwidth: 0,
+            arch: CpuArch::Unknown,
+            region: vec![],
+            accelerator: None,
         },
         ResourceRentalInfo {
             rent_unit_price: 1,
             rent_duration: 100,
             end_of_rent: 101,
         },
+        100,
         0,
         0,
         1,
@@ -630,7 +718,7 @@ pub fn new_test_health_check() -> sp_io::TestExternalities {
 
     let resource_index: u64 = 1;
 
-    let peer_id = "abcd";
+    let peer_id = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e";
     let cpu: u64 = 1;
     let memory: u64 = 1;
     let system = "ubuntu";
@@ -643,6 +731,12 @@ pub fn new_test_health_check() -> sp_io::TestExternalities {
         memory.clone(),
         system.as_bytes().to_vec(),
         cpu_model.as_bytes().to_vec(),
+        100,
+        StorageType::Ssd,
+        100,
+        CpuArch::X86_64,
+        "CN-SH".as_bytes().to_vec(),
+        None,
     );
     let statistics = ResourceRentalStatistics::new(0, 0, 0, 0);
     let resource_rental_info = ResourceRentalInfo::new(
@@ -654,7 +748,7 @@ pub fn new_test_health_check() -> sp_io::TestExternalities {
     let computing_resource = ComputingResource::new(
         resource_index,
         2,
-        peer_id.as_bytes().to_vec(),
+        PeerId::parse(peer_id.as_bytes()).unwrap(),
         resource_config.clone(),
         statistics.clone(),
         resource_rental_info.clone(),
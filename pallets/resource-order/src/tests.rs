@@ -1,10 +1,21 @@
 use crate::mock::ResourceOrder;
-use crate::{mock::*, Error};
+use crate::{
+    mock::*, BlockWithComputeRequest, Error, LivenessPayload, LivenessProof, PeerId, Releases,
+    RentalAgreements, ResourceOrders, StorageVersion,
+};
 use alloc::vec;
-use frame_support::{assert_noop, assert_ok};
+use codec::Encode;
+use frame_support::{assert_noop, assert_ok, traits::Currency};
+use pallet_provider::Error as ProviderError;
+use primitives::p_market::OperatorScope;
 use primitives::p_provider::ResourceStatus;
-use primitives::p_resource_order::{AgreementStatus, OrderStatus};
-use sp_core::Bytes;
+use primitives::p_resource_order::{
+    AgreementStatus, BidStatus, ClusterMember, ClusterStatus, ComputeRequestStatus,
+    DeploymentManifest, Manifest, OrderInterface, OrderStatus, ResourceRequirement, TenantInfo,
+};
+use primitives::p_sealed_box;
+use sp_core::{ed25519, Bytes, Pair, H256};
+use sp_std::time::Duration;
 
 #[test]
 fn it_works_for_default_value() {
@@ -41,7 +52,8 @@ fn it_works_for_create_order_info() {
                 Origin::signed(account_id),
                 100,
                 rent_duration,
-                public_key.clone()
+                public_key.clone(),
+                None
             ),
             Error::<Test>::ResourceNotExist
         );
@@ -50,7 +62,8 @@ fn it_works_for_create_order_info() {
                 Origin::signed(account_id),
                 resource_index,
                 10000,
-                public_key.clone()
+                public_key.clone(),
+                None
             ),
             Error::<Test>::ExceedTheRentableTime
         );
@@ -59,7 +72,8 @@ fn it_works_for_create_order_info() {
                 Origin::signed(account_id),
                 2,
                 rent_duration,
-                public_key.clone()
+                public_key.clone(),
+                None
             ),
             Error::<Test>::ResourceHasBeenRented
         );
@@ -68,14 +82,20 @@ fn it_works_for_create_order_info() {
             Origin::signed(account_id),
             resource_index,
             rent_duration,
-            public_key
+            public_key,
+            None
         ));
 
         let resource_status = Provider::resource(resource_index).unwrap().status;
         assert_eq!(resource_status, ResourceStatus::Locked);
 
         assert_eq!(ResourceOrder::user_orders(account_id), vec![0]);
-        assert_eq!(ResourceOrder::order_index(), 1)
+        assert_eq!(ResourceOrder::order_index(), 1);
+
+        // the rent (unit price 1 * 1 hour) is held in the order pool
+        assert_eq!(ResourceOrder::resource_orders(0).unwrap().price, 1);
+        assert_eq!(Balances::free_balance(account_id), 99);
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 1001);
     });
 }
 
@@ -127,35 +147,310 @@ fn it_works_for_order_exec() {
         };
         let total_staking = Market::total_staked();
         assert_eq!(total_staking, market_staking);
+
+        // the executed order can no longer be canceled
+        assert_noop!(
+            ResourceOrder::cancel_order(Origin::signed(1), 0),
+            Error::<Test>::OrderStatusError
+        );
+    });
+}
+
+#[test]
+fn it_works_for_cancel_order() {
+    new_test_order().execute_with(|| {
+        let account_id = 1;
+        let resource_index = 1;
+        let order_index = 0;
+
+        assert_noop!(
+            ResourceOrder::cancel_order(Origin::signed(account_id), 100),
+            Error::<Test>::OrderDoesNotExist
+        );
+        assert_noop!(
+            ResourceOrder::cancel_order(Origin::signed(100), 0),
+            Error::<Test>::OrderNotOwnedByYou
+        );
+
+        assert_ok!(ResourceOrder::cancel_order(Origin::signed(account_id), 0));
+
+        let order_status = ResourceOrder::resource_orders(order_index).unwrap().status;
+        assert_eq!(order_status, OrderStatus::Canceled);
+
+        let resource_status = Provider::resource(resource_index).unwrap().status;
+        assert_eq!(resource_status, ResourceStatus::Unused);
+
+        // the order price is returned to the tenant
+        assert_eq!(Balances::free_balance(account_id), 101);
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 999);
+    });
+}
+
+#[test]
+fn it_works_for_order_timeout() {
+    new_test_pub().execute_with(|| {
+        let account_id = 1;
+        let resource_index = 1;
+
+        assert_ok!(ResourceOrder::create_order_info(
+            Origin::signed(account_id),
+            resource_index,
+            1,
+            Bytes(vec![1, 2, 3]),
+            None
+        ));
+        // the order times out after 30 minutes
+        assert_eq!(ResourceOrder::block_order(1 + 30 * MINUTES), vec![0]);
+
+        System::set_block_number(1 + 30 * MINUTES);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(
+            1 + 30 * MINUTES,
+        );
+
+        let order = ResourceOrder::resource_orders(0).unwrap();
+        assert_eq!(order.status, OrderStatus::Canceled);
+        let resource_status = Provider::resource(resource_index).unwrap().status;
+        assert_eq!(resource_status, ResourceStatus::Unused);
+        assert_eq!(Balances::free_balance(account_id), 100);
+    });
+}
+
+#[test]
+fn it_works_for_order_by_spec() {
+    new_test_pub().execute_with(|| {
+        let ubuntu = "ubuntu".as_bytes().to_vec();
+        // unit price up to 3 for 2 hours, 6 is held until a resource is matched
+        let requirement = ResourceRequirement::new(1, 1, ubuntu.clone(), 3, 2);
+
+        for hours in [0, u32::MAX].iter() {
+            assert_noop!(
+                ResourceOrder::create_order_by_spec(
+                    Origin::signed(1),
+                    ResourceRequirement::new(1, 1, ubuntu.clone(), 3, *hours),
+                    Bytes(vec![1, 2, 3])
+                ),
+                Error::<Test>::InvalidRentDuration
+            );
+        }
+
+        // resource 1 (unit price 1) is the only unused one meeting the requirement
+        assert_ok!(ResourceOrder::create_order_by_spec(
+            Origin::signed(1),
+            requirement.clone(),
+            Bytes(vec![1, 2, 3])
+        ));
+        let order = ResourceOrder::resource_orders(0).unwrap();
+        assert_eq!(order.resource_index, 1);
+        assert_eq!(order.price, 2);
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Locked
+        );
+        // the difference with the price of the resource is refunded
+        assert_eq!(Balances::free_balance(1), 98);
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 1002);
+
+        // nothing left to match, the orders wait
+        assert_ok!(ResourceOrder::create_order_by_spec(
+            Origin::signed(2),
+            requirement.clone(),
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_ok!(ResourceOrder::create_order_by_spec(
+            Origin::signed(2),
+            ResourceRequirement::new(4, 1, ubuntu.clone(), 1, 1),
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_eq!(ResourceOrder::waiting_order_queue(), vec![1, 2]);
+        assert_eq!(
+            ResourceOrder::waiting_orders(1).unwrap().deadline,
+            1 + HOURS
+        );
+        assert!(ResourceOrder::resource_orders(1).is_none());
+        assert_eq!(Balances::free_balance(2), 93);
+        assert_noop!(
+            ResourceOrder::create_order_by_spec(
+                Origin::signed(2),
+                requirement,
+                Bytes(vec![1, 2, 3])
+            ),
+            Error::<Test>::TooManyWaitingOrders
+        );
+
+        // one waiting order is matched in a block, the unmatched one goes to the back
+        System::set_block_number(2);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(2);
+        assert_eq!(ResourceOrder::waiting_order_queue(), vec![2, 1]);
+
+        // resource 1 is released, the first waiting order is matched once its turn comes
+        assert_ok!(ResourceOrder::cancel_order(Origin::signed(1), 0));
+        System::set_block_number(3);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(3);
+        assert_eq!(ResourceOrder::waiting_order_queue(), vec![1, 2]);
+        assert!(ResourceOrder::resource_orders(1).is_none());
+        System::set_block_number(4);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(4);
+        let order = ResourceOrder::resource_orders(1).unwrap();
+        assert_eq!(order.resource_index, 1);
+        assert_eq!(order.tenant_info.account_id, 2);
+        assert!(ResourceOrder::waiting_orders(1).is_none());
+        assert_eq!(ResourceOrder::waiting_order_queue(), vec![2]);
+        assert_eq!(Balances::free_balance(2), 97);
+
+        // no resource has 4 cpus, the escrow is refunded after the deadline
+        System::set_block_number(2 + HOURS);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(2 + HOURS);
+        assert!(ResourceOrder::waiting_orders(2).is_none());
+        assert!(ResourceOrder::waiting_order_queue().is_empty());
+        assert_eq!(Balances::free_balance(2), 98);
+    });
+}
+
+/// tenant: 2, provider of resources 1 and 2: 1
+#[test]
+fn it_works_for_compute_request() {
+    new_test_pub().execute_with(|| {
+        // unit price up to 5 for 2 hours, 10 is held until the settlement
+        let requirement = ResourceRequirement::new(1, 1, "ubuntu".as_bytes().to_vec(), 5, 2);
+
+        assert_noop!(
+            ResourceOrder::create_compute_request(
+                Origin::signed(2),
+                requirement.clone(),
+                HOURS + 1,
+                Bytes(vec![1, 2, 3])
+            ),
+            Error::<Test>::InvalidBiddingPeriod
+        );
+        for hours in [0, u32::MAX].iter() {
+            assert_noop!(
+                ResourceOrder::create_compute_request(
+                    Origin::signed(2),
+                    ResourceRequirement::new(1, 1, "ubuntu".as_bytes().to_vec(), 5, *hours),
+                    10,
+                    Bytes(vec![1, 2, 3])
+                ),
+                Error::<Test>::InvalidRentDuration
+            );
+        }
+        assert_ok!(ResourceOrder::create_compute_request(
+            Origin::signed(2),
+            requirement.clone(),
+            10,
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_eq!(ResourceOrder::compute_requests(0).unwrap().deadline, 11);
+        assert_eq!(ResourceOrder::block_compute_request(12), vec![0]);
+        assert_eq!(Balances::free_balance(2), 90);
+
+        // a second unused resource of provider 1
+        let mut resource = Provider::resource(2).unwrap();
+        resource.index = 2;
+        resource.update_status(ResourceStatus::Unused);
+        <Provider as OrderInterface>::update_computing_resource(2, resource);
+
+        assert_noop!(
+            ResourceOrder::bid_compute_request(Origin::signed(2), 0, 1, 3),
+            Error::<Test>::OrderNotOwnedByYou
+        );
+        assert_noop!(
+            ResourceOrder::bid_compute_request(Origin::signed(1), 0, 1, 6),
+            Error::<Test>::BidAboveMaxPrice
+        );
+        assert_ok!(ResourceOrder::bid_compute_request(
+            Origin::signed(1),
+            0,
+            1,
+            3
+        ));
+        assert_ok!(ResourceOrder::bid_compute_request(
+            Origin::signed(1),
+            0,
+            2,
+            2
+        ));
+        // the resource is locked by the bid
+        assert_noop!(
+            ResourceOrder::bid_compute_request(Origin::signed(1), 0, 1, 1),
+            Error::<Test>::ResourceHasBeenRented
+        );
+
+        // the lowest bid wins in the block after the deadline
+        System::set_block_number(12);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(12);
+        let request = ResourceOrder::compute_requests(0).unwrap();
+        assert_eq!(request.status, ComputeRequestStatus::Settled);
+        assert_eq!(request.order_index, Some(0));
+        let order = ResourceOrder::resource_orders(0).unwrap();
+        assert_eq!(order.resource_index, 2);
+        assert_eq!(order.price, 4);
+        assert_eq!(order.status, OrderStatus::Finished);
+        assert_eq!(
+            ResourceOrder::rental_agreements(0).unwrap().resource_index,
+            2
+        );
+        assert_eq!(Provider::resource(2).unwrap().status, ResourceStatus::Inuse);
+        // the losing bid is released, both are kept as price history
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Unused
+        );
+        let bids = ResourceOrder::request_bids(0);
+        assert_eq!(bids[0].status, BidStatus::Lost);
+        assert_eq!(bids[1].status, BidStatus::Won);
+        assert_eq!(Balances::free_balance(2), 96);
+
+        assert_noop!(
+            ResourceOrder::bid_compute_request(Origin::signed(1), 0, 1, 3),
+            Error::<Test>::BiddingClosed
+        );
+
+        // without a bid the escrow is refunded
+        assert_ok!(ResourceOrder::create_compute_request(
+            Origin::signed(2),
+            requirement.clone(),
+            1,
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_eq!(Balances::free_balance(2), 86);
+        System::set_block_number(14);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(14);
+        assert_eq!(
+            ResourceOrder::compute_requests(1).unwrap().status,
+            ComputeRequestStatus::Unfilled
+        );
+        assert_eq!(Balances::free_balance(2), 96);
+
+        // the pool cannot refund, the request is retried in the next block with room
+        assert_ok!(ResourceOrder::create_compute_request(
+            Origin::signed(2),
+            requirement,
+            1,
+            Bytes(vec![1, 2, 3])
+        ));
+        let pool = ResourceOrder::order_pool();
+        let pool_balance = Balances::free_balance(pool);
+        Balances::make_free_balance_be(&pool, 1);
+        BlockWithComputeRequest::<Test>::insert(17, vec![100, 101]);
+        System::set_block_number(16);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(16);
+        assert_eq!(
+            ResourceOrder::compute_requests(2).unwrap().status,
+            ComputeRequestStatus::Bidding
+        );
+        assert_eq!(ResourceOrder::block_compute_request(17), vec![100, 101]);
+        assert_eq!(ResourceOrder::block_compute_request(18), vec![2]);
+
+        Balances::make_free_balance_be(&pool, pool_balance);
+        System::set_block_number(18);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(18);
+        assert_eq!(
+            ResourceOrder::compute_requests(2).unwrap().status,
+            ComputeRequestStatus::Unfilled
+        );
+        assert_eq!(Balances::free_balance(2), 96);
     });
 }
-//
-// #[test]
-// fn it_works_for_cancel_order() {
-//     new_test_order().execute_with(|| {
-//         let account_id = 1;
-//         let resource_index = 1;
-//         let order_index = 0;
-//
-//         assert_noop!(
-//             ResourceOrder::cancel_order(Origin::signed(account_id), 100),
-//             Error::<Test>::OrderDoesNotExist
-//         );
-//         assert_noop!(
-//             ResourceOrder::cancel_order(Origin::signed(100), 0),
-//             Error::<Test>::OrderNotOwnedByYou
-//         );
-//
-//         assert_ok!(ResourceOrder::cancel_order(Origin::signed(account_id), 0));
-//
-//         let order_status = ResourceOrder::resource_orders(order_index).unwrap().status;
-//         assert_eq!(order_status, OrderStatus::Canceled);
-//
-//         let resource_status = Provider::resource(resource_index).unwrap().status;
-//         assert_eq!(resource_status, ResourceStatus::Unused);
-//     });
-// }
-//
 
 /// test heartbeat
 /// orderindex: 0, client id: 1,
@@ -165,15 +460,15 @@ fn it_works_for_heartbeat() {
         let account_id = 2;
 
         assert_noop!(
-            ResourceOrder::heartbeat(Origin::signed(account_id), 100),
+            ResourceOrder::heartbeat(Origin::signed(account_id), 100, None),
             Error::<Test>::ProtocolDoesNotExist
         );
         assert_noop!(
-            ResourceOrder::heartbeat(Origin::signed(100), 0),
+            ResourceOrder::heartbeat(Origin::signed(100), 0, None),
             Error::<Test>::ProtocolNotOwnedByYou
         );
 
-        // assert_ok!(ResourceOrder::heartbeat(Origin::signed(account_id), 0));
+        // assert_ok!(ResourceOrder::heartbeat(Origin::signed(account_id), 0, None));
         //
         // let block_number = 50;
         // let agreement = ResourceOrder::rental_agreements(0).unwrap();
@@ -198,17 +493,744 @@ fn it_works_for_heartbeat() {
     });
 }
 
+/// test heartbeat payment
+/// agreement price: 100, start block: 1, end block: 101
+#[test]
+fn it_works_for_heartbeat_payment() {
+    new_test_agreement().execute_with(|| {
+        let provider = 2;
+
+        assert_ok!(ResourceOrder::heartbeat(Origin::signed(provider), 0, None));
+
+        // 49 of the 100 blocks have been served
+        let agreement = ResourceOrder::rental_agreements(0).unwrap();
+        assert_eq!(agreement.calculation, 50);
+        assert_eq!(agreement.receive_amount, 49);
+        assert_eq!(Balances::free_balance(provider), 149);
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 951);
+        // the heartbeat deadline moves with the report
+        let list: Vec<u64> = vec![];
+        assert_eq!(ResourceOrder::heartbeat_deadlines(10 * MINUTES + 2), list);
+        assert_eq!(
+            ResourceOrder::heartbeat_deadlines(50 + 10 * MINUTES + 1),
+            vec![0]
+        );
+
+        // the rest is released when the agreement ends
+        System::set_block_number(101);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(101);
+
+        assert_eq!(Balances::free_balance(provider), 200);
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 900);
+        assert_eq!(Balances::free_balance(1), 100);
+        // the completed agreement raises the reputation
+        assert_eq!(Provider::provider_reputation(&provider), 520);
+    });
+}
+
+/// test heartbeat liveness proof
+/// provider: 2, agreement: 0, current block: 50
+#[test]
+fn it_works_for_liveness_proof() {
+    new_test_agreement().execute_with(|| {
+        let provider = 2;
+        let machine = ed25519::Pair::from_seed(&[1; 32]);
+        let prove = |pair: &ed25519::Pair, index: u64, block: BlockNumber| {
+            let payload = LivenessPayload {
+                index,
+                block,
+                nonce: System::block_hash(block),
+            };
+            Some(LivenessProof {
+                block,
+                signature: pair.sign(&payload.encode()),
+            })
+        };
+        frame_system::BlockHash::<Test>::insert(40, H256::repeat_byte(40));
+        frame_system::BlockHash::<Test>::insert(45, H256::repeat_byte(45));
+
+        // the peer id of the agreement has no key to verify with
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, prove(&machine, 0, 40)),
+            Error::<Test>::PeerKeyUnavailable
+        );
+
+        RentalAgreements::<Test>::mutate(0, |agreement| {
+            agreement.as_mut().unwrap().peer_id = PeerId::from_ed25519(&machine.public())
+        });
+
+        // signed by another machine, for another agreement or over an unknown block
+        let other = ed25519::Pair::from_seed(&[2; 32]);
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, prove(&other, 0, 40)),
+            Error::<Test>::InvalidLivenessProof
+        );
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, prove(&machine, 1, 40)),
+            Error::<Test>::InvalidLivenessProof
+        );
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, prove(&machine, 0, 30)),
+            Error::<Test>::StaleLivenessProof
+        );
+
+        assert_ok!(ResourceOrder::heartbeat(
+            Origin::signed(provider),
+            0,
+            prove(&machine, 0, 40)
+        ));
+        System::assert_last_event(Event::ResourceOrder(crate::Event::LivenessProven(0, 40)));
+        assert_eq!(ResourceOrder::liveness_proofs(0), Some(40));
+
+        // the proof cannot be replayed
+        System::set_block_number(60);
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, prove(&machine, 0, 40)),
+            Error::<Test>::StaleLivenessProof
+        );
+        assert_ok!(ResourceOrder::heartbeat(
+            Origin::signed(provider),
+            0,
+            prove(&machine, 0, 45)
+        ));
+        assert_eq!(ResourceOrder::liveness_proofs(0), Some(45));
+
+        // the proof is dropped with the agreement
+        assert_ok!(ResourceOrder::terminate_agreement(Origin::signed(1), 0));
+        assert_eq!(ResourceOrder::liveness_proofs(0), None);
+    });
+}
+
+/// test operator
+/// provider: 2, operator: 3, resource index: 1
+#[test]
+fn it_works_for_operator() {
+    new_test_order().execute_with(|| {
+        let provider = 2;
+        let operator = 3;
+
+        assert_noop!(
+            ResourceOrder::order_exec(Origin::signed(operator), 0),
+            Error::<Test>::OrderNotOwnedByYou
+        );
+
+        assert_ok!(Market::authorize_operator(
+            Origin::signed(provider),
+            operator,
+            OperatorScope::Resource(1)
+        ));
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(operator), 0));
+
+        // the agreement belongs to the provider
+        let agreement = ResourceOrder::rental_agreements(0).unwrap();
+        assert_eq!(agreement.provider, provider);
+        assert_eq!(Provider::resource_reputation(1), 505);
+        assert_eq!(ResourceOrder::provider_agreements(provider), vec![0]);
+
+        // the operator can report heartbeats until it is revoked
+        assert_ok!(ResourceOrder::heartbeat(Origin::signed(operator), 0, None));
+        System::assert_last_event(Event::ResourceOrder(crate::Event::HealthCheckSuccess(
+            provider,
+            0,
+            System::block_number(),
+        )));
+        // but the resource is only drained by its owner
+        assert_noop!(
+            Provider::start_draining(Origin::signed(operator), 1),
+            ProviderError::<Test>::IllegalRequest
+        );
+        assert_ok!(Provider::start_draining(Origin::signed(provider), 1));
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Draining
+        );
+        assert_ok!(Market::revoke_operator(
+            Origin::signed(provider),
+            operator,
+            OperatorScope::Resource(1)
+        ));
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(operator), 0, None),
+            Error::<Test>::ProtocolNotOwnedByYou
+        );
+    });
+}
+
+/// test early termination
+/// agreement price: 100, start block: 1, end block: 101, current block: 50
+#[test]
+fn it_works_for_terminate_agreement() {
+    new_test_agreement().execute_with(|| {
+        let tenant = 1;
+        let provider = 2;
+
+        assert_noop!(
+            ResourceOrder::terminate_agreement(Origin::signed(tenant), 100),
+            Error::<Test>::ProtocolDoesNotExist
+        );
+        assert_noop!(
+            ResourceOrder::terminate_agreement(Origin::signed(provider), 0),
+            Error::<Test>::ProtocolNotOwnedByYou
+        );
+
+        // a pending renewal is canceled and refunded with the agreement
+        assert_ok!(ResourceOrder::renew_agreement(Origin::signed(tenant), 0, 1));
+        assert_eq!(Balances::free_balance(tenant), 99);
+
+        assert_ok!(ResourceOrder::terminate_agreement(
+            Origin::signed(tenant),
+            0
+        ));
+        assert_eq!(
+            ResourceOrder::resource_orders(1).unwrap().status,
+            OrderStatus::Canceled
+        );
+
+        // 49 blocks served plus 10% of the remaining 51 go to the provider, the rest is refunded
+        assert_eq!(Balances::free_balance(provider), 154);
+        assert_eq!(Balances::free_balance(tenant), 146);
+
+        let agreement = ResourceOrder::rental_agreements(0).unwrap();
+        assert_eq!(agreement.status, AgreementStatus::Finished);
+        assert_eq!(agreement.end, 50);
+
+        let list: Vec<u64> = vec![];
+        assert_eq!(ResourceOrder::block_agreement(101), list);
+        assert_eq!(ResourceOrder::user_agreements(tenant), list);
+        assert_eq!(ResourceOrder::provider_agreements(provider), list);
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Unused
+        );
+
+        assert_noop!(
+            ResourceOrder::terminate_agreement(Origin::signed(tenant), 0),
+            Error::<Test>::AgreementHasBeenFinished
+        );
+    });
+}
+
+/// tenant: 2, provider of resources 1 and 2: 1
+#[test]
+fn it_works_for_cluster_order() {
+    new_test_pub().execute_with(|| {
+        let tenant = 2;
+        let provider = 1;
+        // a second unused resource of provider 1
+        let mut resource = Provider::resource(2).unwrap();
+        resource.index = 2;
+        resource.update_status(ResourceStatus::Unused);
+        <Provider as OrderInterface>::update_computing_resource(2, resource);
+
+        assert_noop!(
+            ResourceOrder::create_cluster_order(
+                Origin::signed(tenant),
+                vec![],
+                1,
+                Bytes(vec![1, 2, 3])
+            ),
+            Error::<Test>::InvalidClusterSize
+        );
+        assert_noop!(
+            ResourceOrder::create_cluster_order(
+                Origin::signed(tenant),
+                vec![ClusterMember::Resource(1)],
+                u32::MAX,
+                Bytes(vec![1, 2, 3])
+            ),
+            Error::<Test>::InvalidRentDuration
+        );
+        // none of the resources is locked when one of them fails
+        assert_noop!(
+            ResourceOrder::create_cluster_order(
+                Origin::signed(tenant),
+                vec![ClusterMember::Resource(1), ClusterMember::Resource(100)],
+                1,
+                Bytes(vec![1, 2, 3])
+            ),
+            Error::<Test>::ResourceNotExist
+        );
+
+        // the spec is matched to resource 2, resource 1 is locked by then
+        assert_ok!(ResourceOrder::create_cluster_order(
+            Origin::signed(tenant),
+            vec![
+                ClusterMember::Resource(1),
+                ClusterMember::Spec {
+                    cpu: 1,
+                    memory: 1,
+                    system: "ubuntu".as_bytes().to_vec(),
+                    max_price: 1,
+                },
+            ],
+            1,
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_eq!(ResourceOrder::cluster_orders(0).unwrap().orders, vec![0, 1]);
+        assert_eq!(ResourceOrder::resource_orders(1).unwrap().resource_index, 2);
+        assert_eq!(ResourceOrder::user_clusters(tenant), vec![0]);
+        assert_eq!(Balances::free_balance(tenant), 98);
+
+        // the agreements share the cluster as the provider executes the orders
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(provider), 0));
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(provider), 1));
+        assert_eq!(
+            ResourceOrder::cluster_orders(0).unwrap().agreements,
+            vec![0, 1]
+        );
+        assert_eq!(ResourceOrder::agreement_cluster(1), Some(0));
+        assert_eq!(
+            ResourceOrder::user_cluster_agreements(tenant),
+            vec![(0, Some(0)), (1, Some(0))]
+        );
+
+        assert_noop!(
+            ResourceOrder::renew_cluster(Origin::signed(provider), 0, 1),
+            Error::<Test>::ClusterNotOwnedByYou
+        );
+        assert_ok!(ResourceOrder::renew_cluster(Origin::signed(tenant), 0, 1));
+        assert_eq!(
+            ResourceOrder::resource_orders(2).unwrap().agreement_index,
+            Some(0)
+        );
+        assert_eq!(
+            ResourceOrder::resource_orders(3).unwrap().agreement_index,
+            Some(1)
+        );
+
+        assert_ok!(ResourceOrder::terminate_cluster(Origin::signed(tenant), 0));
+        assert_eq!(
+            ResourceOrder::rental_agreements(0).unwrap().status,
+            AgreementStatus::Finished
+        );
+        assert_eq!(
+            ResourceOrder::rental_agreements(1).unwrap().status,
+            AgreementStatus::Finished
+        );
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Unused
+        );
+        assert_eq!(
+            Provider::resource(2).unwrap().status,
+            ResourceStatus::Unused
+        );
+    });
+}
+
+/// tenant: 2, provider of resources 1 and 2: 1
+#[test]
+fn it_works_for_failed_cluster() {
+    new_test_pub().execute_with(|| {
+        let tenant = 2;
+        let provider = 1;
+        let mut resource = Provider::resource(2).unwrap();
+        resource.index = 2;
+        resource.update_status(ResourceStatus::Unused);
+        <Provider as OrderInterface>::update_computing_resource(2, resource);
+
+        assert_ok!(ResourceOrder::create_cluster_order(
+            Origin::signed(tenant),
+            vec![ClusterMember::Resource(1), ClusterMember::Resource(2)],
+            1,
+            Bytes(vec![1, 2, 3])
+        ));
+        assert_eq!(
+            ResourceOrder::cluster_orders(0).unwrap().status,
+            ClusterStatus::Active
+        );
+        assert_eq!(Balances::free_balance(tenant), 98);
+
+        // only one of the orders is executed in time
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(provider), 0));
+        System::set_block_number(301);
+        ResourceOrder::order_check(301);
+
+        // the running agreement is terminated without the fee, the cluster is refunded whole
+        assert_eq!(
+            ResourceOrder::resource_orders(1).unwrap().status,
+            OrderStatus::Canceled
+        );
+        assert_eq!(
+            ResourceOrder::rental_agreements(0).unwrap().status,
+            AgreementStatus::Finished
+        );
+        assert_eq!(
+            ResourceOrder::cluster_orders(0).unwrap().status,
+            ClusterStatus::Failed
+        );
+        System::assert_last_event(Event::ResourceOrder(crate::Event::ClusterFailed(tenant, 0)));
+        assert_eq!(Balances::free_balance(tenant), 100);
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Unused
+        );
+        assert_eq!(
+            Provider::resource(2).unwrap().status,
+            ResourceStatus::Unused
+        );
+    });
+}
+
+/// tenant: 2, provider of resource 1: 1
+#[test]
+fn it_works_for_manifest() {
+    new_test_pub().execute_with(|| {
+        let tenant = 2;
+        let provider = 1;
+        let manifest = Manifest::Plain(DeploymentManifest {
+            image_digest: "sha256:4f53".as_bytes().to_vec(),
+            ports: vec![5432],
+            cpu_limit: 1,
+            memory_limit: 1,
+            env_hash: H256::repeat_byte(1),
+        });
+
+        assert_noop!(
+            ResourceOrder::create_order_info(
+                Origin::signed(tenant),
+                1,
+                1,
+                Bytes(vec![1, 2, 3]),
+                Some(Manifest::Encrypted(vec![]))
+            ),
+            Error::<Test>::InvalidManifest
+        );
+        assert_ok!(ResourceOrder::create_order_info(
+            Origin::signed(tenant),
+            1,
+            1,
+            Bytes(vec![1, 2, 3]),
+            Some(manifest.clone())
+        ));
+        assert_eq!(ResourceOrder::order_manifests(0), Some(manifest.clone()));
+
+        // the manifest moves to the agreement and is surfaced to the provider agent
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(provider), 0));
+        assert_eq!(ResourceOrder::order_manifests(0), None);
+        assert_eq!(
+            ResourceOrder::agreement_manifests(0),
+            Some(manifest.clone())
+        );
+        assert!(System::events().iter().any(|record| record.event
+            == Event::ResourceOrder(crate::Event::OrderExecSuccess(
+                provider,
+                0,
+                1,
+                0,
+                Some(manifest.clone())
+            ))));
+
+        let encrypted = Manifest::Encrypted(vec![7; 64]);
+        assert_noop!(
+            ResourceOrder::update_manifest(Origin::signed(provider), 0, encrypted.clone()),
+            Error::<Test>::ProtocolNotOwnedByYou
+        );
+        assert_ok!(ResourceOrder::update_manifest(
+            Origin::signed(tenant),
+            0,
+            encrypted.clone()
+        ));
+        assert_eq!(
+            ResourceOrder::agreement_manifests(0),
+            Some(encrypted.clone())
+        );
+        System::assert_last_event(Event::ResourceOrder(crate::Event::ManifestUpdated(
+            tenant, 0, encrypted,
+        )));
+
+        // the manifest is dropped with the agreement
+        assert_ok!(ResourceOrder::terminate_agreement(
+            Origin::signed(tenant),
+            0
+        ));
+        assert_eq!(ResourceOrder::agreement_manifests(0), None);
+    });
+}
+
+/// tenant: 2, provider of resource 1: 1
+#[test]
+fn it_works_for_access_info() {
+    new_test_pub().execute_with(|| {
+        let tenant = 2;
+        let provider = 1;
+        // the tenant orders with its X25519 public key
+        let tenant_secret = [7u8; 32];
+        let tenant_public = p_sealed_box::public_key(&tenant_secret);
+
+        assert_ok!(ResourceOrder::create_order_info(
+            Origin::signed(tenant),
+            1,
+            1,
+            Bytes(tenant_public.to_vec()),
+            None
+        ));
+        assert_ok!(ResourceOrder::order_exec(Origin::signed(provider), 0));
+
+        let agreement = ResourceOrder::rental_agreements(0).unwrap();
+        let sealed = p_sealed_box::seal(
+            b"ssh root@10.0.0.8 -p 2222",
+            &agreement.tenant_info.public_key,
+            [9u8; 32],
+        )
+        .unwrap();
+
+        assert_noop!(
+            ResourceOrder::publish_access_info(Origin::signed(tenant), 0, sealed.clone()),
+            Error::<Test>::ProtocolNotOwnedByYou
+        );
+        assert_noop!(
+            ResourceOrder::publish_access_info(Origin::signed(provider), 0, vec![]),
+            Error::<Test>::InvalidAccessInfo
+        );
+        assert_ok!(ResourceOrder::publish_access_info(
+            Origin::signed(provider),
+            0,
+            sealed
+        ));
+        System::assert_last_event(Event::ResourceOrder(crate::Event::AccessInfoPublished(
+            tenant, 0, 1,
+        )));
+
+        // only the tenant opens the access info
+        let access_info = ResourceOrder::access_infos(0).unwrap();
+        assert_eq!(
+            p_sealed_box::open(&access_info.payload, &tenant_secret).unwrap(),
+            b"ssh root@10.0.0.8 -p 2222".to_vec()
+        );
+
+        // rotation
+        let sealed = p_sealed_box::seal(b"ssh root@10.0.0.9", &tenant_public, [10u8; 32]).unwrap();
+        assert_ok!(ResourceOrder::publish_access_info(
+            Origin::signed(provider),
+            0,
+            sealed
+        ));
+        let access_info = ResourceOrder::access_infos(0).unwrap();
+        assert_eq!(access_info.version, 2);
+        assert_eq!(
+            p_sealed_box::open(&access_info.payload, &tenant_secret).unwrap(),
+            b"ssh root@10.0.0.9".to_vec()
+        );
+
+        // the access info is dropped with the agreement
+        assert_ok!(ResourceOrder::terminate_agreement(
+            Origin::signed(tenant),
+            0
+        ));
+        assert_eq!(ResourceOrder::access_infos(0), None);
+    });
+}
+
+/// test free resource trial
+/// client: 1, provider: 2
+#[test]
+fn it_works_for_free_resource() {
+    new_test_pub().execute_with(|| {
+        let account_id = 1;
+        let public_key = Bytes(vec![1, 2, 3]);
+
+        assert_noop!(
+            ResourceOrder::apply_free_resource(
+                Origin::signed(account_id),
+                1,
+                1,
+                25,
+                0,
+                public_key.clone()
+            ),
+            Error::<Test>::FreeResourceForbidden
+        );
+
+        assert_ok!(ResourceOrder::apply_free_resource(
+            Origin::signed(account_id),
+            1,
+            1,
+            2,
+            0,
+            public_key.clone()
+        ));
+        assert_eq!(ResourceOrder::apply_users(account_id), 0);
+        assert_eq!(ResourceOrder::apply_order_index(), 1);
+
+        // only one trial at a time
+        assert_noop!(
+            ResourceOrder::apply_free_resource(
+                Origin::signed(account_id),
+                1,
+                1,
+                2,
+                0,
+                public_key.clone()
+            ),
+            Error::<Test>::FreeResourceApplied
+        );
+
+        let peer_id = "12D3KooW9tHTtS3inCZiYykw4u5G4frbjVFqhkmJX12gSNCVeH3e"
+            .as_bytes()
+            .to_vec();
+
+        // only the configured origin can process the apply order
+        assert_noop!(
+            ResourceOrder::process_apply_order(Origin::signed(2), 0, 1),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            ResourceOrder::process_apply_order(Origin::root(), 100, 1),
+            Error::<Test>::FreeResourceNotExists
+        );
+        // the trial runs on an unused resource of a provider
+        assert_noop!(
+            ResourceOrder::process_apply_order(Origin::root(), 0, 100),
+            Error::<Test>::ResourceNotExist
+        );
+        assert_noop!(
+            ResourceOrder::process_apply_order(Origin::root(), 0, 2),
+            Error::<Test>::ResourceHasBeenRented
+        );
+
+        assert_ok!(ResourceOrder::process_apply_order(Origin::root(), 0, 1));
+        let apply_order = ResourceOrder::apply_orders(0).unwrap();
+        assert_eq!(apply_order.status, OrderStatus::Finished);
+        assert_eq!(apply_order.provider, 1);
+        assert_eq!(apply_order.resource_index, 1);
+        assert_eq!(apply_order.end, 1201);
+        assert_eq!(ResourceOrder::block_apply_order(1201), vec![0]);
+        assert_eq!(Provider::resource(1).unwrap().status, ResourceStatus::Inuse);
+
+        assert_noop!(
+            ResourceOrder::process_apply_order(Origin::root(), 0, 1),
+            Error::<Test>::FreeResourceHasBeDeal
+        );
+
+        // the trial expires
+        System::set_block_number(1201);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(1201);
+        let list: Vec<u64> = vec![];
+        assert_eq!(ResourceOrder::block_apply_order(1201), list);
+        System::assert_last_event(Event::ResourceOrder(crate::Event::FreeResourceExpired(
+            0,
+            PeerId::parse(&peer_id).unwrap(),
+        )));
+
+        // the trial has ended and the resource is free again
+        assert_eq!(
+            ResourceOrder::apply_orders(0).unwrap().status,
+            OrderStatus::Expired
+        );
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Unused
+        );
+        assert!(!crate::ApplyUsers::<Test>::contains_key(account_id));
+        assert_ok!(ResourceOrder::apply_free_resource(
+            Origin::signed(account_id),
+            1,
+            1,
+            2,
+            0,
+            public_key
+        ));
+    });
+}
+
+/// test drain mode
+/// provider: 2, tenant: 1, resource index: 1, agreement end block: 101
+#[test]
+fn it_works_for_draining() {
+    new_test_agreement().execute_with(|| {
+        let tenant = 1;
+        let provider = 2;
+
+        assert_ok!(Provider::start_draining(Origin::signed(provider), 1));
+        assert_eq!(
+            Provider::resource(1).unwrap().status,
+            ResourceStatus::Draining
+        );
+
+        // no new orders and no renewals
+        assert_noop!(
+            ResourceOrder::create_order_info(Origin::signed(tenant), 1, 1, Bytes(vec![1]), None),
+            Error::<Test>::ResourceDraining
+        );
+        assert_noop!(
+            ResourceOrder::renew_agreement(Origin::signed(tenant), 0, 1),
+            Error::<Test>::ResourceDraining
+        );
+
+        // the running agreement is completed, then the resource is offlined
+        System::set_block_number(101);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(101);
+        assert_eq!(ResourceOrder::rental_agreements(0), None);
+        assert_eq!(Provider::resource(1), None);
+        assert_eq!(Provider::resource_count(), 0);
+        assert_eq!(Provider::provider(provider), None);
+    });
+}
+
+/// provider: 2, new owner: 100, resource index: 1, agreement index: 0
+#[test]
+fn it_works_for_resource_transfer() {
+    new_test_agreement().execute_with(|| {
+        let provider = 2;
+        let new_owner = 100;
+
+        assert_ok!(Provider::transfer_resource(
+            Origin::signed(provider),
+            1,
+            new_owner
+        ));
+        assert_ok!(Provider::accept_resource_transfer(
+            Origin::signed(new_owner),
+            1
+        ));
+
+        // the running agreement follows the resource
+        let agreement = ResourceOrder::rental_agreements(0).unwrap();
+        assert_eq!(agreement.provider, new_owner);
+        let list: Vec<u64> = vec![];
+        assert_eq!(ResourceOrder::provider_agreements(provider), list);
+        assert_eq!(ResourceOrder::provider_agreements(new_owner), vec![0]);
+
+        // and the heartbeats come from the new owner
+        assert_noop!(
+            ResourceOrder::heartbeat(Origin::signed(provider), 0, None),
+            Error::<Test>::ProtocolNotOwnedByYou
+        );
+        assert_ok!(ResourceOrder::heartbeat(Origin::signed(new_owner), 0, None));
+        assert_eq!(Balances::free_balance(new_owner), 49);
+    });
+}
+
 /// test health check
 /// Provider 2, client 1, agreement index: 0
 #[test]
 fn it_works_for_health_check() {
     new_test_health_check().execute_with(|| {
+        // no heartbeat has been reported since block 1
+        assert_eq!(
+            ResourceOrder::heartbeat_deadlines(10 * MINUTES + 2),
+            vec![0]
+        );
         // hook, health check
-        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(20 * MINUTES);
+        System::set_block_number(10 * MINUTES + 2);
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_initialize(
+            10 * MINUTES + 2,
+        );
 
         // check the agreement status
         let agreement = ResourceOrder::rental_agreements(0).unwrap();
         assert_eq!(agreement.status, AgreementStatus::Punished);
+        // the unreleased rent is returned to the tenant
+        assert_eq!(agreement.penalty_amount, 100);
+        assert_eq!(Balances::free_balance(1), 200);
+        // the fault lasted since block 1, one hour in the statistics
+        let resource = Provider::resource(1).unwrap();
+        assert_eq!(resource.rental_statistics.fault_count, 1);
+        assert_eq!(resource.rental_statistics.fault_duration, 1);
+        // the reputation drops by 100 and 10 per fault hour
+        assert_eq!(Provider::resource_reputation(1), 390);
+        assert_eq!(Provider::provider_reputation(&2), 390);
         // check the block number of the agreement
         // set the block nums
         System::set_block_number(20001);
@@ -243,6 +1265,40 @@ fn it_works_for_health_check() {
     });
 }
 
+#[test]
+fn test_migration_adds_rent_price() {
+    new_test_pub().execute_with(|| {
+        let tenant = TenantInfo {
+            account_id: 2,
+            public_key: Bytes(vec![1]),
+        };
+        // an order stored before the rent was held in the order pool
+        let old = (
+            0u64,
+            tenant.clone(),
+            1u64,
+            1 as BlockNumber,
+            600 as BlockNumber,
+            Duration::from_secs(1),
+            OrderStatus::Pending,
+            None::<u64>,
+        );
+        frame_support::storage::unhashed::put(&ResourceOrders::<Test>::hashed_key_for(0), &old);
+        StorageVersion::<Test>::put(Releases::V1_0_0);
+
+        <ResourceOrder as frame_support::traits::Hooks<BlockNumber>>::on_runtime_upgrade();
+
+        assert_eq!(ResourceOrder::storage_version(), Releases::V3_0_0);
+        let order = ResourceOrder::resource_orders(0).unwrap();
+        assert_eq!(order.price, 0);
+        assert_eq!(order.tenant_info, tenant);
+        assert_eq!(order.rent_duration, 600);
+        assert_eq!(order.status, OrderStatus::Pending);
+        // the order pool was already endowed
+        assert_eq!(Balances::free_balance(ResourceOrder::order_pool()), 1000);
+    });
+}
+
 //
 // #[test]
 // fn it_works_for_renew_agreement() {
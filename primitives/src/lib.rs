@@ -4,8 +4,11 @@ pub mod constants;
 pub mod p_chunkcycle;
 pub mod p_gateway;
 pub mod p_market;
+pub mod p_peer_id;
 pub mod p_provider;
 pub mod p_resource_order;
+#[cfg(feature = "std")]
+pub mod p_sealed_box;
 pub mod p_staking;
 
 use sp_runtime::{
@@ -1,3 +1,4 @@
+use crate::p_peer_id::PeerId;
 use crate::p_provider::ProviderPoints;
 use crate::p_resource_order::RentalAgreement;
 use codec::{Decode, Encode};
@@ -26,7 +27,7 @@ where
     BlockNumber: Parameter + AtLeast32BitUnsigned,
 {
     // [(account, peer_ids), gateway nums]
-    Gateway((Vec<(AccountId, Vec<Vec<u8>>)>, Vec<(Vec<u8>, u128)>, u128)),
+    Gateway((Vec<(AccountId, Vec<PeerId>)>, Vec<(PeerId, u128)>, u128)),
     Provider((Vec<(AccountId, ProviderPoints)>, u128, u128)),
     Client(Vec<(u64, RentalAgreement<AccountId, BlockNumber>)>),
 }
@@ -6,6 +6,8 @@ use sp_debug_derive::RuntimeDebug;
 use sp_runtime::traits::AtLeast32BitUnsigned;
 use sp_std::vec::Vec;
 
+use crate::p_peer_id::PeerId;
+
 /// Gateway node
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -16,7 +18,7 @@ where
     /// gateway node account
     pub account_id: AccountId,
     /// gateway node peer_id
-    pub peer_id: Vec<u8>,
+    pub peer_id: PeerId,
     ///  gateway node registration time
     pub registration_time: BlockNumber,
 }
@@ -25,7 +27,7 @@ impl<BlockNumber, AccountId> GatewayNode<BlockNumber, AccountId>
 where
     BlockNumber: Parameter + AtLeast32BitUnsigned,
 {
-    pub fn new(account_id: AccountId, peer_id: Vec<u8>, registration_time: BlockNumber) -> Self {
+    pub fn new(account_id: AccountId, peer_id: PeerId, registration_time: BlockNumber) -> Self {
         GatewayNode {
             account_id,
             peer_id,
@@ -35,9 +37,9 @@ where
 }
 
 pub trait GatewayInterface<AccountId, BlockNumber> {
-    fn account_own_peerid(who: AccountId, peerid: Vec<u8>) -> bool;
+    fn account_own_peerid(who: AccountId, peerid: PeerId) -> bool;
 
-    fn gateway_online_list() -> (Vec<(AccountId, Vec<Vec<u8>>)>, Vec<(Vec<u8>, u128)>, u128);
+    fn gateway_online_list() -> (Vec<(AccountId, Vec<PeerId>)>, Vec<(PeerId, u128)>, u128);
 
-    fn update_gateway_node_register_time(peerid: Vec<u8>);
+    fn update_gateway_node_register_time(peerid: PeerId);
 }
@@ -1,3 +1,4 @@
+use crate::p_peer_id::PeerId;
 use crate::EraIndex;
 use codec::{Decode, Encode};
 #[cfg(feature = "std")]
@@ -65,7 +66,7 @@ impl StakingAmount {
             return false;
         }
 
-        self.active_amount =  self.active_amount.saturating_sub(price);
+        self.active_amount = self.active_amount.saturating_sub(price);
         self.lock_amount = self.lock_amount.saturating_add(price);
 
         true
@@ -201,3 +202,18 @@ pub trait MarketInterface<AccountId> {
 
     fn client_staking_fee() -> u128;
 }
+
+/// the scope an operator account is authorized for
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum OperatorScope {
+    /// computing resource [resource index]
+    Resource(u64),
+    /// gateway node [peer id]
+    Gateway(PeerId),
+}
+
+pub trait OperatorInterface<AccountId> {
+    /// whether `who` can act for `owner` in the scope, the owner itself is always authorized
+    fn is_authorized(owner: &AccountId, who: &AccountId, scope: OperatorScope) -> bool;
+}
@@ -0,0 +1,336 @@
+use codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_core::ed25519;
+use sp_debug_derive::RuntimeDebug;
+use sp_runtime::traits::Verify;
+use sp_std::vec::Vec;
+
+/// maximum length of the base58 encoded peer id
+pub const MAX_PEER_ID_LEN: usize = 64;
+/// largest public key a peer id may inline with the identity hash
+pub const MAX_INLINE_KEY_LEN: usize = 42;
+
+/// multihash code of the identity hash
+const IDENTITY_CODE: u64 = 0x00;
+/// multihash code of sha2-256
+const SHA2_256_CODE: u64 = 0x12;
+/// digest length of sha2-256
+const SHA2_256_LEN: usize = 32;
+/// protobuf prefix of an ed25519 public key: key type 1 and 32 bytes of data
+const ED25519_KEY_PREFIX: [u8; 4] = [0x08, 0x01, 0x12, 0x20];
+
+/// the bitcoin base58 alphabet used by libp2p
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// why the bytes are not a peer id
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+pub enum PeerIdError {
+    /// longer than MAX_PEER_ID_LEN
+    TooLong,
+    /// neither base58 nor a multihash
+    InvalidEncoding,
+    /// the multihash is not an identity or sha2-256 hash of a key
+    InvalidMultihash,
+}
+
+/// libp2p peer id, kept as its base58 encoding.
+/// the SCALE encoding is the one of the bytes, so it replaces a `Vec<u8>` in storage as is.
+/// the decoding is not bounded, the peer ids stored before the validation of any length still
+/// decode. the extrinsics bound and validate the peer ids they are given
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Default, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct PeerId(Vec<u8>);
+
+impl PeerId {
+    /// parse the base58 encoding or the raw multihash of a peer id
+    pub fn parse(input: &[u8]) -> Result<Self, PeerIdError> {
+        if input.len() > MAX_PEER_ID_LEN {
+            return Err(PeerIdError::TooLong);
+        }
+
+        // the multihash codes are not base58 characters, so the raw bytes come first
+        if is_valid_multihash(input) {
+            return Ok(PeerId(base58_encode(input)));
+        }
+
+        let multihash = base58_decode(input).ok_or(PeerIdError::InvalidEncoding)?;
+        if !is_valid_multihash(&multihash) {
+            return Err(PeerIdError::InvalidMultihash);
+        }
+
+        Ok(PeerId(input.to_vec()))
+    }
+
+    /// wrap stored bytes without validation, for the genesis and the storage migrations
+    pub fn new_unchecked(bytes: Vec<u8>) -> Self {
+        PeerId(bytes)
+    }
+
+    /// whether the bytes are the canonical encoding of a valid peer id
+    pub fn is_valid(&self) -> bool {
+        match PeerId::parse(&self.0) {
+            Ok(peer_id) => peer_id == *self,
+            Err(_) => false,
+        }
+    }
+
+    /// peer id of an ed25519 key, the key is inlined with the identity hash
+    pub fn from_ed25519(public: &ed25519::Public) -> Self {
+        let mut multihash = Vec::with_capacity(2 + ED25519_KEY_PREFIX.len() + 32);
+        multihash.push(IDENTITY_CODE as u8);
+        multihash.push((ED25519_KEY_PREFIX.len() + 32) as u8);
+        multihash.extend_from_slice(&ED25519_KEY_PREFIX);
+        multihash.extend_from_slice(public.as_ref());
+        PeerId(base58_encode(&multihash))
+    }
+
+    /// the ed25519 public key of the peer,
+    /// none for the peer ids that hash their key
+    pub fn ed25519_public(&self) -> Option<ed25519::Public> {
+        let multihash = self.to_multihash()?;
+        if !is_valid_multihash(&multihash) || multihash[0] as u64 != IDENTITY_CODE {
+            return None;
+        }
+        let digest = &multihash[2..];
+        if !digest.starts_with(&ED25519_KEY_PREFIX) || digest.len() != ED25519_KEY_PREFIX.len() + 32
+        {
+            return None;
+        }
+
+        let mut public = [0u8; 32];
+        public.copy_from_slice(&digest[ED25519_KEY_PREFIX.len()..]);
+        Some(ed25519::Public::from_raw(public))
+    }
+
+    /// whether the payload is signed by the ed25519 key of the peer
+    pub fn verify<BlockNumber: Encode, Hash: Encode>(
+        &self,
+        payload: &LivenessPayload<BlockNumber, Hash>,
+        signature: &ed25519::Signature,
+    ) -> bool {
+        match self.ed25519_public() {
+            Some(public) => signature.verify(&payload.encode()[..], &public),
+            None => false,
+        }
+    }
+
+    /// the multihash of the peer id
+    pub fn to_multihash(&self) -> Option<Vec<u8>> {
+        base58_decode(&self.0)
+    }
+
+    /// the base58 encoding
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// what the machine behind a peer id signs to prove it is alive
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct LivenessPayload<BlockNumber, Hash> {
+    /// agreement index, 0 for a gateway node
+    pub index: u64,
+    /// recent block the proof was made at
+    pub block: BlockNumber,
+    /// hash of that block, unknown before it was produced
+    pub nonce: Hash,
+}
+
+/// liveness proof carried by a heartbeat
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct LivenessProof<BlockNumber> {
+    /// block of the signed payload
+    pub block: BlockNumber,
+    /// signature of the payload by the ed25519 peer key
+    pub signature: ed25519::Signature,
+}
+
+/// read an unsigned varint, return the value and the rest of the bytes.
+/// the varint must be minimal, as libp2p requires, so a multihash has a single encoding
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            if *byte == 0 && i > 0 {
+                return None;
+            }
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// an identity hash of a small key or a sha2-256 hash, as libp2p builds peer ids
+fn is_valid_multihash(bytes: &[u8]) -> bool {
+    let (code, rest) = match read_varint(bytes) {
+        Some(x) => x,
+        None => return false,
+    };
+    let (len, digest) = match read_varint(rest) {
+        Some(x) => x,
+        None => return false,
+    };
+    if digest.len() as u64 != len {
+        return false;
+    }
+
+    match code {
+        IDENTITY_CODE => digest.len() > 0 && digest.len() <= MAX_INLINE_KEY_LEN,
+        SHA2_256_CODE => digest.len() == SHA2_256_LEN,
+        _ => false,
+    }
+}
+
+fn base58_decode(input: &[u8]) -> Option<Vec<u8>> {
+    if input.is_empty() {
+        return None;
+    }
+
+    // little endian bytes of the number
+    let mut bytes: Vec<u8> = Vec::with_capacity(input.len());
+    for c in input {
+        let mut carry = BASE58_ALPHABET.iter().position(|x| x == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // every leading '1' is a leading zero byte
+    for _ in input.iter().take_while(|c| **c == b'1') {
+        bytes.push(0);
+    }
+
+    bytes.reverse();
+    Some(bytes)
+}
+
+fn base58_encode(input: &[u8]) -> Vec<u8> {
+    // little endian base58 digits of the number
+    let mut digits: Vec<u8> = Vec::with_capacity(input.len() * 138 / 100 + 1);
+    for byte in input {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut encoded: Vec<u8> = input
+        .iter()
+        .take_while(|byte| **byte == 0)
+        .map(|_| b'1')
+        .collect();
+    encoded.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|digit| BASE58_ALPHABET[*digit as usize]),
+    );
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    // ed25519 peer id of the libp2p peer id spec and its public key
+    const ED25519_PEER_ID: &[u8] = b"12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA";
+    const ED25519_PUBLIC: [u8; 32] =
+        hex!("2ffa35a99d3a3cfbb17bb7c1dc5561b18a8dcca4df38dc613ea859c37eb1336b");
+    // sha2-256 peer id of the libp2p docs and its multihash
+    const SHA2_PEER_ID: &[u8] = b"QmYyQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N";
+    const SHA2_MULTIHASH: [u8; 34] =
+        hex!("12209dff3b17d74cf4d38a50d8b6383e92d181a10395a5e73a726dcccbd21bf6f0b9");
+
+    #[test]
+    fn parse_ed25519_peer_id() {
+        let peer_id = PeerId::parse(ED25519_PEER_ID).unwrap();
+        assert!(peer_id.is_valid());
+        assert_eq!(peer_id.as_bytes(), ED25519_PEER_ID);
+
+        let public = ed25519::Public::from_raw(ED25519_PUBLIC);
+        assert_eq!(peer_id.ed25519_public(), Some(public));
+        assert_eq!(PeerId::from_ed25519(&public), peer_id);
+    }
+
+    #[test]
+    fn parse_sha2_peer_id() {
+        let peer_id = PeerId::parse(SHA2_PEER_ID).unwrap();
+        assert!(peer_id.is_valid());
+        assert_eq!(peer_id.to_multihash().unwrap(), SHA2_MULTIHASH.to_vec());
+        // the key is hashed, the signatures cannot be checked
+        assert_eq!(peer_id.ed25519_public(), None);
+    }
+
+    #[test]
+    fn raw_multihash_round_trip() {
+        let peer_id = PeerId::parse(&SHA2_MULTIHASH).unwrap();
+        assert_eq!(peer_id.as_bytes(), SHA2_PEER_ID);
+        assert_eq!(base58_encode(&SHA2_MULTIHASH), SHA2_PEER_ID.to_vec());
+        assert_eq!(
+            base58_decode(SHA2_PEER_ID).unwrap(),
+            SHA2_MULTIHASH.to_vec()
+        );
+
+        let multihash = PeerId::parse(ED25519_PEER_ID)
+            .unwrap()
+            .to_multihash()
+            .unwrap();
+        assert_eq!(
+            PeerId::parse(&multihash).unwrap().as_bytes(),
+            ED25519_PEER_ID
+        );
+    }
+
+    #[test]
+    fn reject_non_canonical_peer_ids() {
+        // the raw multihash parses, it is not the stored form
+        assert!(!PeerId::new_unchecked(SHA2_MULTIHASH.to_vec()).is_valid());
+
+        // a leading '1' is a leading zero byte of the multihash
+        let padded = [&b"1"[..], SHA2_PEER_ID].concat();
+        assert_eq!(PeerId::parse(&padded), Err(PeerIdError::InvalidMultihash));
+
+        // the code 0x12 as a two byte varint
+        let long_varint = [&[0x92, 0x00][..], &SHA2_MULTIHASH[1..]].concat();
+        assert_eq!(
+            PeerId::parse(&base58_encode(&long_varint)),
+            Err(PeerIdError::InvalidMultihash)
+        );
+
+        // 0, O, I and l are not base58
+        let mut typo = SHA2_PEER_ID.to_vec();
+        typo[4] = b'0';
+        assert_eq!(PeerId::parse(&typo), Err(PeerIdError::InvalidEncoding));
+        assert_eq!(PeerId::parse(b""), Err(PeerIdError::InvalidEncoding));
+    }
+
+    #[test]
+    fn reject_too_long_peer_ids() {
+        let long = [SHA2_PEER_ID, &[b'Q'; MAX_PEER_ID_LEN][..]].concat();
+        assert_eq!(PeerId::parse(&long), Err(PeerIdError::TooLong));
+        assert_eq!(
+            PeerId::parse(&[b'Q'; MAX_PEER_ID_LEN + 1]),
+            Err(PeerIdError::TooLong)
+        );
+        assert!(!PeerId::new_unchecked(long).is_valid());
+    }
+}
@@ -6,6 +6,8 @@ use sp_debug_derive::RuntimeDebug;
 use sp_runtime::traits::AtLeast32BitUnsigned;
 use sp_std::vec::Vec;
 
+use crate::p_peer_id::PeerId;
+
 /// ComputingResources
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -18,7 +20,7 @@ where
     /// provider account
     pub account_id: AccountId,
     /// computing resource link id
-    pub peer_id: Vec<u8>,
+    pub peer_id: PeerId,
     /// resource configuration
     pub config: ResourceConfig,
     /// resource rental statistics
@@ -36,7 +38,7 @@ where
     pub fn new(
         index: u64,
         account_id: AccountId,
-        peer_id: Vec<u8>,
+        peer_id: PeerId,
         config: ResourceConfig,
         rental_statistics: ResourceRentalStatistics,
         rental_info: ResourceRentalInfo<BlockNumber>,
@@ -91,13 +93,17 @@ impl ProviderPoints {
     pub fn add_points(&mut self, r_points: u64, d_points: u64) {
         self.duration_points = self.duration_points.saturating_add(d_points);
         self.resource_points = self.resource_points.saturating_add(r_points);
-        self.total_points = self.total_points.saturating_add(d_points.saturating_add(r_points) as u128);
+        self.total_points = self
+            .total_points
+            .saturating_add(d_points.saturating_add(r_points) as u128);
     }
 
     pub fn sub_points(&mut self, r_points: u64, d_points: u64) {
         self.duration_points = self.duration_points.saturating_sub(d_points);
         self.resource_points = self.resource_points.saturating_sub(r_points);
-        self.total_points = self.total_points.saturating_sub(d_points.saturating_add(r_points) as u128);
+        self.total_points = self
+            .total_points
+            .saturating_sub(d_points.saturating_add(r_points) as u128);
     }
 }
 
@@ -112,6 +118,107 @@ pub enum ResourceStatus {
     Unused,
     /// Disconnected
     Offline,
+    /// in use, no new orders, offlined when the agreement finishes
+    Draining,
+    /// recovering from offline, heartbeats without rentals before it is unused again
+    Probation,
+}
+
+/// why the resource status changed
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum StatusChangeReason {
+    /// registered by the provider
+    Registered,
+    /// locked by an order
+    Ordered,
+    /// rented by an agreement
+    Rented,
+    /// the order or the agreement ended
+    Released,
+    /// missed the heartbeat of an agreement
+    Faulted,
+    /// the provider stopped new orders
+    Draining,
+    /// the provider asked to recover the offline resource
+    RecoveryRequested,
+    /// heartbeats reported for the whole probation
+    ProbationPassed,
+}
+
+/// a status change of the resource
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct StatusChange<BlockNumber> {
+    /// new status
+    pub status: ResourceStatus,
+    /// why it changed
+    pub reason: StatusChangeReason,
+    /// block of the change
+    pub block: BlockNumber,
+}
+
+impl<BlockNumber> StatusChange<BlockNumber> {
+    pub fn new(status: ResourceStatus, reason: StatusChangeReason, block: BlockNumber) -> Self {
+        StatusChange {
+            status,
+            reason,
+            block,
+        }
+    }
+}
+
+/// probation of a recovering resource
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Probation<BlockNumber> {
+    /// block the probation started, it restarts when a heartbeat is late
+    pub start: BlockNumber,
+    /// block of the last heartbeat
+    pub last_heartbeat: BlockNumber,
+}
+
+impl<BlockNumber: Clone> Probation<BlockNumber> {
+    pub fn new(start: BlockNumber) -> Self {
+        Probation {
+            start: start.clone(),
+            last_heartbeat: start,
+        }
+    }
+}
+
+/// resource weights are expressed per mille
+pub const PER_MILLE: u64 = 1000;
+/// maximum weight of a resource dimension
+pub const MAX_RESOURCE_WEIGHT: u64 = 1_000_000;
+/// maximum length of the region code
+pub const MAX_REGION_LEN: usize = 16;
+/// maximum length of the accelerator description
+pub const MAX_ACCELERATOR_LEN: usize = 64;
+
+/// storage medium of the resource
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum StorageType {
+    /// not reported, resources registered before the storage was described
+    Unknown,
+    /// mechanical hard disk
+    Hdd,
+    /// sata solid state disk
+    Ssd,
+    /// nvme solid state disk
+    Nvme,
+}
+
+/// cpu architecture of the resource
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum CpuArch {
+    /// not reported, resources registered before the architecture was described
+    Unknown,
+    X86_64,
+    Aarch64,
+    Riscv64,
 }
 
 /// resource configuration
@@ -122,19 +229,269 @@ pub struct ResourceConfig {
     pub memory: u64,
     pub system: Vec<u8>,
     pub cpu_model: Vec<u8>,
+    /// storage size in GB
+    pub storage: u64,
+    /// storage medium
+    pub storage_type: StorageType,
+    /// network bandwidth in Mbps
+    pub bandwidth: u64,
+    /// cpu architecture
+    pub arch: CpuArch,
+    /// geographic region code, e.g. b"CN-SH"
+    pub region: Vec<u8>,
+    /// accelerator description, e.g. b"NVIDIA A100 40GB"
+    pub accelerator: Option<Vec<u8>>,
 }
 
 impl ResourceConfig {
-    pub fn new(cpu: u64, memory: u64, system: Vec<u8>, cpu_model: Vec<u8>) -> Self {
+    pub fn new(
+        cpu: u64,
+        memory: u64,
+        system: Vec<u8>,
+        cpu_model: Vec<u8>,
+        storage: u64,
+        storage_type: StorageType,
+        bandwidth: u64,
+        arch: CpuArch,
+        region: Vec<u8>,
+        accelerator: Option<Vec<u8>>,
+    ) -> Self {
         Self {
             cpu,
             memory,
             system,
             cpu_model,
+            storage,
+            storage_type,
+            bandwidth,
+            arch,
+            region,
+            accelerator,
+        }
+    }
+
+    /// points of the resource under the given weights
+    pub fn resource_points(&self, weights: &ResourceWeights) -> u64 {
+        let accelerator = if self.accelerator.is_some() { 1 } else { 0 };
+        ResourceWeights::weighted(self.cpu, weights.cpu)
+            .saturating_add(ResourceWeights::weighted(self.memory, weights.memory))
+            .saturating_add(ResourceWeights::weighted(self.storage, weights.storage))
+            .saturating_add(ResourceWeights::weighted(self.bandwidth, weights.bandwidth))
+            .saturating_add(ResourceWeights::weighted(accelerator, weights.accelerator))
+    }
+}
+
+/// resource configuration before the storage, bandwidth, architecture, region and accelerator were added
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct ResourceConfigV1 {
+    pub cpu: u64,
+    pub memory: u64,
+    pub system: Vec<u8>,
+    pub cpu_model: Vec<u8>,
+}
+
+impl From<ResourceConfigV1> for ResourceConfig {
+    fn from(old: ResourceConfigV1) -> Self {
+        ResourceConfig::new(
+            old.cpu,
+            old.memory,
+            old.system,
+            old.cpu_model,
+            0,
+            StorageType::Unknown,
+            0,
+            CpuArch::Unknown,
+            Vec::new(),
+            None,
+        )
+    }
+}
+
+/// one resource of a batch registration
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceSpec<Balance> {
+    /// computing resource link id
+    pub peer_id: PeerId,
+    /// resource configuration
+    pub config: ResourceConfig,
+    /// rental unit price
+    pub price: Balance,
+    /// provide rental time in hours
+    pub rent_duration_hour: u32,
+    /// index of a previous registration of the peer
+    pub new_index: u64,
+}
+
+impl<Balance> ResourceSpec<Balance> {
+    pub fn new(
+        peer_id: PeerId,
+        config: ResourceConfig,
+        price: Balance,
+        rent_duration_hour: u32,
+        new_index: u64,
+    ) -> Self {
+        ResourceSpec {
+            peer_id,
+            config,
+            price,
+            rent_duration_hour,
+            new_index,
         }
     }
 }
 
+/// per mille weights of the resource dimensions, used for the stake and the provider points
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceWeights {
+    /// per cpu
+    pub cpu: u64,
+    /// per GB of memory
+    pub memory: u64,
+    /// per GB of storage
+    pub storage: u64,
+    /// per Mbps of bandwidth
+    pub bandwidth: u64,
+    /// per accelerator
+    pub accelerator: u64,
+}
+
+impl Default for ResourceWeights {
+    /// a cpu or a GB of memory is one point, 100 GB of storage or 100 Mbps of bandwidth is one point,
+    /// an accelerator is eight points
+    fn default() -> Self {
+        ResourceWeights {
+            cpu: 1000,
+            memory: 1000,
+            storage: 10,
+            bandwidth: 10,
+            accelerator: 8000,
+        }
+    }
+}
+
+impl ResourceWeights {
+    pub fn new(cpu: u64, memory: u64, storage: u64, bandwidth: u64, accelerator: u64) -> Self {
+        ResourceWeights {
+            cpu,
+            memory,
+            storage,
+            bandwidth,
+            accelerator,
+        }
+    }
+
+    /// every weight is bounded and at least one dimension counts
+    pub fn is_valid(&self) -> bool {
+        let weights = [
+            self.cpu,
+            self.memory,
+            self.storage,
+            self.bandwidth,
+            self.accelerator,
+        ];
+        weights.iter().all(|w| *w <= MAX_RESOURCE_WEIGHT) && weights.iter().any(|w| *w > 0)
+    }
+
+    fn weighted(amount: u64, weight: u64) -> u64 {
+        let points = (amount as u128).saturating_mul(weight as u128) / PER_MILLE as u128;
+        points.min(u64::MAX as u128) as u64
+    }
+}
+
+/// accepted range of a resource dimension
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceLimit {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl ResourceLimit {
+    pub fn new(min: u64, max: u64) -> Self {
+        ResourceLimit { min, max }
+    }
+
+    pub fn contains(&self, amount: u64) -> bool {
+        self.min <= amount && amount <= self.max
+    }
+}
+
+/// accepted ranges of the registered resources
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceLimits {
+    /// number of cpus
+    pub cpu: ResourceLimit,
+    /// memory in GB
+    pub memory: ResourceLimit,
+    /// storage in GB
+    pub storage: ResourceLimit,
+    /// bandwidth in Mbps
+    pub bandwidth: ResourceLimit,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            cpu: ResourceLimit::new(1, 64),
+            memory: ResourceLimit::new(1, 256),
+            storage: ResourceLimit::new(0, u64::MAX),
+            bandwidth: ResourceLimit::new(0, u64::MAX),
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn new(
+        cpu: ResourceLimit,
+        memory: ResourceLimit,
+        storage: ResourceLimit,
+        bandwidth: ResourceLimit,
+    ) -> Self {
+        ResourceLimits {
+            cpu,
+            memory,
+            storage,
+            bandwidth,
+        }
+    }
+
+    /// every range is ordered and a resource has at least a cpu and some memory
+    pub fn is_valid(&self) -> bool {
+        [self.cpu, self.memory, self.storage, self.bandwidth]
+            .iter()
+            .all(|l| l.min <= l.max)
+            && self.cpu.min > 0
+            && self.memory.min > 0
+    }
+
+    /// whether the config is within the limits
+    pub fn contains(&self, config: &ResourceConfig) -> bool {
+        self.cpu.contains(config.cpu)
+            && self.memory.contains(config.memory)
+            && self.storage.contains(config.storage)
+            && self.bandwidth.contains(config.bandwidth)
+    }
+}
+
+/// stake and points recorded when the resource was registered
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceLock {
+    /// staked amount of the resource
+    pub staked: u128,
+    /// provider points of the resource
+    pub points: u64,
+}
+
+impl ResourceLock {
+    pub fn new(staked: u128, points: u64) -> Self {
+        ResourceLock { staked, points }
+    }
+}
+
 /// resource statistics
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -220,6 +577,150 @@ impl<BlockNumber> ResourceRentalInfo<BlockNumber> {
     }
 }
 
+/// most resources returned by one query
+pub const MAX_QUERY_LIMIT: u32 = 100;
+
+/// order of the resources returned by a query
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ResourceSort {
+    /// by resource index
+    Index,
+    /// cheapest first
+    PriceAsc,
+    /// most expensive first
+    PriceDesc,
+}
+
+impl Default for ResourceSort {
+    fn default() -> Self {
+        ResourceSort::Index
+    }
+}
+
+/// resource catalogue query, the conditions left empty match every resource
+#[derive(PartialEq, Eq, Clone, Default, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(default))]
+pub struct ResourceQuery {
+    /// resource status
+    pub status: Option<ResourceStatus>,
+    /// least cpu cores
+    pub min_cpu: Option<u64>,
+    /// least memory
+    pub min_memory: Option<u64>,
+    /// highest rental unit price
+    pub max_price: Option<u128>,
+    /// operating system
+    pub system: Option<Vec<u8>>,
+    /// cpu model
+    pub cpu_model: Option<Vec<u8>>,
+    /// least hours left before the end of rent
+    pub min_remaining_hours: Option<u32>,
+    /// order of the result
+    pub sort: ResourceSort,
+    /// resources skipped before the page
+    pub offset: u32,
+    /// page size, zero or anything above MAX_QUERY_LIMIT is MAX_QUERY_LIMIT
+    pub limit: u32,
+}
+
+impl ResourceQuery {
+    /// whether the resource matches the conditions, the remaining blocks are `end_of_rent` minus now
+    pub fn matches(
+        &self,
+        config: &ResourceConfig,
+        status: &ResourceStatus,
+        price: u128,
+        remaining_blocks: u64,
+    ) -> bool {
+        self.status.map_or(true, |x| &x == status)
+            && self.min_cpu.map_or(true, |x| config.cpu >= x)
+            && self.min_memory.map_or(true, |x| config.memory >= x)
+            && self.max_price.map_or(true, |x| price <= x)
+            && self.system.as_ref().map_or(true, |x| x == &config.system)
+            && self
+                .cpu_model
+                .as_ref()
+                .map_or(true, |x| x == &config.cpu_model)
+            && self
+                .min_remaining_hours
+                .map_or(true, |x| remaining_blocks >= (x as u64).saturating_mul(600))
+    }
+}
+
+/// reputation of a provider or a resource without history
+pub const NEUTRAL_REPUTATION: u32 = 500;
+/// highest reputation
+pub const MAX_REPUTATION: u32 = 1000;
+
+/// what changed the reputation
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ReputationEvent {
+    /// an order was executed
+    Rented,
+    /// an agreement was completed
+    Completed,
+    /// an agreement was punished [fault duration in hours]
+    Faulted(u32),
+}
+
+/// reputation score, it decays towards the neutral score over time
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Reputation<BlockNumber> {
+    /// score from 0 to MAX_REPUTATION
+    pub score: u32,
+    /// block of the last update
+    pub updated: BlockNumber,
+}
+
+impl<BlockNumber> Reputation<BlockNumber> {
+    pub fn new(updated: BlockNumber) -> Self {
+        Reputation {
+            score: NEUTRAL_REPUTATION,
+            updated,
+        }
+    }
+
+    /// move a tenth of the way to the neutral score for every decay period
+    pub fn decay(&mut self, periods: u32) {
+        for _ in 0..periods.min(64) {
+            if self.score > NEUTRAL_REPUTATION {
+                self.score -= (self.score - NEUTRAL_REPUTATION + 9) / 10;
+            } else {
+                self.score += (NEUTRAL_REPUTATION - self.score + 9) / 10;
+            }
+        }
+        if periods >= 64 {
+            self.score = NEUTRAL_REPUTATION;
+        }
+    }
+
+    /// apply the event to the score
+    pub fn apply(&mut self, event: ReputationEvent) {
+        self.score = match event {
+            ReputationEvent::Rented => self.score.saturating_add(5),
+            ReputationEvent::Completed => self.score.saturating_add(20),
+            ReputationEvent::Faulted(hours) => self
+                .score
+                .saturating_sub(hours.saturating_mul(10).saturating_add(100)),
+        }
+        .min(MAX_REPUTATION);
+    }
+}
+
 pub trait ProviderInterface<AccountId> {
+    /// accrue the duration points of the resources online up to now, at the end of the era
+    fn accrue_duration_points();
+
+    /// [(provider, points)], total resource points, total duration points
     fn get_providers_points() -> (Vec<(AccountId, ProviderPoints)>, u128, u128);
+
+    /// start a new era of duration points
+    fn reset_duration_points();
+
+    /// current reputation of the provider
+    fn get_provider_reputation(who: &AccountId) -> u32;
 }
@@ -3,13 +3,16 @@ use frame_support::Parameter;
 
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
-use sp_core::Bytes;
+use sp_core::{Bytes, H256};
 use sp_debug_derive::RuntimeDebug;
+use sp_runtime::helpers_128bit::multiply_by_rational;
 use sp_runtime::traits::AtLeast32BitUnsigned;
+use sp_runtime::Perbill;
 use sp_std::convert::TryInto;
 use sp_std::vec::Vec;
 
-use crate::p_provider::{ComputingResource, ResourceConfig, ResourceRentalInfo};
+use crate::p_peer_id::PeerId;
+use crate::p_provider::{ComputingResource, ReputationEvent, ResourceConfig, ResourceRentalInfo};
 use sp_core::sp_std::time::Duration;
 
 /// resourceOrder
@@ -21,7 +24,7 @@ pub struct ResourceOrder<AccountId, BlockNumber> {
     /// TenantInformation
     pub tenant_info: TenantInfo<AccountId>,
     /// OrderAmount
-    // pub price: u128,
+    pub price: u128,
     /// ResourceIndex
     pub resource_index: u64,
     /// BlockAtCreationTime
@@ -46,6 +49,71 @@ pub struct TenantInfo<AccountId> {
     pub public_key: Bytes,
 }
 
+/// maximum length of the image digest of a manifest
+pub const MAX_IMAGE_DIGEST_LEN: usize = 128;
+/// maximum number of ports exposed by a manifest
+pub const MAX_MANIFEST_PORTS: usize = 16;
+/// maximum length of an encrypted manifest
+pub const MAX_ENCRYPTED_MANIFEST_LEN: usize = 1024;
+
+/// what the provider deploys on the resource of the order
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct DeploymentManifest {
+    /// digest of the container image, e.g. sha256:...
+    pub image_digest: Vec<u8>,
+    /// ports exposed by the container
+    pub ports: Vec<u16>,
+    /// cpu cores the container may use
+    pub cpu_limit: u64,
+    /// memory (G) the container may use
+    pub memory_limit: u64,
+    /// hash of the environment blob, the blob itself travels out-of-band
+    pub env_hash: H256,
+}
+
+/// deployment manifest of an order or an agreement
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Manifest {
+    /// readable by everyone
+    Plain(DeploymentManifest),
+    /// encoded manifest encrypted to the provider
+    Encrypted(Vec<u8>),
+}
+
+impl Manifest {
+    /// whether the manifest is within the bounds
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Manifest::Plain(manifest) => {
+                !manifest.image_digest.is_empty()
+                    && manifest.image_digest.len() <= MAX_IMAGE_DIGEST_LEN
+                    && manifest.ports.len() <= MAX_MANIFEST_PORTS
+            }
+            Manifest::Encrypted(data) => {
+                !data.is_empty() && data.len() <= MAX_ENCRYPTED_MANIFEST_LEN
+            }
+        }
+    }
+}
+
+/// maximum length of the sealed access info of an agreement
+pub const MAX_ACCESS_INFO_LEN: usize = 1024;
+
+/// how the tenant reaches the machine of an agreement (address, ssh port, credentials),
+/// sealed by the provider to the public key of the tenant
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct AccessInfo<BlockNumber> {
+    /// incremented every time the provider rotates the access info, starts at 1
+    pub version: u32,
+    /// sealed box of the access info
+    pub payload: Vec<u8>,
+    /// block the version was published at
+    pub block: BlockNumber,
+}
+
 /// LeaseAgreement
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -60,13 +128,15 @@ where
     /// TenantInformation
     pub tenant_info: TenantInfo<AccountId>,
     /// ComputingResourceLinkID
-    pub peer_id: Vec<u8>,
+    pub peer_id: PeerId,
     /// ResourceIndex
     pub resource_index: u64,
     /// ResourceConfiguration
     pub config: ResourceConfig,
     /// ResourceRentalInformation
     pub rental_info: ResourceRentalInfo<BlockNumber>,
+    /// RentAmount held in escrow for this agreement
+    pub price: u128,
     /// PenaltyAmount
     pub penalty_amount: u128,
     /// ReceiveAmount
@@ -104,6 +174,8 @@ pub enum OrderStatus {
     Finished,
     /// Canceled
     Canceled,
+    /// the free resource trial has ended
+    Expired,
 }
 
 #[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
@@ -122,6 +194,7 @@ impl<AccountId, BlockNumber> ResourceOrder<AccountId, BlockNumber> {
     pub fn new(
         index: u64,
         tenant_info: TenantInfo<AccountId>,
+        price: u128,
         resource_index: u64,
         create: BlockNumber,
         rent_duration: BlockNumber,
@@ -130,6 +203,7 @@ impl<AccountId, BlockNumber> ResourceOrder<AccountId, BlockNumber> {
         ResourceOrder {
             index,
             tenant_info,
+            price,
             resource_index,
             create,
             rent_duration,
@@ -143,6 +217,7 @@ impl<AccountId, BlockNumber> ResourceOrder<AccountId, BlockNumber> {
     pub fn renew(
         index: u64,
         tenant_info: TenantInfo<AccountId>,
+        price: u128,
         resource_index: u64,
         create: BlockNumber,
         rent_duration: BlockNumber,
@@ -152,6 +227,7 @@ impl<AccountId, BlockNumber> ResourceOrder<AccountId, BlockNumber> {
         ResourceOrder {
             index,
             tenant_info,
+            price,
             resource_index,
             create,
             rent_duration,
@@ -188,10 +264,11 @@ where
         index: u64,
         provider: AccountId,
         tenant_info: TenantInfo<AccountId>,
-        peer_id: Vec<u8>,
+        peer_id: PeerId,
         resource_index: u64,
         config: ResourceConfig,
         rental_info: ResourceRentalInfo<BlockNumber>,
+        price: u128,
         penalty_amount: u128,
         receive_amount: u128,
         start: BlockNumber,
@@ -207,6 +284,7 @@ where
             resource_index,
             config,
             rental_info,
+            price,
             penalty_amount,
             receive_amount,
             start,
@@ -218,40 +296,87 @@ where
     }
 
     /// ExecuteTheAgreement
-    pub fn execution(&mut self, block_number: &BlockNumber) -> bool {
+    /// return the amount released to the provider since the last report
+    pub fn execution(&mut self, block_number: &BlockNumber) -> Option<u128> {
         // determine whether the agreement is punished
         if self.status != AgreementStatus::Using {
-            return false;
+            return None;
         }
 
-        // get order duration
-        let _duration = TryInto::<u128>::try_into(self.end.clone() - self.start.clone())
-            .ok()
-            .unwrap();
+        // the rent that has not been released yet
+        let remaining = self.remaining_amount();
+        let amount;
         //if the current block protocol has not ended
         if block_number < &self.end {
-            // (The current block - the last reported block) The total block duration of the protocol Order Amount = Amount obtained during this period
-            let this_block = block_number.clone() - self.calculation.clone();
-            // calculate the number of blocks
-            let _this_block = TryInto::<u128>::try_into(this_block).ok().unwrap();
+            // (The current block - the last reported block) / (end block - the last reported block) * unreleased amount = Amount obtained during this period
+            let this_block =
+                TryInto::<u128>::try_into(block_number.clone() - self.calculation.clone())
+                    .unwrap_or(0);
+            // calculate the number of blocks left in the agreement
+            let rest_block =
+                TryInto::<u128>::try_into(self.end.clone() - self.calculation.clone()).unwrap_or(0);
             // calculate the amount earned during this period
+            amount = multiply_by_rational(remaining, this_block, rest_block).unwrap_or(0);
 
             self.calculation = block_number.clone();
         } else {
-            // end of current agreement
+            // end of current agreement, release all the rest
+            amount = remaining;
             self.calculation = self.end.clone();
         }
 
-        true
+        self.receive_amount = self.receive_amount.saturating_add(amount);
+        Some(amount)
+    }
+
+    /// the rent still held in escrow
+    pub fn remaining_amount(&self) -> u128 {
+        self.price
+            .saturating_sub(self.receive_amount)
+            .saturating_sub(self.penalty_amount)
+    }
+
+    /// punish the agreement, the provider forfeits the unreleased rent
+    /// return the amount to refund to the tenant
+    pub fn punish(&mut self) -> u128 {
+        let forfeit = self.remaining_amount();
+        self.penalty_amount = self.penalty_amount.saturating_add(forfeit);
+        self.status = AgreementStatus::Punished;
+        forfeit
+    }
+
+    /// terminate the agreement before the end
+    /// the rent of the served blocks and the fee share of the rest go to the provider, the remainder is refunded
+    /// return (amount released to the provider, amount refunded to the tenant)
+    pub fn terminate(&mut self, block_number: &BlockNumber, fee: Perbill) -> Option<(u128, u128)> {
+        // pay for the blocks served so far
+        let served = self.execution(block_number)?;
+
+        // early termination fee
+        let remaining = self.remaining_amount();
+        let fee_amount = fee * remaining;
+        self.receive_amount = self.receive_amount.saturating_add(fee_amount);
+        let refund = remaining.saturating_sub(fee_amount);
+
+        // the refunded rent is no longer part of the agreement
+        self.price = self.price.saturating_sub(refund);
+        if block_number < &self.end {
+            self.end = block_number.clone();
+        }
+        self.status = AgreementStatus::Finished;
+
+        Some((served.saturating_add(fee_amount), refund))
     }
 
     /// Renewal
     pub fn renew(
         &mut self,
         duration: BlockNumber,
+        price: u128,
         resource_config: ComputingResource<BlockNumber, AccountId>,
     ) {
         // negotiated price increase
+        self.price = self.price.saturating_add(price);
         // agreement end deadline increased
         self.end += duration;
         // update protocol resource snapshot
@@ -343,9 +468,220 @@ pub trait OrderInterface {
         index: u64,
         resource_info: ComputingResource<Self::BlockNumber, Self::AccountId>,
     );
+
+    /// amount staked for the resource when it was registered
+    fn get_resource_staked_amount(index: u64) -> u128;
+
+    /// offline the resource: unlock its staking and remove its points
+    fn offline_computing_resource(index: u64);
+
+    /// update the reputation of the resource and its provider
+    fn update_reputation(index: u64, event: ReputationEvent);
+
+    /// the cheapest unused resource meeting the requirement whose rent ends after the block,
+    /// and the number of resources read to find it
+    fn match_resource(
+        requirement: &ResourceRequirement,
+        end: Self::BlockNumber,
+    ) -> (Option<u64>, u64);
 }
 
-/// resourceOrder
+/// what a tenant needs from the resource of an order matched by the pallet
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ResourceRequirement {
+    /// least cpu cores
+    pub cpu: u64,
+    /// least memory (G)
+    pub memory: u64,
+    /// operating system
+    pub system: Vec<u8>,
+    /// highest rental unit price
+    pub max_price: u128,
+    /// lease duration (hours)
+    pub rent_duration: u32,
+}
+
+impl ResourceRequirement {
+    pub fn new(
+        cpu: u64,
+        memory: u64,
+        system: Vec<u8>,
+        max_price: u128,
+        rent_duration: u32,
+    ) -> Self {
+        ResourceRequirement {
+            cpu,
+            memory,
+            system,
+            max_price,
+            rent_duration,
+        }
+    }
+
+    /// whether the resource config is enough, the price is checked by the caller
+    pub fn matches(&self, config: &ResourceConfig) -> bool {
+        config.cpu >= self.cpu && config.memory >= self.memory && config.system == self.system
+    }
+
+    /// the most the order can cost, held until a resource is matched
+    pub fn max_cost(&self) -> u128 {
+        self.max_price.saturating_mul(self.rent_duration as u128)
+    }
+}
+
+/// order waiting for a resource meeting its requirement
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct WaitingOrder<AccountId, BlockNumber> {
+    /// index of the order once it is matched
+    pub index: u64,
+    /// TenantInformation
+    pub tenant_info: TenantInfo<AccountId>,
+    /// what the resource must meet
+    pub requirement: ResourceRequirement,
+    /// BlockAtCreationTime
+    pub create: BlockNumber,
+    /// block after which the order is canceled and the escrow refunded
+    pub deadline: BlockNumber,
+}
+
+/// resource of a cluster order
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ClusterMember {
+    /// the resource of the index
+    Resource(u64),
+    /// the cheapest unused resource meeting the spec
+    Spec {
+        /// least cpu cores
+        cpu: u64,
+        /// least memory (G)
+        memory: u64,
+        /// operating system
+        system: Vec<u8>,
+        /// highest rental unit price
+        max_price: u128,
+    },
+}
+
+/// orders of several resources rented together, their agreements share the cluster index
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ClusterOrder<AccountId, BlockNumber> {
+    /// cluster index
+    pub index: u64,
+    /// tenant
+    pub tenant: AccountId,
+    /// orders of the resources, in the order of the members
+    pub orders: Vec<u64>,
+    /// agreements created as the providers execute the orders
+    pub agreements: Vec<u64>,
+    /// lease duration (hours)
+    pub rent_duration: u32,
+    /// BlockAtCreationTime
+    pub create: BlockNumber,
+    /// whether the cluster is still rented whole
+    pub status: ClusterStatus,
+}
+
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ClusterStatus {
+    /// the orders are pending or executed
+    Active,
+    /// an order timed out, the cluster has been canceled and refunded
+    Failed,
+}
+
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum ComputeRequestStatus {
+    /// providers bid until the deadline
+    Bidding,
+    /// the lowest valid bid has been turned into an agreement
+    Settled,
+    /// no valid bid, the escrow has been refunded
+    Unfilled,
+}
+
+/// compute request the providers bid on, the lowest bid wins at the deadline
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct ComputeRequest<AccountId, BlockNumber> {
+    /// request index
+    pub index: u64,
+    /// TenantInformation
+    pub tenant_info: TenantInfo<AccountId>,
+    /// what the resource must meet, max_price is the highest unit price accepted
+    pub requirement: ResourceRequirement,
+    /// BlockAtCreationTime
+    pub create: BlockNumber,
+    /// last block the bids are accepted at
+    pub deadline: BlockNumber,
+    /// order created for the winning bid
+    pub order_index: Option<u64>,
+    /// request status
+    pub status: ComputeRequestStatus,
+}
+
+impl<AccountId, BlockNumber> ComputeRequest<AccountId, BlockNumber> {
+    pub fn new(
+        index: u64,
+        tenant_info: TenantInfo<AccountId>,
+        requirement: ResourceRequirement,
+        create: BlockNumber,
+        deadline: BlockNumber,
+    ) -> Self {
+        ComputeRequest {
+            index,
+            tenant_info,
+            requirement,
+            create,
+            deadline,
+            order_index: None,
+            status: ComputeRequestStatus::Bidding,
+        }
+    }
+
+    pub fn settle(&mut self, order_index: u64) {
+        self.order_index = Some(order_index);
+        self.status = ComputeRequestStatus::Settled;
+    }
+
+    pub fn unfilled(&mut self) {
+        self.status = ComputeRequestStatus::Unfilled;
+    }
+}
+
+#[derive(Encode, Decode, RuntimeDebug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum BidStatus {
+    /// the resource is locked until the request is settled
+    Pending,
+    /// the bid won the request
+    Won,
+    /// the bid lost or could not be executed, the resource has been released
+    Lost,
+}
+
+/// bid of a provider on a compute request, kept after the settlement as price history
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Bid<AccountId, BlockNumber> {
+    /// provider or operator who placed the bid
+    pub bidder: AccountId,
+    /// resource offered
+    pub resource_index: u64,
+    /// rental unit price offered
+    pub unit_price: u128,
+    /// block the bid was placed at
+    pub block: BlockNumber,
+    /// bid status
+    pub status: BidStatus,
+}
+
+/// free resource apply order
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct ApplyOrder<AccountId, BlockNumber> {
@@ -354,11 +690,23 @@ pub struct ApplyOrder<AccountId, BlockNumber> {
     /// provider
     pub provider: AccountId,
     /// peer_id
-    pub peer_id: Vec<u8>,
+    pub peer_id: PeerId,
+    /// resource of the trial, set when the order is processed
+    pub resource_index: u64,
     /// TenantInformation
     pub tenant_info: TenantInfo<AccountId>,
+    /// number of cpu cores applied
+    pub cpu: u64,
+    /// memory applied (G)
+    pub memory: u64,
+    /// trial duration (hours)
+    pub rent_duration: u32,
+    /// deploy type
+    pub deploy_type: u32,
     /// BlockAtCreationTime
     pub create: BlockNumber,
+    /// block at which the trial expires, set when the order is processed
+    pub end: BlockNumber,
     /// Timestamp
     pub time: Duration,
     /// OrderStatus
@@ -373,6 +721,10 @@ where
     pub fn new(
         index: u64,
         tenant_info: TenantInfo<AccountId>,
+        cpu: u64,
+        memory: u64,
+        rent_duration: u32,
+        deploy_type: u32,
         create: BlockNumber,
         time: Duration,
     ) -> Self {
@@ -380,18 +732,36 @@ where
             index,
             provider: Default::default(),
             peer_id: Default::default(),
+            resource_index: 0,
             tenant_info,
+            cpu,
+            memory,
+            rent_duration,
+            deploy_type,
             create,
+            end: Default::default(),
             time,
             status: OrderStatus::Pending,
         }
     }
 
-    pub fn processed(&mut self, provider: AccountId, peer_id: Vec<u8>) {
+    pub fn processed(
+        &mut self,
+        provider: AccountId,
+        peer_id: PeerId,
+        resource_index: u64,
+        end: BlockNumber,
+    ) {
         self.provider = provider;
         self.peer_id = peer_id;
+        self.resource_index = resource_index;
+        self.end = end;
         self.status = OrderStatus::Finished
     }
+
+    pub fn expire(&mut self) {
+        self.status = OrderStatus::Expired
+    }
 }
 
 pub trait ResourceOrderInterface<AccountId, BlockNumber>
@@ -399,4 +769,18 @@ where
     BlockNumber: Parameter + AtLeast32BitUnsigned,
 {
     fn get_rental_agreements() -> Vec<(u64, RentalAgreement<AccountId, BlockNumber>)>;
+
+    /// the agreements of the resource are served by the new provider
+    fn transfer_agreements(resource_index: u64, from: AccountId, to: AccountId);
+}
+
+impl<AccountId, BlockNumber> ResourceOrderInterface<AccountId, BlockNumber> for ()
+where
+    BlockNumber: Parameter + AtLeast32BitUnsigned,
+{
+    fn get_rental_agreements() -> Vec<(u64, RentalAgreement<AccountId, BlockNumber>)> {
+        Vec::new()
+    }
+
+    fn transfer_agreements(_resource_index: u64, _from: AccountId, _to: AccountId) {}
 }
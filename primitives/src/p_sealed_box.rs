@@ -0,0 +1,169 @@
+//! sealed boxes compatible with libsodium crypto_box_seal (X25519, XSalsa20-Poly1305),
+//! the providers seal the access info of an agreement to the public key of the tenant
+
+use blake2_rfc::blake2b::blake2b;
+use poly1305::universal_hash::NewUniversalHash;
+use poly1305::Poly1305;
+use salsa20::cipher::generic_array::GenericArray;
+use salsa20::cipher::{NewCipher, StreamCipher};
+use salsa20::{hsalsa20, Key, XNonce, XSalsa20};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// length of an X25519 key
+pub const KEY_LEN: usize = 32;
+/// length of the poly1305 tag
+pub const TAG_LEN: usize = 16;
+/// bytes a sealed box adds to the payload: the ephemeral public key and the tag
+pub const SEAL_OVERHEAD: usize = KEY_LEN + TAG_LEN;
+/// length of the xsalsa20 nonce
+const NONCE_LEN: usize = 24;
+
+/// why the payload cannot be sealed or opened
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SealError {
+    /// the public key is not 32 bytes
+    InvalidKey,
+    /// shorter than the ephemeral public key and the tag
+    TooShort,
+    /// not sealed to this key, or modified
+    Forged,
+}
+
+/// X25519 public key of the secret key
+pub fn public_key(secret: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    PublicKey::from(&StaticSecret::from(*secret)).to_bytes()
+}
+
+/// seal the payload to the X25519 public key of the recipient.
+/// `ephemeral` is the only randomness of the box, it must be fresh random bytes for every payload
+pub fn seal(
+    payload: &[u8],
+    recipient: &[u8],
+    ephemeral: [u8; KEY_LEN],
+) -> Result<Vec<u8>, SealError> {
+    let recipient = to_public_key(recipient)?;
+    let ephemeral = StaticSecret::from(ephemeral);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let nonce = seal_nonce(&ephemeral_public, &recipient);
+    let key = box_key(&ephemeral, &recipient);
+
+    // ephemeral public key | tag | encrypted payload
+    let mut sealed = Vec::with_capacity(SEAL_OVERHEAD + payload.len());
+    sealed.extend_from_slice(ephemeral_public.as_bytes());
+    sealed.extend_from_slice(&[0u8; TAG_LEN]);
+    sealed.extend_from_slice(payload);
+
+    let (head, body) = sealed.split_at_mut(SEAL_OVERHEAD);
+    let mut cipher = XSalsa20::new(&key, XNonce::from_slice(&nonce));
+    let mac = mac_key(&mut cipher);
+    cipher.apply_keystream(body);
+    let tag = mac.compute_unpadded(body).into_bytes();
+    head[KEY_LEN..].copy_from_slice(&tag);
+
+    Ok(sealed)
+}
+
+/// open a sealed box with the X25519 secret key of the recipient
+pub fn open(sealed: &[u8], secret: &[u8; KEY_LEN]) -> Result<Vec<u8>, SealError> {
+    if sealed.len() < SEAL_OVERHEAD {
+        return Err(SealError::TooShort);
+    }
+    let secret = StaticSecret::from(*secret);
+    let recipient = PublicKey::from(&secret);
+    let ephemeral_public = to_public_key(&sealed[..KEY_LEN])?;
+    let nonce = seal_nonce(&ephemeral_public, &recipient);
+    let key = box_key(&secret, &ephemeral_public);
+
+    let mut payload = sealed[SEAL_OVERHEAD..].to_vec();
+    let mut cipher = XSalsa20::new(&key, XNonce::from_slice(&nonce));
+    let mac = mac_key(&mut cipher);
+    // the output of poly1305 compares in constant time
+    let tag = poly1305::Tag::new(GenericArray::clone_from_slice(
+        &sealed[KEY_LEN..SEAL_OVERHEAD],
+    ));
+    if mac.compute_unpadded(&payload) != tag {
+        return Err(SealError::Forged);
+    }
+    cipher.apply_keystream(&mut payload);
+
+    Ok(payload)
+}
+
+fn to_public_key(bytes: &[u8]) -> Result<PublicKey, SealError> {
+    if bytes.len() != KEY_LEN {
+        return Err(SealError::InvalidKey);
+    }
+    let mut key = [0u8; KEY_LEN];
+    key.copy_from_slice(bytes);
+    Ok(PublicKey::from(key))
+}
+
+/// the nonce of a sealed box is the blake2b-192 of the ephemeral and the recipient public keys
+fn seal_nonce(ephemeral: &PublicKey, recipient: &PublicKey) -> [u8; NONCE_LEN] {
+    let mut input = [0u8; 2 * KEY_LEN];
+    input[..KEY_LEN].copy_from_slice(ephemeral.as_bytes());
+    input[KEY_LEN..].copy_from_slice(recipient.as_bytes());
+
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(blake2b(NONCE_LEN, &[], &input).as_bytes());
+    nonce
+}
+
+/// crypto_box key: hsalsa20 of the X25519 shared secret
+fn box_key(secret: &StaticSecret, public: &PublicKey) -> Key {
+    let shared = secret.diffie_hellman(public);
+    hsalsa20(Key::from_slice(shared.as_bytes()), &GenericArray::default())
+}
+
+/// the first 32 bytes of the keystream key poly1305, the rest encrypts the payload
+fn mac_key(cipher: &mut XSalsa20) -> Poly1305 {
+    let mut key = [0u8; 32];
+    cipher.apply_keystream(&mut key);
+    Poly1305::new(poly1305::Key::from_slice(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    // X25519 keys of RFC 7748 section 6.1
+    const ALICE_SECRET: [u8; 32] =
+        hex!("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+    const BOB_SECRET: [u8; 32] =
+        hex!("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+    const BOB_PUBLIC: [u8; 32] =
+        hex!("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+    const PAYLOAD: &[u8] = b"ssh root@10.0.0.8 -p 2222";
+    // the payload sealed to bob with the ephemeral key of alice, as libsodium does
+    const SEALED: [u8; 73] = hex!("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a5f3c2cac50a84e61943a7e5768f91268d474c4e69a976c2590577b54dd551965577873069c742bf6cb");
+
+    #[test]
+    fn seal_matches_test_vector() {
+        assert_eq!(public_key(&BOB_SECRET), BOB_PUBLIC);
+
+        let sealed = seal(PAYLOAD, &BOB_PUBLIC, ALICE_SECRET).unwrap();
+        assert_eq!(sealed.len(), PAYLOAD.len() + SEAL_OVERHEAD);
+        assert_eq!(sealed, SEALED.to_vec());
+    }
+
+    #[test]
+    fn open_matches_test_vector() {
+        assert_eq!(open(&SEALED, &BOB_SECRET).unwrap(), PAYLOAD.to_vec());
+
+        // only the recipient opens the box
+        assert_eq!(open(&SEALED, &ALICE_SECRET), Err(SealError::Forged));
+        // a modified box is rejected
+        let mut forged = SEALED;
+        forged[SEAL_OVERHEAD] ^= 1;
+        assert_eq!(open(&forged, &BOB_SECRET), Err(SealError::Forged));
+        assert_eq!(
+            open(&SEALED[..SEAL_OVERHEAD - 1], &BOB_SECRET),
+            Err(SealError::TooShort)
+        );
+        assert_eq!(
+            seal(PAYLOAD, &BOB_PUBLIC[..31], ALICE_SECRET),
+            Err(SealError::InvalidKey)
+        );
+    }
+}
@@ -143,10 +143,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     //   `spec_version`, and `authoring_version` are the same between Wasm and native.
     // This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
     //   the compatible custom types.
-    spec_version: 105,
+    spec_version: 106,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    transaction_version: 2,
 };
 
 /// This determines the average expected block time that we are targeting.
@@ -594,12 +594,39 @@ impl pallet_authority_discovery::Config for Runtime {}
 parameter_types! {
     // polling interval
     pub const ResourceInterval: BlockNumber = 3 * HOURS;
+    // maximum number of resources expired in a block
+    pub const MaxExpirationsPerBlock: u32 = 500;
+    // the reputation decays a tenth of the way to neutral every period
+    pub const ReputationDecayPeriod: BlockNumber = DAYS;
+    pub const MaxBatchResources: u32 = 50;
+    pub const ProbationPeriod: BlockNumber = HOURS;
+    pub const ProbationHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // the auto extension is attempted this long before the end of rent
+    pub const AutoExtendWindow: BlockNumber = HOURS;
+    pub const MaxStatusHistory: u32 = 20;
     // health check interval
     pub const HealthCheckInterval: BlockNumber = 10 * MINUTES;
     // gateway node timed removal interval
     pub const GatewayNodeTimedRemovalInterval: BlockNumber = 30 * MINUTES;
     //gateway node heartbeat reporting interval
     pub const GatewayNodeHeartbeatInterval: BlockNumber = 10 * MINUTES;
+    // maximum duration of a free resource trial (hours)
+    pub const MaxFreeResourceDuration: u32 = 24;
+    // maximum number of agreements checked for missed heartbeats in one block
+    pub const MaxHealthChecksPerBlock: u32 = 500;
+    // share of the remaining rent paid to the provider on early termination
+    pub const EarlyTerminationFee: Perbill = Perbill::from_percent(10);
+    // how long an order waits for a resource meeting its requirement
+    pub const OrderMatchWaitingTime: BlockNumber = 2 * HOURS;
+    pub const MaxWaitingOrders: u32 = 100;
+    // waiting orders matched in a block
+    pub const MaxWaitingOrderMatches: u32 = 10;
+    // longest bidding on a compute request
+    pub const MaxBiddingPeriod: BlockNumber = DAYS;
+    pub const MaxBidsPerRequest: u32 = 50;
+    pub const MaxComputeRequestsPerBlock: u32 = 100;
+    // maximum number of resources in a cluster order
+    pub const MaxClusterSize: u32 = 16;
 }
 
 /// ResourceOrder
@@ -611,8 +638,21 @@ impl pallet_resource_order::Config for Runtime {
     type NumberToBalance = ConvertInto;
     type BalanceToNumber = ConvertInto;
     type HealthCheckInterval = HealthCheckInterval;
+    type OrderWaitingTime = OrderWaitingTime;
+    type MaxHealthChecksPerBlock = MaxHealthChecksPerBlock;
+    type EarlyTerminationFee = EarlyTerminationFee;
+    type OperatorInterface = Market;
     type UnixTime = Timestamp;
     type MarketInterface = Market;
+    type FreeResourceOrigin = EnsureRoot<AccountId>;
+    type MaxFreeResourceDuration = MaxFreeResourceDuration;
+    type OrderMatchWaitingTime = OrderMatchWaitingTime;
+    type MaxWaitingOrders = MaxWaitingOrders;
+    type MaxWaitingOrderMatches = MaxWaitingOrderMatches;
+    type MaxBiddingPeriod = MaxBiddingPeriod;
+    type MaxBidsPerRequest = MaxBidsPerRequest;
+    type MaxComputeRequestsPerBlock = MaxComputeRequestsPerBlock;
+    type MaxClusterSize = MaxClusterSize;
 }
 
 impl pallet_provider::Config for Runtime {
@@ -622,6 +662,15 @@ impl pallet_provider::Config for Runtime {
     type NumberToBalance = ConvertInto;
     type ResourceInterval = ResourceInterval;
     type MarketInterface = Market;
+    type MaxExpirationsPerBlock = MaxExpirationsPerBlock;
+    type ReputationDecayPeriod = ReputationDecayPeriod;
+    type MaxBatchResources = MaxBatchResources;
+    type ProbationPeriod = ProbationPeriod;
+    type ProbationHeartbeatInterval = ProbationHeartbeatInterval;
+    type MaxStatusHistory = MaxStatusHistory;
+    type AutoExtendWindow = AutoExtendWindow;
+    type ResourceOrderInterface = ResourceOrder;
+    type OperatorInterface = Market;
 }
 
 impl pallet_gateway::Config for Runtime {
@@ -633,6 +682,7 @@ impl pallet_gateway::Config for Runtime {
     type GatewayNodeTimedRemovalInterval = GatewayNodeTimedRemovalInterval;
     type GatewayNodeHeartbeatInterval = GatewayNodeHeartbeatInterval;
     type MarketInterface = Market;
+    type OperatorInterface = Market;
 }
 
 parameter_types! {
@@ -648,6 +698,7 @@ impl pallet_chunkcycle::Config for Runtime {
     type BlockNumberToNumber = ConvertInto;
     type MarketInterface = Market;
     type GatewayInterface = Gateway;
+    type ProviderInterface = Provider;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -842,6 +893,20 @@ impl_runtime_apis! {
         }
     }
 
+    impl pallet_provider_rpc_runtime_api::ProviderApi<Block, AccountId, BlockNumber> for Runtime {
+        fn resources(
+            query: pallet_provider_rpc_runtime_api::ResourceQuery,
+        ) -> Vec<pallet_provider_rpc_runtime_api::ComputingResource<BlockNumber, AccountId>> {
+            Provider::query_resources(query)
+        }
+    }
+
+    impl pallet_resource_order_rpc_runtime_api::ResourceOrderApi<Block, AccountId> for Runtime {
+        fn user_cluster_agreements(who: AccountId) -> Vec<(u64, Option<u64>)> {
+            ResourceOrder::user_cluster_agreements(who)
+        }
+    }
+
     #[cfg(feature = "runtime-benchmarks")]
     impl frame_benchmarking::Benchmark<Block> for Runtime {
         fn dispatch_benchmark(